@@ -0,0 +1,307 @@
+use std::{rc::Rc, num::NonZeroU32};
+
+use crate::utils::*;
+use crate::mipmap::mip_level_count;
+
+/// Format of the frame given to the bloom renderer, and of its mip chain (HDR, so that bright areas are not clamped).
+pub const BLOOM_INPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Parameters of the bloom post-effect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomSettings {
+    /// Amount of bloom added to the frame.
+    pub intensity: f32,
+    /// Brightness above which pixels start to bloom.
+    pub threshold: f32,
+    /// Width of the smooth transition around the threshold.
+    pub soft_knee: f32,
+    /// Spread of the upsampling filter, in texels of each level.
+    pub radius: f32,
+    /// Maximum count of levels of the downsample chain, more levels give a wider glow.
+    pub max_levels: u32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            intensity: 0.6,
+            threshold: 0.8,
+            soft_knee: 0.4,
+            radius: 1.0,
+            max_levels: 6,
+        }
+    }
+}
+
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+struct UniformData {
+    threshold: f32,
+    soft_knee: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+/// Bloom post-effect, insertable after the interlace merge: the frame is rendered to `input_texture`, then `draw` composites it with its glow to the output.
+///
+/// Bright areas are extracted to half resolution, blurred down a mip chain with a dual filter, and accumulated back up the chain.
+pub struct BloomRenderer {
+    width: u32,
+    height: u32,
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    settings: BloomSettings,
+    input_texture: wgpu::Texture,
+    /// Half resolution mip chain, each level is downsampled from the previous one, then accumulates the upsampled next one.
+    chain_texture: wgpu::Texture,
+    chain_views: Vec<wgpu::TextureView>,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    prefilter_bind_group: wgpu::BindGroup,
+    /// Bind group reading each level of the chain.
+    chain_bind_groups: Vec<wgpu::BindGroup>,
+    composite_bind_group: wgpu::BindGroup,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+/// Record a pass drawing a fullscreen triangle.
+fn record_bloom_pass(encoder: &mut wgpu::CommandEncoder, label: &str, view: &wgpu::TextureView, load: wgpu::LoadOp<wgpu::Color>, pipeline: &wgpu::RenderPipeline, bind_group: &wgpu::BindGroup) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+impl BloomRenderer {
+    pub fn new(device: Rc<wgpu::Device>, queue: Rc<wgpu::Queue>, width: u32, height: u32, target: wgpu::TextureFormat, settings: BloomSettings) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Bloom uniform buffer"),
+            size: std::mem::size_of::<UniformData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let sampler = create_sampler(&device, Some("Bloom sampler"), wgpu::FilterMode::Linear);
+
+        let texture_binding = wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        };
+
+        let bind_group_layout = create_bind_group_layout(&device, Some("Bloom bind group layout"),
+            vec![
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                texture_binding,
+                texture_binding,
+            ],
+        wgpu::ShaderStages::FRAGMENT);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Bloom shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/bloom.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, fragment_entry_point: &'static str, target: wgpu::TextureFormat, blend: wgpu::BlendState| {
+            create_render_pipeline_with_options(&device, Some(label), &[], &pipeline_layout, &shader, target, RenderPipelineOptions {
+                blend: Some(blend),
+                fragment_entry_point,
+                ..Default::default()
+            })
+        };
+
+        let additive_blend = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+
+        let prefilter_pipeline = create_pipeline("Bloom prefilter pipeline", "fs_prefilter", BLOOM_INPUT_FORMAT, wgpu::BlendState::REPLACE);
+        let downsample_pipeline = create_pipeline("Bloom downsample pipeline", "fs_downsample", BLOOM_INPUT_FORMAT, wgpu::BlendState::REPLACE);
+        let upsample_pipeline = create_pipeline("Bloom upsample pipeline", "fs_upsample", BLOOM_INPUT_FORMAT, additive_blend);
+        let composite_pipeline = create_pipeline("Bloom composite pipeline", "fs_composite", target, wgpu::BlendState::REPLACE);
+
+        let textures = Self::create_textures(&device, &sampler, &uniform_buffer, &bind_group_layout, width, height, settings.max_levels);
+
+        let bloom = Self {
+            width,
+            height,
+            device,
+            queue,
+            settings,
+            input_texture: textures.input_texture,
+            chain_texture: textures.chain_texture,
+            chain_views: textures.chain_views,
+            sampler,
+            uniform_buffer,
+            bind_group_layout,
+            prefilter_bind_group: textures.prefilter_bind_group,
+            chain_bind_groups: textures.chain_bind_groups,
+            composite_bind_group: textures.composite_bind_group,
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+        };
+
+        bloom.write_settings();
+        bloom
+    }
+
+    fn create_textures(device: &wgpu::Device, sampler: &wgpu::Sampler, uniform_buffer: &wgpu::Buffer, bind_group_layout: &wgpu::BindGroupLayout, width: u32, height: u32, max_levels: u32) -> BloomTextures {
+        let input_texture = device.create_texture(&texture_descriptor(Some("Bloom input texture"), width.max(1), height.max(1), BLOOM_INPUT_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC));
+
+        let chain_width = (width / 2).max(1);
+        let chain_height = (height / 2).max(1);
+        let level_count = mip_level_count(chain_width, chain_height).min(max_levels.max(1));
+        let chain_texture = device.create_texture(&wgpu::TextureDescriptor {
+            mip_level_count: level_count,
+            ..texture_descriptor(Some("Bloom chain texture"), chain_width, chain_height, BLOOM_INPUT_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+        });
+
+        let chain_views: Vec<wgpu::TextureView> = (0..level_count).map(|level| chain_texture.create_view(&wgpu::TextureViewDescriptor {
+            base_mip_level: level,
+            mip_level_count: NonZeroU32::new(1),
+            ..Default::default()
+        })).collect();
+
+        let input_view = input_texture.create_view(&Default::default());
+
+        let create_bloom_bind_group = |label: &str, source: &wgpu::TextureView, bloom: &wgpu::TextureView| {
+            create_bind_group(device, Some(label), bind_group_layout, vec![
+                uniform_buffer.as_entire_binding(),
+                wgpu::BindingResource::Sampler(sampler),
+                wgpu::BindingResource::TextureView(source),
+                wgpu::BindingResource::TextureView(bloom),
+            ])
+        };
+
+        // the last binding is only read by the composite, but must not be the level being rendered to
+        let prefilter_bind_group = create_bloom_bind_group("Bloom prefilter bind group", &input_view, &input_view);
+        let chain_bind_groups = chain_views.iter().map(|view| create_bloom_bind_group("Bloom chain bind group", view, view)).collect();
+        let composite_bind_group = create_bloom_bind_group("Bloom composite bind group", &input_view, &chain_views[0]);
+
+        BloomTextures {
+            input_texture,
+            chain_texture,
+            chain_views,
+            prefilter_bind_group,
+            chain_bind_groups,
+            composite_bind_group,
+        }
+    }
+
+    fn recreate_textures(&mut self) {
+        let textures = Self::create_textures(&self.device, &self.sampler, &self.uniform_buffer, &self.bind_group_layout, self.width, self.height, self.settings.max_levels);
+        self.input_texture = textures.input_texture;
+        self.chain_texture = textures.chain_texture;
+        self.chain_views = textures.chain_views;
+        self.prefilter_bind_group = textures.prefilter_bind_group;
+        self.chain_bind_groups = textures.chain_bind_groups;
+        self.composite_bind_group = textures.composite_bind_group;
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.recreate_textures();
+    }
+
+    pub fn settings(&self) -> BloomSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: BloomSettings) {
+        let levels_changed = settings.max_levels != self.settings.max_levels;
+        self.settings = settings;
+        self.write_settings();
+
+        if levels_changed {
+            self.recreate_textures();
+        }
+    }
+
+    fn write_settings(&self) {
+        let uniform_data = UniformData {
+            threshold: self.settings.threshold,
+            soft_knee: self.settings.soft_knee,
+            intensity: self.settings.intensity,
+            radius: self.settings.radius,
+        };
+
+        self.queue.write_buffer(&self.uniform_buffer, 0, unsafe { any_as_u8_slice(&uniform_data) });
+    }
+
+    /// Texture (of `BLOOM_INPUT_FORMAT`) the frame must be rendered to before calling `draw`.
+    pub fn input_texture(&self) -> &wgpu::Texture {
+        &self.input_texture
+    }
+
+    /// Count of levels of the downsample chain (limited by `BloomSettings::max_levels` and the frame size).
+    pub fn level_count(&self) -> u32 {
+        self.chain_texture.mip_level_count()
+    }
+
+    /// Render the input texture with its bloom to a texture of the target format given at creation.
+    pub fn draw(&mut self, output_view: &wgpu::TextureView) {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Bloom encoder"),
+        });
+
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+
+        record_bloom_pass(&mut encoder, "Bloom prefilter pass", &self.chain_views[0], clear, &self.prefilter_pipeline, &self.prefilter_bind_group);
+
+        for level in 1..self.chain_views.len() {
+            record_bloom_pass(&mut encoder, "Bloom downsample pass", &self.chain_views[level], clear, &self.downsample_pipeline, &self.chain_bind_groups[level - 1]);
+        }
+
+        // accumulate from the smallest level up to the first one
+        for level in (0..self.chain_views.len() - 1).rev() {
+            record_bloom_pass(&mut encoder, "Bloom upsample pass", &self.chain_views[level], wgpu::LoadOp::Load, &self.upsample_pipeline, &self.chain_bind_groups[level + 1]);
+        }
+
+        record_bloom_pass(&mut encoder, "Bloom composite pass", output_view, clear, &self.composite_pipeline, &self.composite_bind_group);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+struct BloomTextures {
+    input_texture: wgpu::Texture,
+    chain_texture: wgpu::Texture,
+    chain_views: Vec<wgpu::TextureView>,
+    prefilter_bind_group: wgpu::BindGroup,
+    chain_bind_groups: Vec<wgpu::BindGroup>,
+    composite_bind_group: wgpu::BindGroup,
+}
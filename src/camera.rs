@@ -0,0 +1,470 @@
+use glam::{Mat3, Mat4, Vec2, Vec3};
+
+const MIN_ZOOM: f32 = 0.01;
+const MAX_ZOOM: f32 = 1000.0;
+
+/// 2D camera of the scenes drawn in world units: at zoom 1, the view is 2 units high centered on `position`, y up.
+///
+/// The width of the view follows the aspect ratio of the viewport, so that shapes are not stretched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera2d {
+    /// World position at the center of the view.
+    pub position: Vec2,
+    pub zoom: f32,
+    /// Counterclockwise rotation of the view in radians.
+    pub rotation: f32,
+    viewport_width: u32,
+    viewport_height: u32,
+}
+
+/// Camera as read by shaders (see `Camera2d` in `shader.wgsl`).
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+pub struct Camera2dUniform {
+    /// Columns of a mat3x3, padded to 4 floats.
+    clip_to_world: [[f32; 4]; 3],
+    world_to_clip: [[f32; 4]; 3],
+    position: [f32; 2],
+    zoom: f32,
+    rotation: f32,
+    /// Size of a pixel of the viewport in world units, for anti-aliasing.
+    pixel_size: f32,
+    padding: [f32; 3],
+}
+
+fn padded_columns(matrix: Mat3) -> [[f32; 4]; 3] {
+    [matrix.x_axis.extend(0.0).to_array(), matrix.y_axis.extend(0.0).to_array(), matrix.z_axis.extend(0.0).to_array()]
+}
+
+impl Camera2d {
+    pub fn new(viewport_width: u32, viewport_height: u32) -> Self {
+        Self {
+            position: Vec2::ZERO,
+            zoom: 1.0,
+            rotation: 0.0,
+            viewport_width: viewport_width.max(1),
+            viewport_height: viewport_height.max(1),
+        }
+    }
+
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport_width = width.max(1);
+        self.viewport_height = height.max(1);
+    }
+
+    /// Width over height of the viewport.
+    pub fn aspect(&self) -> f32 {
+        self.viewport_width as f32 / self.viewport_height as f32
+    }
+
+    /// Back to the origin, without zoom nor rotation.
+    pub fn reset(&mut self) {
+        self.position = Vec2::ZERO;
+        self.zoom = 1.0;
+        self.rotation = 0.0;
+    }
+
+    /// Transform of clip space positions (normalized device coordinates) to world positions.
+    pub fn clip_to_world(&self) -> Mat3 {
+        Mat3::from_translation(self.position) * Mat3::from_angle(self.rotation) * Mat3::from_scale(Vec2::new(self.aspect(), 1.0) / self.zoom)
+    }
+
+    pub fn world_to_clip(&self) -> Mat3 {
+        self.clip_to_world().inverse()
+    }
+
+    /// World position under a point of the viewport, in normalized device coordinates.
+    pub fn clip_to_world_point(&self, ndc: Vec2) -> Vec2 {
+        self.clip_to_world().transform_point2(ndc)
+    }
+
+    /// Size of a pixel of the viewport in world units.
+    pub fn pixel_size(&self) -> f32 {
+        2.0 / (self.zoom * self.viewport_height as f32)
+    }
+
+    /// Multiply the zoom by `factor`, keeping the world position under `ndc` in place.
+    pub fn zoom_at(&mut self, ndc: Vec2, factor: f32) {
+        let anchor = self.clip_to_world_point(ndc);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.position += anchor - self.clip_to_world_point(ndc);
+    }
+
+    /// Move the view so that the scene follows a drag of `delta` pixels of the viewport (y down).
+    pub fn drag(&mut self, delta: Vec2) {
+        let ndc_delta = Vec2::new(2.0 * delta.x / self.viewport_width as f32, -2.0 * delta.y / self.viewport_height as f32);
+        self.position -= self.clip_to_world().transform_vector2(ndc_delta);
+    }
+
+    /// Move the view by a distance in view heights, along the axes of the view (rotated with it).
+    pub fn pan(&mut self, delta: Vec2) {
+        self.position += Mat3::from_angle(self.rotation).transform_vector2(delta * 2.0 / self.zoom);
+    }
+
+    /// The rotation is kept in [-pi; pi[.
+    pub fn rotate(&mut self, angle: f32) {
+        self.rotation = (self.rotation + angle + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+    }
+
+    pub fn uniform(&self) -> Camera2dUniform {
+        Camera2dUniform {
+            clip_to_world: padded_columns(self.clip_to_world()),
+            world_to_clip: padded_columns(self.world_to_clip()),
+            position: self.position.to_array(),
+            zoom: self.zoom,
+            rotation: self.rotation,
+            pixel_size: self.pixel_size(),
+            padding: [0.0; 3],
+        }
+    }
+}
+
+/// How a `Camera3d` moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Turns around `target` at `distance`.
+    Orbit,
+    /// Moves freely from `position`.
+    Fly,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// Vertical field of view in radians.
+    Perspective { fov_y: f32 },
+    /// Height of the view in world units.
+    Orthographic { height: f32 },
+}
+
+/// 3D camera with a right-handed y up world, looking down -z at zero yaw and pitch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera3d {
+    mode: CameraMode,
+    pub projection: Projection,
+    /// Point looked at in orbit mode.
+    pub target: Vec3,
+    /// Distance to `target` in orbit mode.
+    pub distance: f32,
+    /// Position in fly mode.
+    pub position: Vec3,
+    /// Rotation around the y axis in radians, counterclockwise seen from above.
+    pub yaw: f32,
+    /// Rotation above the horizon in radians, kept within (-pi/2; pi/2).
+    pub pitch: f32,
+    pub near: f32,
+    pub far: f32,
+    /// Sub-pixel offset of the samples in clip space units, such as the per-field offset of the interlaced renderer.
+    jitter: Vec2,
+    viewport_width: u32,
+    viewport_height: u32,
+}
+
+/// Camera as read by shaders (see `Camera3d` in the 3D shaders).
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+pub struct Camera3dUniform {
+    view: [f32; 16],
+    /// Includes the jitter.
+    projection: [f32; 16],
+    view_projection: [f32; 16],
+    /// Maps clip space positions (with their depth) back to the world, to generate rays.
+    inverse_view_projection: [f32; 16],
+    position: [f32; 3],
+    /// 1 for an orthographic projection: rays are parallel.
+    orthographic: u32,
+}
+
+/// Pitch limit, looking straight up or down makes the view direction parallel to the up vector.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+impl Camera3d {
+    /// Orbit camera looking at the origin from 5 units away, with a 60 degrees perspective.
+    pub fn new(viewport_width: u32, viewport_height: u32) -> Self {
+        Self {
+            mode: CameraMode::Orbit,
+            projection: Projection::Perspective { fov_y: 60f32.to_radians() },
+            target: Vec3::ZERO,
+            distance: 5.0,
+            position: Vec3::new(0.0, 0.0, 5.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            near: 0.1,
+            far: 100.0,
+            jitter: Vec2::ZERO,
+            viewport_width: viewport_width.max(1),
+            viewport_height: viewport_height.max(1),
+        }
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: CameraMode) -> Self {
+        self.set_mode(mode);
+        self
+    }
+
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport_width = width.max(1);
+        self.viewport_height = height.max(1);
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.viewport_width as f32 / self.viewport_height as f32
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Switch mode without moving the camera: the orbit target is put in front of the camera at the orbit distance.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        match (self.mode, mode) {
+            (CameraMode::Orbit, CameraMode::Fly) => self.position = self.eye(),
+            (CameraMode::Fly, CameraMode::Orbit) => self.target = self.position + self.forward() * self.distance,
+            _ => {},
+        }
+        self.mode = mode;
+    }
+
+    pub fn jitter(&self) -> Vec2 {
+        self.jitter
+    }
+
+    /// Offset the samples by a fraction of pixel, in clip space units (see `InterlacedRendererState::scene_offset`).
+    pub fn set_jitter(&mut self, jitter: Vec2) {
+        self.jitter = jitter;
+    }
+
+    /// Unit view direction.
+    pub fn forward(&self) -> Vec3 {
+        Vec3::new(-self.yaw.sin() * self.pitch.cos(), self.pitch.sin(), -self.yaw.cos() * self.pitch.cos())
+    }
+
+    /// Unit direction to the right of the view, always horizontal.
+    pub fn right(&self) -> Vec3 {
+        Vec3::new(self.yaw.cos(), 0.0, -self.yaw.sin())
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.right().cross(self.forward())
+    }
+
+    /// Position of the camera.
+    pub fn eye(&self) -> Vec3 {
+        match self.mode {
+            CameraMode::Orbit => self.target - self.forward() * self.distance,
+            CameraMode::Fly => self.position,
+        }
+    }
+
+    /// Turn the view by angles in radians: around the target in orbit mode, in place in fly mode.
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Move along the right, up and forward axes of the view: the target is panned in orbit mode (getting closer changes the distance instead).
+    pub fn translate(&mut self, delta: Vec3) {
+        let (right, up, forward) = (self.right(), self.up(), self.forward());
+
+        match self.mode {
+            CameraMode::Orbit => {
+                self.target += right * delta.x + up * delta.y;
+                self.distance = (self.distance - delta.z).max(self.near);
+            },
+            CameraMode::Fly => self.position += right * delta.x + up * delta.y + forward * delta.z,
+        }
+    }
+
+    /// Multiply the orbit distance (or the height of an orthographic view) by `factor`.
+    pub fn zoom(&mut self, factor: f32) {
+        match (self.mode, &mut self.projection) {
+            (_, Projection::Orthographic { height }) => *height = (*height * factor).max(0.001),
+            (CameraMode::Orbit, _) => self.distance = (self.distance * factor).max(self.near),
+            (CameraMode::Fly, _) => {},
+        }
+    }
+
+    pub fn view(&self) -> Mat4 {
+        let eye = self.eye();
+        Mat4::look_at_rh(eye, eye + self.forward(), Vec3::Y)
+    }
+
+    /// Projection to wgpu clip space (depth in [0; 1]), including the jitter.
+    pub fn projection(&self) -> Mat4 {
+        let projection = match self.projection {
+            Projection::Perspective { fov_y } => Mat4::perspective_rh(fov_y, self.aspect(), self.near, self.far),
+            Projection::Orthographic { height } => {
+                let (half_width, half_height) = (height * self.aspect() / 2.0, height / 2.0);
+                Mat4::orthographic_rh(-half_width, half_width, -half_height, half_height, self.near, self.far)
+            },
+        };
+
+        // geometry moves the opposite way of the samples
+        Mat4::from_translation((-self.jitter).extend(0.0)) * projection
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection() * self.view()
+    }
+
+    pub fn uniform(&self) -> Camera3dUniform {
+        let view_projection = self.view_projection();
+
+        Camera3dUniform {
+            view: self.view().to_cols_array(),
+            projection: self.projection().to_cols_array(),
+            view_projection: view_projection.to_cols_array(),
+            inverse_view_projection: view_projection.inverse().to_cols_array(),
+            position: self.eye().to_array(),
+            orthographic: matches!(self.projection, Projection::Orthographic { .. }) as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec2, b: Vec2) {
+        assert!((a - b).length() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn zoom_at_keeps_anchor() {
+        let mut camera = Camera2d::new(1600, 900);
+        camera.position = Vec2::new(3.0, -2.0);
+        camera.rotation = 0.7;
+
+        for (ndc, factor) in [(Vec2::new(0.5, -0.25), 2.0), (Vec2::new(-1.0, 1.0), 0.3), (Vec2::ZERO, 1.5)] {
+            let anchor = camera.clip_to_world_point(ndc);
+            camera.zoom_at(ndc, factor);
+            assert_near(camera.clip_to_world_point(ndc), anchor);
+        }
+    }
+
+    #[test]
+    fn zoom_at_clamps_zoom() {
+        let mut camera = Camera2d::new(100, 100);
+        let anchor = camera.clip_to_world_point(Vec2::new(0.5, 0.5));
+
+        camera.zoom_at(Vec2::new(0.5, 0.5), 1e6);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+        assert_near(camera.clip_to_world_point(Vec2::new(0.5, 0.5)), anchor);
+
+        camera.zoom_at(Vec2::ZERO, 1e-9);
+        assert_eq!(camera.zoom, MIN_ZOOM);
+    }
+
+    #[test]
+    fn aspect_correction() {
+        let mut camera = Camera2d::new(1600, 800);
+
+        // 2 units high, as wide as the viewport
+        assert_near(camera.clip_to_world_point(Vec2::new(1.0, 1.0)), Vec2::new(2.0, 1.0));
+        assert_near(camera.clip_to_world_point(Vec2::new(-1.0, -1.0)), Vec2::new(-2.0, -1.0));
+
+        // square pixels
+        let pixel_width = camera.clip_to_world().transform_vector2(Vec2::new(2.0 / 1600.0, 0.0)).length();
+        assert!((pixel_width - camera.pixel_size()).abs() < 1e-6);
+
+        camera.set_viewport(400, 800);
+        camera.zoom = 2.0;
+        assert_near(camera.clip_to_world_point(Vec2::new(1.0, 1.0)), Vec2::new(0.25, 0.5));
+    }
+
+    #[test]
+    fn world_to_clip_inverts_clip_to_world() {
+        let mut camera = Camera2d::new(1280, 720);
+        camera.position = Vec2::new(-1.0, 4.0);
+        camera.zoom = 3.0;
+        camera.rotate(2.0);
+
+        let ndc = Vec2::new(0.3, -0.8);
+        assert_near(camera.world_to_clip().transform_point2(camera.clip_to_world_point(ndc)), ndc);
+    }
+
+    fn assert_near3(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{} != {}", a, b);
+    }
+
+    fn camera3d() -> Camera3d {
+        let mut camera = Camera3d::new(1280, 720);
+        camera.target = Vec3::new(1.0, 0.5, -2.0);
+        camera.rotate(0.8, -0.3);
+        camera
+    }
+
+    #[test]
+    fn jitter_shifts_ndc() {
+        let point = Vec3::new(0.3, 0.7, -1.5);
+        let jitter = Vec2::new(0.002, -0.003);
+
+        for projection in [Projection::Perspective { fov_y: 1.0 }, Projection::Orthographic { height: 4.0 }] {
+            let mut camera = camera3d().with_projection(projection);
+            let ndc = camera.view_projection().project_point3(point);
+
+            camera.set_jitter(jitter);
+            let jittered = camera.view_projection().project_point3(point);
+
+            assert_near(jittered.truncate(), ndc.truncate() - jitter);
+            assert!((jittered.z - ndc.z).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn mode_switch_keeps_view() {
+        let mut camera = camera3d();
+        let (eye, forward) = (camera.eye(), camera.forward());
+
+        camera.set_mode(CameraMode::Fly);
+        assert_near3(camera.eye(), eye);
+        assert_near3(camera.forward(), forward);
+
+        // moved in fly mode, the orbit target is put in front of the camera
+        camera.translate(Vec3::new(1.0, -2.0, 0.5));
+        let (eye, forward) = (camera.eye(), camera.forward());
+
+        camera.set_mode(CameraMode::Orbit);
+        assert_near3(camera.eye(), eye);
+        assert_near3(camera.forward(), forward);
+        assert_near3(camera.target, eye + forward * camera.distance);
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut camera = Camera3d::new(100, 100);
+
+        camera.rotate(0.0, 10.0);
+        assert_eq!(camera.pitch, MAX_PITCH);
+        // never parallel to the up vector
+        assert!(camera.forward().dot(Vec3::Y) < 1.0);
+        assert!(camera.view().is_finite());
+
+        camera.rotate(0.0, -20.0);
+        assert_eq!(camera.pitch, -MAX_PITCH);
+    }
+
+    #[test]
+    fn inverse_view_projection_round_trip() {
+        for projection in [Projection::Perspective { fov_y: 1.0 }, Projection::Orthographic { height: 4.0 }] {
+            let mut camera = camera3d().with_projection(projection);
+            camera.set_jitter(Vec2::new(0.001, 0.002));
+
+            let uniform = camera.uniform();
+            let view_projection = Mat4::from_cols_array(&uniform.view_projection);
+            let inverse_view_projection = Mat4::from_cols_array(&uniform.inverse_view_projection);
+
+            for point in [Vec3::new(0.3, 0.7, -1.5), Vec3::new(-2.0, 1.0, 3.0), camera.target] {
+                assert_near3(inverse_view_projection.project_point3(view_projection.project_point3(point)), point);
+            }
+
+            // the center of the view on the near plane (moved by the jitter) is in front of the eye
+            let near_center = inverse_view_projection.project_point3((-camera.jitter()).extend(0.0));
+            assert!((near_center - camera.eye()).dot(camera.forward()) > 0.0);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+/// Frame time is considered over budget above this ratio of the target.
+const OVER_BUDGET_RATIO: f32 = 1.05;
+/// Frame time is considered to leave enough headroom to scale up below this ratio of the target.
+const HEADROOM_RATIO: f32 = 0.8;
+/// Weight of the newest frame time in the smoothed frame time.
+const SMOOTHING: f32 = 0.1;
+
+/// Chooses the render scale of the interlaced fields so that frame time stays within a target budget.
+pub struct DynamicResolutionController {
+    target_frame_time: Duration,
+    min_scale: f32,
+    max_scale: f32,
+    /// Scale changes by this amount at once. The scale is always a multiple of it, so that only a few texture sizes are used.
+    step: f32,
+    /// Minimum count of frames between two changes, to let the frame time settle.
+    cooldown: u32,
+    scale: f32,
+    smoothed_frame_time: Option<f32>,
+    frames_since_change: u32,
+}
+
+impl DynamicResolutionController {
+    pub fn new(target_frame_time: Duration) -> Self {
+        Self {
+            target_frame_time,
+            min_scale: 0.5,
+            max_scale: 1.0,
+            step: 0.1,
+            cooldown: 30,
+            scale: 1.0,
+            smoothed_frame_time: None,
+            frames_since_change: 0,
+        }
+    }
+
+    /// Panics unless 0 < `min_scale` <= `max_scale`.
+    pub fn with_scale_range(mut self, min_scale: f32, max_scale: f32) -> Self {
+        assert!(min_scale > 0.0 && min_scale <= max_scale, "the render scale range must be positive and ordered, got {}..{}", min_scale, max_scale);
+        self.min_scale = min_scale;
+        self.max_scale = max_scale;
+        self.scale = self.scale.clamp(min_scale, max_scale);
+        self
+    }
+
+    /// Panics if the step is not strictly positive, as the scale is snapped to a multiple of it.
+    pub fn with_step(mut self, step: f32, cooldown: u32) -> Self {
+        assert!(step > 0.0, "the step of the render scale must be positive, got {}", step);
+        self.step = step;
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn target_frame_time(&self) -> Duration {
+        self.target_frame_time
+    }
+
+    pub fn set_target_frame_time(&mut self, target_frame_time: Duration) {
+        self.target_frame_time = target_frame_time;
+    }
+
+    /// Forget the frame time history and go back to the maximum scale.
+    pub fn reset(&mut self) {
+        self.scale = self.max_scale;
+        self.smoothed_frame_time = None;
+        self.frames_since_change = 0;
+    }
+
+    /// Feed the time taken by the last frame. Returns the new scale when it changes.
+    pub fn update(&mut self, frame_time: Duration) -> Option<f32> {
+        let frame_time = frame_time.as_secs_f32();
+        let smoothed = match self.smoothed_frame_time {
+            Some(smoothed) => smoothed + (frame_time - smoothed) * SMOOTHING,
+            None => frame_time,
+        };
+        self.smoothed_frame_time = Some(smoothed);
+
+        self.frames_since_change += 1;
+        if self.frames_since_change < self.cooldown {
+            return None;
+        }
+
+        let target = self.target_frame_time.as_secs_f32();
+        let mut scale = self.scale;
+
+        if smoothed > target * OVER_BUDGET_RATIO {
+            scale -= self.step;
+        } else if smoothed < target * HEADROOM_RATIO {
+            scale += self.step;
+        }
+
+        // snap to a multiple of the step, to avoid accumulating rounding errors
+        scale = ((scale / self.step).round() * self.step).clamp(self.min_scale, self.max_scale);
+
+        if (scale - self.scale).abs() < f32::EPSILON {
+            return None;
+        }
+
+        self.scale = scale;
+        self.frames_since_change = 0;
+        Some(scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic]
+    fn zero_step_is_rejected() {
+        DynamicResolutionController::new(Duration::from_millis(16)).with_step(0.0, 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn nan_step_is_rejected() {
+        DynamicResolutionController::new(Duration::from_millis(16)).with_step(f32::NAN, 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn reversed_scale_range_is_rejected() {
+        DynamicResolutionController::new(Duration::from_millis(16)).with_scale_range(0.8, 0.5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn nan_scale_range_is_rejected() {
+        DynamicResolutionController::new(Duration::from_millis(16)).with_scale_range(f32::NAN, 1.0);
+    }
+
+    fn assert_scale(scale: Option<f32>, expected: f32) {
+        assert!(matches!(scale, Some(scale) if (scale - expected).abs() < 1e-5), "{:?} != {}", scale, expected);
+    }
+
+    #[test]
+    fn steps_down_over_budget() {
+        let mut controller = DynamicResolutionController::new(Duration::from_millis(10)).with_step(0.1, 3);
+
+        assert_eq!(controller.update(Duration::from_millis(20)), None);
+        assert_eq!(controller.update(Duration::from_millis(20)), None);
+        assert_scale(controller.update(Duration::from_millis(20)), 0.9);
+
+        // down to the minimum scale, and no further
+        let scales: Vec<f32> = (0..30).filter_map(|_| controller.update(Duration::from_millis(20))).collect();
+        assert_eq!(scales.len(), 4);
+        assert!((controller.scale() - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn steps_up_with_headroom() {
+        let mut controller = DynamicResolutionController::new(Duration::from_millis(10)).with_step(0.1, 1);
+        (0..5).for_each(|_| { controller.update(Duration::from_millis(30)); });
+        assert!(controller.scale() < 0.65);
+
+        // the smoothed frame time has to fall below the headroom before scaling up
+        assert_eq!(controller.update(Duration::from_millis(1)), None);
+
+        let scales: Vec<f32> = (0..100).filter_map(|_| controller.update(Duration::from_millis(1))).collect();
+        assert!(scales.windows(2).all(|pair| pair[1] > pair[0]), "{:?}", scales);
+        assert!((controller.scale() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn stays_within_budget() {
+        let mut controller = DynamicResolutionController::new(Duration::from_millis(10)).with_step(0.1, 1);
+
+        // between the headroom and the budget
+        assert!((0..50).all(|_| controller.update(Duration::from_millis(9)).is_none()));
+        assert_eq!(controller.scale(), 1.0);
+    }
+
+    #[test]
+    fn cooldown_after_change() {
+        let mut controller = DynamicResolutionController::new(Duration::from_millis(10)).with_step(0.1, 5);
+        let results: Vec<Option<f32>> = (0..15).map(|_| controller.update(Duration::from_millis(50))).collect();
+
+        let changes: Vec<usize> = results.iter().enumerate().filter(|(_, scale)| scale.is_some()).map(|(i, _)| i).collect();
+        assert_eq!(changes, vec![4, 9, 14]);
+    }
+
+    #[test]
+    fn scale_snaps_to_steps() {
+        // the maximum is not a multiple of the step, the first change snaps to one
+        let mut controller = DynamicResolutionController::new(Duration::from_millis(10)).with_scale_range(0.3, 0.93).with_step(0.25, 1);
+        assert!((controller.scale() - 0.93).abs() < 1e-5);
+
+        assert_scale(controller.update(Duration::from_millis(50)), 0.75);
+        assert_scale(controller.update(Duration::from_millis(50)), 0.5);
+        // 0.25 is below the minimum
+        assert_scale(controller.update(Duration::from_millis(50)), 0.3);
+        assert_eq!(controller.update(Duration::from_millis(50)), None);
+
+        controller.reset();
+        assert!((controller.scale() - 0.93).abs() < 1e-5);
+    }
+}
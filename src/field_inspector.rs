@@ -0,0 +1,479 @@
+use std::num::NonZeroU32;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver};
+
+use crate::mipmap::{create_texture_with_mips, MipmapGenerator};
+use crate::utils::*;
+
+/// From 1/2, fields are shown from their mip chain.
+pub const MIN_ZOOM: f32 = 0.125;
+pub const MAX_ZOOM: f32 = 64.0;
+
+/// What the field inspector shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InspectorView {
+    /// First field on the left half of the output, second field on the right half, so that the same texels of both fields can be
+    /// compared at a glance. At native resolution, only the left half of each field fits.
+    SideBySide,
+    /// First field on the top half of the output, second field on the bottom half: at native resolution, each one fits in its half.
+    Stacked,
+    /// Absolute difference between the fields, over the whole output.
+    Difference,
+}
+
+/// Texel of the fields under an output pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InspectedTexel {
+    /// Field shown under the pixel, 0 for the first one (always 0 in the difference view).
+    pub field: usize,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Values of a texel in both fields, as read back from the GPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TexelValues {
+    pub x: u32,
+    pub y: u32,
+    pub fields: [[u8; 4]; 2],
+}
+
+/// Read back of a texel of both fields which is not mapped yet.
+struct PendingRead {
+    x: u32,
+    y: u32,
+    mapped: Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+struct UniformData {
+    output_size: [f32; 2],
+    pan: [f32; 2],
+    cursor: [f32; 2],
+    zoom: f32,
+    difference_scale: f32,
+    view: u32,
+    current_field: u32,
+    /// Mip level of the fields shown, above 0 when zoomed out.
+    level: u32,
+    padding: u32,
+}
+
+/// Debug view of the two field textures of the interlaced renderer at native resolution, drawn instead of the merged frame.
+///
+/// The field rendered this frame is highlighted, and the view can be zoomed and panned to inspect single texels.
+/// When zoomed out, the fields are copied to textures with a mip chain so that they are not aliased.
+pub struct FieldInspector {
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    mipmap_generator: MipmapGenerator,
+    /// Copies of the fields with a mip chain, only used when zoomed out.
+    field_mips: Option<[wgpu::Texture; 2]>,
+    /// One aligned row per field, to read texels without blocking.
+    readback_buffer: wgpu::Buffer,
+    pending_read: Option<PendingRead>,
+    last_read: Option<TexelValues>,
+    view: InspectorView,
+    /// Output pixels per texel.
+    zoom: f32,
+    /// Texel at the top left corner of each view.
+    pan: [f32; 2],
+    difference_scale: f32,
+}
+
+impl FieldInspector {
+    pub fn new(device: Rc<wgpu::Device>, queue: Rc<wgpu::Queue>, target: wgpu::TextureFormat) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Field inspector uniform buffer"),
+            size: std::mem::size_of::<UniformData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // fields are read with textureLoad, texel by texel
+        let texture_binding = wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+        };
+
+        let bind_group_layout = create_bind_group_layout(&device, Some("Field inspector bind group layout"),
+            vec![
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                texture_binding,
+                texture_binding,
+            ],
+        wgpu::ShaderStages::FRAGMENT);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Field inspector shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/field_inspector.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Field inspector pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = create_render_pipeline(&device, Some("Field inspector pipeline"), &[], &pipeline_layout, &shader, target);
+        let mipmap_generator = MipmapGenerator::new(&device);
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Field inspector readback buffer"),
+            size: 2 * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            device,
+            queue,
+            uniform_buffer,
+            bind_group_layout,
+            pipeline,
+            mipmap_generator,
+            field_mips: None,
+            readback_buffer,
+            pending_read: None,
+            last_read: None,
+            view: InspectorView::SideBySide,
+            zoom: MIN_ZOOM,
+            pan: [0.0, 0.0],
+            difference_scale: 4.0,
+        }
+    }
+
+    pub fn view(&self) -> InspectorView {
+        self.view
+    }
+
+    pub fn set_view(&mut self, view: InspectorView) {
+        self.view = view;
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Output pixels per texel, clamped to [1/8; 64].
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    /// Multiply the zoom by `factor`, keeping the texel under the `cursor` output pixel in place.
+    pub fn zoom_at(&mut self, cursor: [f32; 2], output_size: [u32; 2], factor: f32) {
+        let local = self.local_position(cursor, output_size);
+        let texel = [local[0] / self.zoom + self.pan[0], local[1] / self.zoom + self.pan[1]];
+
+        self.set_zoom(self.zoom * factor);
+        self.pan = [texel[0] - local[0] / self.zoom, texel[1] - local[1] / self.zoom];
+    }
+
+    /// Texel at the top left corner of each view.
+    pub fn pan(&self) -> [f32; 2] {
+        self.pan
+    }
+
+    pub fn set_pan(&mut self, pan: [f32; 2]) {
+        self.pan = pan;
+    }
+
+    /// Move the fields by a distance in output pixels, such as a mouse drag.
+    pub fn pan_by(&mut self, delta: [f32; 2]) {
+        self.pan[0] -= delta[0] / self.zoom;
+        self.pan[1] -= delta[1] / self.zoom;
+    }
+
+    /// Show the fields at native resolution from their top left corner.
+    pub fn reset_zoom(&mut self) {
+        self.zoom = MIN_ZOOM;
+        self.pan = [0.0, 0.0];
+    }
+
+    pub fn difference_scale(&self) -> f32 {
+        self.difference_scale
+    }
+
+    /// Multiplier of the difference view, small differences are hard to see otherwise.
+    pub fn set_difference_scale(&mut self, difference_scale: f32) {
+        self.difference_scale = difference_scale;
+    }
+
+    /// Size of the view of each field, in output pixels.
+    fn view_size(&self, output_size: [u32; 2]) -> [u32; 2] {
+        match self.view {
+            InspectorView::SideBySide => [output_size[0] / 2, output_size[1]],
+            InspectorView::Stacked => [output_size[0], output_size[1] / 2],
+            InspectorView::Difference => output_size,
+        }
+    }
+
+    /// Position of an output pixel in its view, and the index of the view.
+    fn view_position(&self, pixel: [f32; 2], output_size: [u32; 2]) -> (usize, [f32; 2]) {
+        let view_size = self.view_size(output_size);
+        let view_size = [view_size[0] as f32, view_size[1] as f32];
+
+        match self.view {
+            InspectorView::SideBySide if pixel[0] >= view_size[0] => (1, [pixel[0] - view_size[0], pixel[1]]),
+            InspectorView::Stacked if pixel[1] >= view_size[1] => (1, [pixel[0], pixel[1] - view_size[1]]),
+            _ => (0, pixel),
+        }
+    }
+
+    fn local_position(&self, pixel: [f32; 2], output_size: [u32; 2]) -> [f32; 2] {
+        self.view_position(pixel, output_size).1
+    }
+
+    /// Texel shown under an output pixel, None outside of the fields.
+    pub fn texel_at(&self, pixel: [f32; 2], output_size: [u32; 2], field_size: [u32; 2]) -> Option<InspectedTexel> {
+        let (field, local) = self.view_position(pixel, output_size);
+        let x = (local[0] / self.zoom + self.pan[0]).floor();
+        let y = (local[1] / self.zoom + self.pan[1]).floor();
+
+        if x < 0.0 || y < 0.0 || x >= field_size[0] as f32 || y >= field_size[1] as f32 {
+            return None;
+        }
+
+        Some(InspectedTexel {
+            field,
+            x: x as u32,
+            y: y as u32,
+        })
+    }
+
+    /// Values of a texel in both fields, without blocking: returns the last values read back, which can be of a previous frame or of
+    /// another texel (see `TexelValues`), and starts reading the given texel if no read is in flight. Needs 4 bytes per texel formats.
+    pub fn read_texels(&mut self, fields: [&wgpu::Texture; 2], x: u32, y: u32) -> Option<TexelValues> {
+        self.device.poll(wgpu::Maintain::Poll);
+
+        if let Some(pending) = &self.pending_read {
+            match pending.mapped.try_recv() {
+                Ok(result) => {
+                    result.expect("failed to map the field inspector readback buffer");
+
+                    {
+                        let data = self.readback_buffer.slice(..).get_mapped_range();
+                        let row = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+                        let texel = |offset: usize| [data[offset], data[offset + 1], data[offset + 2], data[offset + 3]];
+                        self.last_read = Some(TexelValues { x: pending.x, y: pending.y, fields: [texel(0), texel(row)] });
+                    }
+                    self.readback_buffer.unmap();
+                    self.pending_read = None;
+                },
+                // still in flight
+                Err(_) => return self.last_read,
+            }
+        }
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Field inspector readback encoder"),
+        });
+
+        for (index, field) in fields.iter().enumerate() {
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture: field,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x, y, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &self.readback_buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: index as u64 * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+                        bytes_per_row: NonZeroU32::new(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            );
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = channel();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            // the inspector may be gone
+            sender.send(result).ok();
+        });
+        self.pending_read = Some(PendingRead { x, y, mapped: receiver });
+
+        self.last_read
+    }
+
+    /// Copy the fields to `field_mips` and fill their mip chains, returns the level to show at the current zoom.
+    fn prepare_field_mips(&mut self, encoder: &mut wgpu::CommandEncoder, fields: [&wgpu::Texture; 2]) -> u32 {
+        let up_to_date = matches!(&self.field_mips, Some(mips) if mips[0].size() == fields[0].size() && mips[0].format() == fields[0].format());
+
+        if !up_to_date {
+            let create = |label| create_texture_with_mips(&self.device, Some(label), fields[0].width(), fields[0].height(), fields[0].format(), wgpu::TextureUsages::COPY_DST);
+            self.field_mips = Some([create("Field inspector first field mips"), create("Field inspector second field mips")]);
+        }
+
+        let mips = self.field_mips.as_ref().unwrap();
+
+        for (field, mip) in fields.iter().zip(mips) {
+            encoder.copy_texture_to_texture(field.as_image_copy(), mip.as_image_copy(), field.size());
+            self.mipmap_generator.generate(&self.device, encoder, mip);
+        }
+
+        // one level per halving of the zoom
+        ((1.0 / self.zoom).log2().floor() as u32).min(mips[0].mip_level_count() - 1)
+    }
+
+    /// Draw the fields to the whole output, highlighting `current_field` and the texel under the `cursor` output pixel.
+    pub fn draw(&mut self, output_view: &wgpu::TextureView, output_size: [u32; 2], fields: [&wgpu::Texture; 2], current_field: usize, cursor: Option<[f32; 2]>) {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Field inspector encoder"),
+        });
+
+        // the first mip level is only needed from half the native resolution
+        let level = if self.zoom <= 0.5 { self.prepare_field_mips(&mut encoder, fields) } else { 0 };
+
+        let uniform_data = UniformData {
+            output_size: [output_size[0] as f32, output_size[1] as f32],
+            pan: self.pan,
+            cursor: cursor.unwrap_or([-1.0, -1.0]),
+            zoom: self.zoom,
+            difference_scale: self.difference_scale,
+            view: match self.view {
+                InspectorView::SideBySide => 0,
+                InspectorView::Stacked => 1,
+                InspectorView::Difference => 2,
+            },
+            current_field: current_field as u32,
+            level,
+            padding: 0,
+        };
+        self.queue.write_buffer(&self.uniform_buffer, 0, unsafe { any_as_u8_slice(&uniform_data) });
+
+        // fields are recreated on resize and render scale changes
+        let shown = match &self.field_mips {
+            Some(mips) if level > 0 => [&mips[0], &mips[1]],
+            _ => fields,
+        };
+        let views = [shown[0].create_view(&Default::default()), shown[1].create_view(&Default::default())];
+        let bind_group = create_bind_group(&self.device, Some("Field inspector bind group"), &self.bind_group_layout,
+            vec![
+                self.uniform_buffer.as_entire_binding(),
+                wgpu::BindingResource::TextureView(&views[0]),
+                wgpu::BindingResource::TextureView(&views[1]),
+            ]
+        );
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Field inspector pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT_SIZE: [u32; 2] = [800, 600];
+    const FIELD_SIZE: [u32; 2] = [1600, 600];
+
+    fn inspector(view: InspectorView) -> Option<FieldInspector> {
+        let (device, queue) = test_device()?;
+        let mut inspector = FieldInspector::new(Rc::new(device), Rc::new(queue), wgpu::TextureFormat::Rgba8Unorm);
+        inspector.set_view(view);
+        // native resolution
+        inspector.set_zoom(1.0);
+        Some(inspector)
+    }
+
+    fn texel(field: usize, x: u32, y: u32) -> Option<InspectedTexel> {
+        Some(InspectedTexel { field, x, y })
+    }
+
+    #[test]
+    fn side_by_side_halves() {
+        let Some(inspector) = inspector(InspectorView::SideBySide) else { return };
+        assert_eq!(inspector.view(), InspectorView::SideBySide);
+
+        assert_eq!(inspector.texel_at([399.5, 10.5], OUTPUT_SIZE, FIELD_SIZE), texel(0, 399, 10));
+        assert_eq!(inspector.texel_at([400.0, 10.5], OUTPUT_SIZE, FIELD_SIZE), texel(1, 0, 10));
+        assert_eq!(inspector.texel_at([799.5, 599.5], OUTPUT_SIZE, FIELD_SIZE), texel(1, 399, 599));
+    }
+
+    #[test]
+    fn stacked_halves() {
+        let Some(inspector) = inspector(InspectorView::Stacked) else { return };
+
+        assert_eq!(inspector.texel_at([10.5, 299.5], OUTPUT_SIZE, FIELD_SIZE), texel(0, 10, 299));
+        assert_eq!(inspector.texel_at([10.5, 300.0], OUTPUT_SIZE, FIELD_SIZE), texel(1, 10, 0));
+        // the fields are taller than their view
+        assert_eq!(inspector.texel_at([799.5, 599.5], OUTPUT_SIZE, [800, 200]), None);
+    }
+
+    #[test]
+    fn difference_covers_the_output() {
+        let Some(inspector) = inspector(InspectorView::Difference) else { return };
+
+        assert_eq!(inspector.texel_at([700.5, 500.5], OUTPUT_SIZE, FIELD_SIZE), texel(0, 700, 500));
+    }
+
+    #[test]
+    fn zoom_and_pan() {
+        let Some(mut inspector) = inspector(InspectorView::SideBySide) else { return };
+        inspector.set_zoom(4.0);
+        inspector.set_pan([10.0, 20.0]);
+
+        assert_eq!(inspector.texel_at([0.0, 0.0], OUTPUT_SIZE, FIELD_SIZE), texel(0, 10, 20));
+        assert_eq!(inspector.texel_at([7.9, 4.0], OUTPUT_SIZE, FIELD_SIZE), texel(0, 11, 21));
+        assert_eq!(inspector.texel_at([408.0, 4.0], OUTPUT_SIZE, FIELD_SIZE), texel(1, 12, 21));
+
+        // outside of the fields
+        inspector.set_pan([-1.0, 0.0]);
+        assert_eq!(inspector.texel_at([3.9, 0.0], OUTPUT_SIZE, FIELD_SIZE), None);
+        assert_eq!(inspector.texel_at([4.0, 0.0], OUTPUT_SIZE, FIELD_SIZE), texel(0, 0, 0));
+
+        // a drag moves the texels with the cursor
+        inspector.pan_by([8.0, -4.0]);
+        assert_eq!(inspector.pan(), [-3.0, 1.0]);
+    }
+
+    #[test]
+    fn zoom_keeps_the_texel_under_the_cursor() {
+        let Some(mut inspector) = inspector(InspectorView::SideBySide) else { return };
+        inspector.set_zoom(2.0);
+        inspector.set_pan([5.0, 5.0]);
+
+        let cursor = [500.0, 300.0];
+        let before = inspector.texel_at(cursor, OUTPUT_SIZE, FIELD_SIZE);
+        inspector.zoom_at(cursor, OUTPUT_SIZE, 4.0);
+        assert_eq!(inspector.zoom(), 8.0);
+        assert_eq!(inspector.texel_at(cursor, OUTPUT_SIZE, FIELD_SIZE), before);
+
+        // clamped
+        inspector.zoom_at(cursor, OUTPUT_SIZE, 1000.0);
+        assert_eq!(inspector.zoom(), MAX_ZOOM);
+        assert_eq!(inspector.texel_at(cursor, OUTPUT_SIZE, FIELD_SIZE), before);
+    }
+}
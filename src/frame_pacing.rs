@@ -0,0 +1,244 @@
+use std::time::{Duration, Instant};
+
+use winit::event_loop::ControlFlow;
+
+/// Default part of the wait before a frame which is spent spinning instead of sleeping, as sleeping is not precise enough.
+const DEFAULT_SPIN_THRESHOLD: Duration = Duration::from_micros(1500);
+
+/// Schedules frames at a steady rate, with an optional cap and an idle mode.
+///
+/// Intended use with winit: `should_render` on `MainEventsCleared` to request a redraw, `wait_for_frame` then `frame_rendered` around
+/// the rendering on `RedrawRequested`, and `control_flow` on `RedrawEventsCleared`.
+pub struct FramePacer {
+    /// None when frames are not capped.
+    target_frame_time: Option<Duration>,
+    spin_threshold: Duration,
+    /// Time at which the next frame should start.
+    next_frame: Instant,
+    /// When idle, frames are only rendered after `request_frame`.
+    idle: bool,
+    frame_requested: bool,
+}
+
+/// Time between two frames at the given rate. Rates which are not strictly positive and finite mean uncapped.
+fn frame_time(target_fps: Option<f64>) -> Option<Duration> {
+    target_fps
+        .filter(|fps| fps.is_finite() && *fps > 0.0)
+        .map(|fps| Duration::from_secs_f64(1.0 / fps))
+}
+
+impl FramePacer {
+    /// Frames are not capped when the target is None, or not strictly positive and finite.
+    pub fn new(target_fps: Option<f64>) -> Self {
+        Self {
+            target_frame_time: frame_time(target_fps),
+            spin_threshold: DEFAULT_SPIN_THRESHOLD,
+            next_frame: Instant::now(),
+            idle: false,
+            frame_requested: true,
+        }
+    }
+
+    pub fn with_spin_threshold(mut self, spin_threshold: Duration) -> Self {
+        self.spin_threshold = spin_threshold;
+        self
+    }
+
+    pub fn target_fps(&self) -> Option<f64> {
+        self.target_frame_time.map(|frame_time| 1.0 / frame_time.as_secs_f64())
+    }
+
+    /// Cap the frame rate, None (or a rate which is not strictly positive and finite) for uncapped: frames are then only limited by the present mode.
+    pub fn set_target_fps(&mut self, target_fps: Option<f64>) {
+        self.target_frame_time = frame_time(target_fps);
+        self.next_frame = Instant::now();
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// In idle mode, nothing is rendered until something changes (see `request_frame`).
+    pub fn set_idle(&mut self, idle: bool) {
+        self.idle = idle;
+        self.frame_requested = true;
+    }
+
+    /// Ask for a frame because something changed, needed to render in idle mode.
+    pub fn request_frame(&mut self) {
+        self.frame_requested = true;
+    }
+
+    /// Whether a frame should be started now. Returns true slightly before the frame time, the rest is waited by `wait_for_frame`.
+    pub fn should_render(&self, now: Instant) -> bool {
+        if self.idle && !self.frame_requested {
+            return false;
+        }
+
+        match self.target_frame_time {
+            Some(_) => now + self.spin_threshold >= self.next_frame,
+            None => true,
+        }
+    }
+
+    /// Block until the frame time: sleep most of the remaining time, then spin for precision.
+    pub fn wait_for_frame(&self) {
+        if self.target_frame_time.is_none() {
+            return;
+        }
+
+        loop {
+            let now = Instant::now();
+
+            if now >= self.next_frame {
+                break;
+            }
+
+            let remaining = self.next_frame - now;
+
+            if remaining > self.spin_threshold {
+                std::thread::sleep(remaining - self.spin_threshold);
+            } else {
+                std::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Schedule the next frame, to call once a frame has been rendered.
+    pub fn frame_rendered(&mut self, now: Instant) {
+        self.frame_requested = false;
+
+        if let Some(frame_time) = self.target_frame_time {
+            // keep a steady rate, but do not try to catch up when more than a frame late
+            self.next_frame = if now > self.next_frame + frame_time { now } else { self.next_frame + frame_time };
+        }
+    }
+
+    /// How the event loop should wait for the next frame.
+    pub fn control_flow(&self) -> ControlFlow {
+        if self.idle && !self.frame_requested {
+            return ControlFlow::Wait;
+        }
+
+        match self.target_frame_time {
+            // wake up early enough to spin until the frame time
+            Some(_) => ControlFlow::WaitUntil(self.next_frame.checked_sub(self.spin_threshold).unwrap_or(self.next_frame)),
+            None => ControlFlow::Poll,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_rates_are_uncapped() {
+        for fps in [0.0, -60.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(FramePacer::new(Some(fps)).target_fps(), None);
+
+            let mut pacer = FramePacer::new(Some(60.0));
+            pacer.set_target_fps(Some(fps));
+            assert_eq!(pacer.target_fps(), None);
+        }
+    }
+
+    #[test]
+    fn valid_rate_is_capped() {
+        let pacer = FramePacer::new(Some(120.0));
+        assert!((pacer.target_fps().unwrap() - 120.0).abs() < 1e-3);
+    }
+
+    const FRAME_TIME: Duration = Duration::from_millis(10);
+    const SPIN_THRESHOLD: Duration = Duration::from_millis(1);
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// Pacer at 100 fps, with the time at which its first frame is due.
+    fn capped_pacer() -> (FramePacer, Instant) {
+        let pacer = FramePacer::new(Some(1.0 / FRAME_TIME.as_secs_f64())).with_spin_threshold(SPIN_THRESHOLD);
+        let start = pacer.next_frame;
+        (pacer, start)
+    }
+
+    #[test]
+    fn uncapped_renders_every_frame() {
+        let mut pacer = FramePacer::new(None);
+        let start = Instant::now();
+
+        for frame in 0..3 {
+            assert!(pacer.should_render(start + ms(frame)));
+            pacer.frame_rendered(start + ms(frame));
+            assert_eq!(pacer.control_flow(), ControlFlow::Poll);
+        }
+    }
+
+    #[test]
+    fn capped_frames_start_a_spin_threshold_early() {
+        let (mut pacer, start) = capped_pacer();
+        assert!(pacer.should_render(start));
+        pacer.frame_rendered(start);
+
+        assert!(!pacer.should_render(start + ms(8)));
+        assert!(pacer.should_render(start + FRAME_TIME - SPIN_THRESHOLD));
+        assert_eq!(pacer.control_flow(), ControlFlow::WaitUntil(start + FRAME_TIME - SPIN_THRESHOLD));
+    }
+
+    #[test]
+    fn capped_rate_is_steady() {
+        let (mut pacer, start) = capped_pacer();
+        pacer.frame_rendered(start);
+
+        // rendered late, but within a frame: the next frame is still on the grid
+        pacer.frame_rendered(start + ms(15));
+        assert_eq!(pacer.next_frame, start + ms(20));
+        pacer.frame_rendered(start + ms(29));
+        assert_eq!(pacer.next_frame, start + ms(30));
+
+        // more than a frame late: no catching up
+        pacer.frame_rendered(start + ms(45));
+        assert_eq!(pacer.next_frame, start + ms(45));
+        assert!(!pacer.should_render(start + ms(43)));
+    }
+
+    #[test]
+    fn idle_waits_for_requests() {
+        let (mut pacer, start) = capped_pacer();
+        pacer.set_idle(true);
+        assert!(pacer.is_idle());
+
+        // entering idle mode renders a frame
+        assert!(pacer.should_render(start));
+        pacer.frame_rendered(start);
+
+        assert!(!pacer.should_render(start + ms(100)));
+        assert_eq!(pacer.control_flow(), ControlFlow::Wait);
+
+        // requested frames keep the rate
+        pacer.request_frame();
+        assert!(!pacer.should_render(start + ms(5)));
+        assert!(pacer.should_render(start + FRAME_TIME));
+        assert_eq!(pacer.control_flow(), ControlFlow::WaitUntil(start + FRAME_TIME - SPIN_THRESHOLD));
+        pacer.frame_rendered(start + FRAME_TIME);
+        assert_eq!(pacer.control_flow(), ControlFlow::Wait);
+
+        pacer.set_idle(false);
+        assert!(pacer.should_render(start + FRAME_TIME * 2));
+    }
+
+    #[test]
+    fn idle_uncapped() {
+        let mut pacer = FramePacer::new(None);
+        let start = Instant::now();
+        pacer.set_idle(true);
+        pacer.frame_rendered(start);
+        assert!(!pacer.should_render(start));
+        assert_eq!(pacer.control_flow(), ControlFlow::Wait);
+
+        pacer.request_frame();
+        assert!(pacer.should_render(start));
+        assert_eq!(pacer.control_flow(), ControlFlow::Poll);
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Statistics of the frame times currently in the window of a series.
+#[derive(Clone, Copy, Debug)]
+pub struct TimingSummary {
+    pub count: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+/// Rolling window of the times of one measurement (CPU frame time, GPU pass time...).
+pub struct TimingSeries {
+    name: &'static str,
+    times: VecDeque<Duration>,
+    capacity: usize,
+}
+
+impl TimingSeries {
+    fn new(name: &'static str, capacity: usize) -> Self {
+        Self {
+            name,
+            times: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Times in the window, oldest first.
+    pub fn times(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.times.iter().copied()
+    }
+
+    fn record(&mut self, time: Duration) {
+        if self.times.len() == self.capacity {
+            self.times.pop_front();
+        }
+
+        self.times.push_back(time);
+    }
+
+    /// None when nothing was recorded yet.
+    pub fn summary(&self) -> Option<TimingSummary> {
+        if self.times.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<Duration> = self.times.iter().copied().collect();
+        sorted.sort_unstable();
+
+        // nearest rank percentile
+        let percentile = |p: f64| sorted[((p / 100.0 * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+
+        Some(TimingSummary {
+            count: sorted.len(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+        })
+    }
+
+    /// Count of times per bucket of `bucket_width`, the last bucket also counts every longer time.
+    pub fn histogram(&self, bucket_width: Duration, bucket_count: usize) -> Vec<u32> {
+        let mut buckets = vec![0; bucket_count];
+
+        if bucket_count == 0 {
+            return buckets;
+        }
+
+        for time in &self.times {
+            let bucket = (time.as_nanos() / bucket_width.as_nanos().max(1)) as usize;
+            buckets[bucket.min(bucket_count - 1)] += 1;
+        }
+
+        buckets
+    }
+}
+
+/// Collects frame times in named rolling windows and reports them periodically through `log` and optionally to a CSV file.
+pub struct FrameStats {
+    series: Vec<TimingSeries>,
+    window_size: usize,
+    histogram_bucket_width: Duration,
+    histogram_bucket_count: usize,
+    report_interval: Duration,
+    start: Instant,
+    last_report: Instant,
+    csv: Option<BufWriter<File>>,
+}
+
+impl FrameStats {
+    /// Keep the last `window_size` times of each series.
+    pub fn new(window_size: usize) -> Self {
+        let now = Instant::now();
+
+        Self {
+            series: vec![],
+            window_size: window_size.max(1),
+            histogram_bucket_width: Duration::from_millis(1),
+            histogram_bucket_count: 34,
+            report_interval: Duration::from_secs(1),
+            start: now,
+            last_report: now,
+            csv: None,
+        }
+    }
+
+    pub fn with_histogram(mut self, bucket_width: Duration, bucket_count: usize) -> Self {
+        self.histogram_bucket_width = bucket_width;
+        self.histogram_bucket_count = bucket_count;
+        self
+    }
+
+    pub fn with_report_interval(mut self, report_interval: Duration) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+
+    /// Also append every report to a CSV file, one line per series.
+    pub fn with_csv_export<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        let mut csv = BufWriter::new(File::create(path)?);
+        writeln!(csv, "time_s,series,count,min_ms,max_ms,mean_ms,p50_ms,p95_ms,p99_ms")?;
+        self.csv = Some(csv);
+        Ok(self)
+    }
+
+    /// Add a time to a series, created on first use.
+    pub fn record(&mut self, series: &'static str, time: Duration) {
+        let index = match self.series.iter().position(|s| s.name == series) {
+            Some(index) => index,
+            None => {
+                self.series.push(TimingSeries::new(series, self.window_size));
+                self.series.len() - 1
+            }
+        };
+
+        self.series[index].record(time);
+    }
+
+    /// Every series, in order of creation.
+    pub fn all_series(&self) -> &[TimingSeries] {
+        &self.series
+    }
+
+    pub fn series(&self, name: &str) -> Option<&TimingSeries> {
+        self.series.iter().find(|s| s.name == name)
+    }
+
+    pub fn summary(&self, name: &str) -> Option<TimingSummary> {
+        self.series(name).and_then(TimingSeries::summary)
+    }
+
+    pub fn histogram(&self, name: &str) -> Option<Vec<u32>> {
+        self.series(name).map(|s| s.histogram(self.histogram_bucket_width, self.histogram_bucket_count))
+    }
+
+    /// Report the statistics if the report interval elapsed since the last report.
+    pub fn report_if_due(&mut self, now: Instant) {
+        if now.duration_since(self.last_report) >= self.report_interval {
+            self.last_report = now;
+            self.report(now);
+        }
+    }
+
+    fn report(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.start).as_secs_f64();
+
+        for series in &self.series {
+            let Some(summary) = series.summary() else { continue };
+
+            log::info!(
+                "{}: {} frames, min {:.3} ms, max {:.3} ms, mean {:.3} ms ({:.1} fps), p50 {:.3} ms, p95 {:.3} ms, p99 {:.3} ms",
+                series.name, summary.count, ms(summary.min), ms(summary.max), ms(summary.mean), 1000.0 / ms(summary.mean), ms(summary.p50), ms(summary.p95), ms(summary.p99)
+            );
+            log::debug!("{} histogram ({:?} buckets): {:?}", series.name, self.histogram_bucket_width, series.histogram(self.histogram_bucket_width, self.histogram_bucket_count));
+
+            if let Some(csv) = &mut self.csv {
+                let result = writeln!(
+                    csv,
+                    "{:.3},{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3}",
+                    elapsed, series.name, summary.count, ms(summary.min), ms(summary.max), ms(summary.mean), ms(summary.p50), ms(summary.p95), ms(summary.p99)
+                );
+
+                if let Err(e) = result {
+                    log::error!("frame stats CSV export failed, disabling it: {}", e);
+                    self.csv = None;
+                }
+            }
+        }
+
+        if let Some(csv) = &mut self.csv {
+            if let Err(e) = csv.flush() {
+                log::error!("frame stats CSV export failed, disabling it: {}", e);
+                self.csv = None;
+            }
+        }
+    }
+}
+
+fn ms(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(values: impl IntoIterator<Item = u64>) -> Vec<Duration> {
+        values.into_iter().map(Duration::from_millis).collect()
+    }
+
+    fn stats_with(times: &[Duration]) -> FrameStats {
+        let mut stats = FrameStats::new(times.len().max(1)).with_histogram(Duration::from_millis(1), 4);
+        for &time in times {
+            stats.record("frame", time);
+        }
+        stats
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        // recorded out of order
+        let mut times = millis(1..=100);
+        times.reverse();
+        let summary = stats_with(&times).summary("frame").unwrap();
+
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.min, Duration::from_millis(1));
+        assert_eq!(summary.max, Duration::from_millis(100));
+        assert_eq!(summary.mean, Duration::from_micros(50_500));
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p95, Duration::from_millis(95));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+
+        // ranks are rounded up on small windows
+        let summary = stats_with(&millis(1..=10)).summary("frame").unwrap();
+        assert_eq!(summary.p50, Duration::from_millis(5));
+        assert_eq!(summary.p95, Duration::from_millis(10));
+        assert_eq!(summary.p99, Duration::from_millis(10));
+
+        let summary = stats_with(&millis([7])).summary("frame").unwrap();
+        assert_eq!((summary.min, summary.p50, summary.p99, summary.max), (Duration::from_millis(7), Duration::from_millis(7), Duration::from_millis(7), Duration::from_millis(7)));
+    }
+
+    #[test]
+    fn rolling_window() {
+        let mut stats = FrameStats::new(3);
+        assert!(stats.summary("frame").is_none());
+
+        for time in millis([100, 1, 2, 3]) {
+            stats.record("frame", time);
+        }
+
+        // the oldest time left the window
+        assert_eq!(stats.series("frame").unwrap().times().collect::<Vec<_>>(), millis([1, 2, 3]));
+        assert_eq!(stats.summary("frame").unwrap().max, Duration::from_millis(3));
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        let times = [Duration::from_micros(500), Duration::from_millis(1), Duration::from_micros(1900), Duration::from_millis(3), Duration::from_millis(10)];
+        let stats = stats_with(&times);
+
+        // the last bucket also counts the longer times
+        assert_eq!(stats.histogram("frame").unwrap(), vec![1, 2, 0, 2]);
+        assert_eq!(stats.series("frame").unwrap().histogram(Duration::from_millis(1), 0), Vec::<u32>::new());
+        assert!(stats.histogram("gpu").is_none());
+    }
+
+    #[test]
+    fn series_in_order_of_creation() {
+        let mut stats = FrameStats::new(4);
+        stats.record("cpu", Duration::from_millis(1));
+        stats.record("gpu", Duration::from_millis(2));
+        stats.record("cpu", Duration::from_millis(3));
+
+        let names: Vec<&str> = stats.all_series().iter().map(TimingSeries::name).collect();
+        assert_eq!(names, vec!["cpu", "gpu"]);
+        assert_eq!(stats.summary("cpu").unwrap().count, 2);
+    }
+}
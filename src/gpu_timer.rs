@@ -0,0 +1,274 @@
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::frame_stats::FrameStats;
+
+/// Frames whose timestamps can be waiting for readback at the same time. Frames started while all are busy are not timed.
+const FRAMES_IN_FLIGHT: usize = 3;
+const TIMESTAMP_SIZE: u64 = std::mem::size_of::<u64>() as u64;
+
+type MapResult = Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>;
+
+/// Handle of a scope begun with `GpuTimer::begin_scope`.
+pub struct GpuScope {
+    index: u32,
+}
+
+/// GPU times of the scopes of a frame, read back by `GpuTimer::collect`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GpuFrameTimes {
+    scopes: Vec<(&'static str, Duration)>,
+}
+
+impl GpuFrameTimes {
+    /// Time of the scope with the given label, summed if it was begun several times in the frame.
+    pub fn get(&self, label: &str) -> Option<Duration> {
+        self.scopes.iter().filter(|(scope, _)| *scope == label).map(|&(_, time)| time).reduce(|a, b| a + b)
+    }
+
+    /// Sum of the times of the scopes with the given labels, None unless all of them were measured in the frame.
+    pub fn total(&self, labels: &[&str]) -> Option<Duration> {
+        labels.iter().map(|label| self.get(label)).sum()
+    }
+}
+
+enum SlotState {
+    Free,
+    Recording,
+    /// Waiting for the readback buffer to be mapped.
+    Mapping(MapResult),
+}
+
+struct FrameSlot {
+    readback_buffer: wgpu::Buffer,
+    /// Label of each scope of the frame, and whether it was ended.
+    scopes: Vec<(&'static str, bool)>,
+    state: SlotState,
+}
+
+struct Queries {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    slots: Vec<FrameSlot>,
+    current_slot: Option<usize>,
+    /// Nanoseconds per timestamp tick.
+    timestamp_period: f32,
+}
+
+/// Measures the GPU time of the work submitted between the begin and the end of scopes, with timestamp queries.
+///
+/// Timestamps are written in their own submissions, so a scope times everything submitted in between, whichever component submits it.
+/// Results are read back asynchronously a few frames later, by `collect`. Without `TIMESTAMP_QUERY`, every method does nothing.
+pub struct GpuTimer {
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    max_scopes: u32,
+    queries: Option<Queries>,
+}
+
+impl GpuTimer {
+    /// `max_scopes` is the maximum count of scopes per frame.
+    pub fn new(device: Rc<wgpu::Device>, queue: Rc<wgpu::Queue>, max_scopes: u32) -> Self {
+        let queries = if device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            Some(create_queries(&device, &queue, max_scopes))
+        } else {
+            log::warn!("GPU timer: timestamp queries are not supported, GPU times will not be measured");
+            None
+        };
+
+        Self {
+            device,
+            queue,
+            max_scopes,
+            queries,
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    /// Start timing a new frame. Does nothing if all readback buffers are still in use.
+    pub fn begin_frame(&mut self) {
+        let Some(queries) = &mut self.queries else { return };
+
+        if queries.current_slot.is_some() {
+            return;
+        }
+
+        if let Some(index) = queries.slots.iter().position(|slot| matches!(slot.state, SlotState::Free)) {
+            let slot = &mut queries.slots[index];
+            slot.scopes.clear();
+            slot.state = SlotState::Recording;
+            queries.current_slot = Some(index);
+        }
+    }
+
+    /// Start timing the work submitted from now on. None if the frame is not timed.
+    pub fn begin_scope(&mut self, label: &'static str) -> Option<GpuScope> {
+        let queries = self.queries.as_mut()?;
+        let slot_index = queries.current_slot?;
+        let slot = &mut queries.slots[slot_index];
+
+        if slot.scopes.len() as u32 >= self.max_scopes {
+            return None;
+        }
+
+        let index = slot.scopes.len() as u32;
+        slot.scopes.push((label, false));
+        self.write_timestamp(slot_index, index * 2);
+
+        Some(GpuScope { index })
+    }
+
+    /// Stop timing the work of a scope.
+    pub fn end_scope(&mut self, scope: Option<GpuScope>) {
+        let Some(scope) = scope else { return };
+        let Some(queries) = &mut self.queries else { return };
+        let Some(slot_index) = queries.current_slot else { return };
+
+        queries.slots[slot_index].scopes[scope.index as usize].1 = true;
+        self.write_timestamp(slot_index, scope.index * 2 + 1);
+    }
+
+    /// Submit the readback of the timestamps of the frame.
+    pub fn end_frame(&mut self) {
+        let Some(queries) = &mut self.queries else { return };
+        let Some(slot_index) = queries.current_slot.take() else { return };
+        let slot = &mut queries.slots[slot_index];
+
+        if slot.scopes.is_empty() {
+            slot.state = SlotState::Free;
+            return;
+        }
+
+        let first_query = slot_index as u32 * self.max_scopes * 2;
+        let query_count = slot.scopes.len() as u32 * 2;
+        let size = query_count as u64 * TIMESTAMP_SIZE;
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("GPU timer resolve encoder"),
+        });
+        encoder.resolve_query_set(&queries.query_set, first_query..first_query + query_count, &queries.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&queries.resolve_buffer, 0, &slot.readback_buffer, 0, size);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let map_result: MapResult = Arc::new(Mutex::new(None));
+        let callback_result = map_result.clone();
+        slot.readback_buffer.slice(..size).map_async(wgpu::MapMode::Read, move |result| {
+            *callback_result.lock().unwrap() = Some(result);
+        });
+        slot.state = SlotState::Mapping(map_result);
+    }
+
+    /// Record the times of the frames whose readback finished into `stats`, one series per scope label, and return them. Never blocks.
+    pub fn collect(&mut self, stats: &mut FrameStats) -> Vec<GpuFrameTimes> {
+        let mut frames = vec![];
+        let Some(queries) = &mut self.queries else { return frames };
+
+        self.device.poll(wgpu::Maintain::Poll);
+
+        for slot in &mut queries.slots {
+            let SlotState::Mapping(map_result) = &slot.state else { continue };
+            let Some(result) = map_result.lock().unwrap().take() else { continue };
+
+            if result.is_ok() {
+                let size = slot.scopes.len() as u64 * 2 * TIMESTAMP_SIZE;
+
+                {
+                    let data = slot.readback_buffer.slice(..size).get_mapped_range();
+                    let timestamps: &[u64] = bytemuck::cast_slice(&data);
+                    let mut frame = GpuFrameTimes::default();
+
+                    for (i, &(label, ended)) in slot.scopes.iter().enumerate() {
+                        let (start, end) = (timestamps[i * 2], timestamps[i * 2 + 1]);
+
+                        // timestamps of some drivers are not monotonic across submissions
+                        if ended && end >= start {
+                            let time = Duration::from_nanos(((end - start) as f64 * queries.timestamp_period as f64) as u64);
+                            stats.record(label, time);
+                            frame.scopes.push((label, time));
+                        }
+                    }
+
+                    frames.push(frame);
+                }
+
+                slot.readback_buffer.unmap();
+            }
+
+            slot.state = SlotState::Free;
+        }
+
+        frames
+    }
+
+    fn write_timestamp(&self, slot_index: usize, query: u32) {
+        let Some(queries) = &self.queries else { return };
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("GPU timer timestamp encoder"),
+        });
+        encoder.write_timestamp(&queries.query_set, slot_index as u32 * self.max_scopes * 2 + query);
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+fn create_queries(device: &wgpu::Device, queue: &wgpu::Queue, max_scopes: u32) -> Queries {
+    let queries_per_frame = max_scopes * 2;
+
+    let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+        label: Some("GPU timer query set"),
+        ty: wgpu::QueryType::Timestamp,
+        count: queries_per_frame * FRAMES_IN_FLIGHT as u32,
+    });
+
+    let buffer_size = queries_per_frame as u64 * TIMESTAMP_SIZE;
+
+    let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("GPU timer resolve buffer"),
+        size: buffer_size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+
+    let slots = (0..FRAMES_IN_FLIGHT)
+        .map(|_| FrameSlot {
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("GPU timer readback buffer"),
+                size: buffer_size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            scopes: vec![],
+            state: SlotState::Free,
+        })
+        .collect();
+
+    Queries {
+        query_set,
+        resolve_buffer,
+        slots,
+        current_slot: None,
+        timestamp_period: queue.get_timestamp_period(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_totals() {
+        let frame = GpuFrameTimes {
+            scopes: vec![("scene", Duration::from_millis(3)), ("merge", Duration::from_millis(1)), ("scene", Duration::from_millis(2))],
+        };
+
+        assert_eq!(frame.get("scene"), Some(Duration::from_millis(5)));
+        assert_eq!(frame.total(&["scene", "merge"]), Some(Duration::from_millis(6)));
+        // a frame without the scene, such as a paused one, is not comparable
+        assert_eq!(frame.total(&["scene", "bloom"]), None);
+        assert_eq!(GpuFrameTimes::default().get("scene"), None);
+    }
+}
@@ -0,0 +1,528 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::utils::{create_bind_group, create_bind_group_layout, any_as_u8_slice, read_texture, texture_descriptor};
+
+/// Side of the square windows over which SSIM is computed (they do not overlap).
+const WINDOW_SIZE: u32 = 8;
+/// SSIM stabilization constants, for values in [0; 1].
+const SSIM_C1: f64 = 0.01 * 0.01;
+const SSIM_C2: f64 = 0.03 * 0.03;
+
+/// Similarity of a test image to a reference image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageMetrics {
+    /// Mean squared error of the RGB channels, in [0; 1].
+    pub mse: f64,
+    /// Peak signal-to-noise ratio in dB, infinite for identical images.
+    pub psnr: f64,
+    /// Mean structural similarity of the luma over 8x8 windows, 1 for identical images.
+    pub ssim: f64,
+}
+
+impl ImageMetrics {
+    fn from_windows(windows: impl Iterator<Item = (f64, f64, f64)>) -> Self {
+        let (mut squared_error, mut ssim, mut pixel_count, mut window_count) = (0.0, 0.0, 0.0, 0);
+
+        for (window_squared_error, window_ssim, window_pixel_count) in windows {
+            if window_pixel_count > 0.0 {
+                squared_error += window_squared_error;
+                ssim += window_ssim;
+                pixel_count += window_pixel_count;
+                window_count += 1;
+            }
+        }
+
+        let mse = squared_error / (3.0 * pixel_count).max(1.0);
+
+        Self {
+            mse,
+            psnr: if mse > 0.0 { 10.0 * (1.0 / mse).log10() } else { f64::INFINITY },
+            ssim: if window_count > 0 { ssim / window_count as f64 } else { 1.0 },
+        }
+    }
+}
+
+impl fmt::Display for ImageMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PSNR {:.2} dB, SSIM {:.4}, MSE {:.6}", self.psnr, self.ssim, self.mse)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageMetricsError {
+    /// The reference and test images do not have the same size.
+    SizeMismatch,
+    /// Only 8 bits RGBA and BGRA formats can be read back by the CPU fallback.
+    UnsupportedFormat(wgpu::TextureFormat),
+    /// A texture lacks the usages needed by the backend.
+    MissingUsage(wgpu::TextureUsages),
+}
+
+impl fmt::Display for ImageMetricsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageMetricsError::SizeMismatch => write!(f, "the reference and test images do not have the same size"),
+            ImageMetricsError::UnsupportedFormat(format) => write!(f, "{:?} images are not supported by the CPU image metrics", format),
+            ImageMetricsError::MissingUsage(usage) => write!(f, "the images need the {:?} usage", usage),
+        }
+    }
+}
+
+impl std::error::Error for ImageMetricsError {}
+
+/// Where image metrics are computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricsBackend {
+    /// Compute shader, only the window sums are read back.
+    Gpu,
+    /// Both images are read back and compared on the CPU, for adapters without compute shaders.
+    Cpu,
+}
+
+impl MetricsBackend {
+    /// GPU if the adapter supports compute shaders, CPU otherwise.
+    pub fn for_adapter(adapter: &wgpu::Adapter) -> Self {
+        if adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) { MetricsBackend::Gpu } else { MetricsBackend::Cpu }
+    }
+}
+
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+struct UniformData {
+    width: u32,
+    height: u32,
+    heatmap_scale: f32,
+    padding: u32,
+}
+
+struct GpuResources {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+}
+
+/// Computes PSNR, SSIM and an error heatmap between a reference texture and a test texture of the same size.
+///
+/// Only needs a device, so it can be used headless. The pure CPU functions `compute_metrics` and `error_heatmap` work without any GPU.
+pub struct ImageMetricsEvaluator {
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    backend: MetricsBackend,
+    gpu: Option<GpuResources>,
+    heatmap_texture: Option<wgpu::Texture>,
+    heatmap_scale: f32,
+}
+
+impl ImageMetricsEvaluator {
+    pub fn new(device: Rc<wgpu::Device>, queue: Rc<wgpu::Queue>, backend: MetricsBackend) -> Self {
+        let gpu = match backend {
+            MetricsBackend::Gpu => Some(create_gpu_resources(&device)),
+            MetricsBackend::Cpu => None,
+        };
+
+        Self {
+            device,
+            queue,
+            backend,
+            gpu,
+            heatmap_texture: None,
+            heatmap_scale: 4.0,
+        }
+    }
+
+    pub fn backend(&self) -> MetricsBackend {
+        self.backend
+    }
+
+    pub fn heatmap_scale(&self) -> f32 {
+        self.heatmap_scale
+    }
+
+    /// Amplification of the errors in the heatmap: errors of 1 / scale and above are shown white.
+    pub fn set_heatmap_scale(&mut self, heatmap_scale: f32) {
+        self.heatmap_scale = heatmap_scale;
+    }
+
+    /// Rgba8Unorm heatmap of the per-pixel error of the last evaluation, None before the first one.
+    pub fn heatmap_texture(&self) -> Option<&wgpu::Texture> {
+        self.heatmap_texture.as_ref()
+    }
+
+    /// Compare two textures, blocks until the results are read back.
+    ///
+    /// Textures need the TEXTURE_BINDING usage with the GPU backend, COPY_SRC with the CPU backend.
+    /// sRGB textures are decoded to linear values by both backends.
+    pub fn evaluate(&mut self, reference: &wgpu::Texture, test: &wgpu::Texture) -> Result<ImageMetrics, ImageMetricsError> {
+        if reference.size() != test.size() {
+            return Err(ImageMetricsError::SizeMismatch);
+        }
+
+        let usage = match self.backend {
+            MetricsBackend::Gpu => wgpu::TextureUsages::TEXTURE_BINDING,
+            MetricsBackend::Cpu => wgpu::TextureUsages::COPY_SRC,
+        };
+
+        if !reference.usage().contains(usage) || !test.usage().contains(usage) {
+            return Err(ImageMetricsError::MissingUsage(usage));
+        }
+
+        self.prepare_heatmap_texture(reference.width(), reference.height());
+
+        match self.backend {
+            MetricsBackend::Gpu => Ok(self.evaluate_gpu(reference, test)),
+            MetricsBackend::Cpu => self.evaluate_cpu(reference, test),
+        }
+    }
+
+    fn prepare_heatmap_texture(&mut self, width: u32, height: u32) {
+        let up_to_date = matches!(&self.heatmap_texture, Some(texture) if texture.width() == width && texture.height() == height);
+
+        if !up_to_date {
+            let usage = wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST;
+            self.heatmap_texture = Some(self.device.create_texture(&texture_descriptor(Some("Image metrics heatmap texture"), width, height, wgpu::TextureFormat::Rgba8Unorm, usage)));
+        }
+    }
+
+    fn evaluate_gpu(&self, reference: &wgpu::Texture, test: &wgpu::Texture) -> ImageMetrics {
+        let gpu = self.gpu.as_ref().unwrap();
+        let (width, height) = (reference.width(), reference.height());
+        let (groups_x, groups_y) = (width.div_ceil(WINDOW_SIZE), height.div_ceil(WINDOW_SIZE));
+        let windows_size = (groups_x * groups_y) as u64 * std::mem::size_of::<[f32; 4]>() as u64;
+
+        let uniform_data = UniformData { width, height, heatmap_scale: self.heatmap_scale, padding: 0 };
+        self.queue.write_buffer(&gpu.uniform_buffer, 0, unsafe { any_as_u8_slice(&uniform_data) });
+
+        let windows_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Image metrics windows buffer"),
+            size: windows_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Image metrics readback buffer"),
+            size: windows_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = create_bind_group(&self.device, Some("Image metrics bind group"), &gpu.bind_group_layout, vec![
+            gpu.uniform_buffer.as_entire_binding(),
+            wgpu::BindingResource::TextureView(&reference.create_view(&Default::default())),
+            wgpu::BindingResource::TextureView(&test.create_view(&Default::default())),
+            wgpu::BindingResource::TextureView(&self.heatmap_texture.as_ref().unwrap().create_view(&Default::default())),
+            windows_buffer.as_entire_binding(),
+        ]);
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Image metrics encoder"),
+        });
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Image metrics pass"),
+            });
+            compute_pass.set_pipeline(&gpu.pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(groups_x, groups_y, 1);
+        }
+
+        encoder.copy_buffer_to_buffer(&windows_buffer, 0, &readback_buffer, 0, windows_size);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).unwrap();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap().expect("failed to map the image metrics readback buffer");
+
+        let metrics = {
+            let data = readback_buffer.slice(..).get_mapped_range();
+            let windows: &[[f32; 4]] = bytemuck::cast_slice(&data);
+            ImageMetrics::from_windows(windows.iter().map(|w| (w[0] as f64, w[1] as f64, w[2] as f64)))
+        };
+        readback_buffer.unmap();
+
+        metrics
+    }
+
+    fn evaluate_cpu(&self, reference: &wgpu::Texture, test: &wgpu::Texture) -> Result<ImageMetrics, ImageMetricsError> {
+        let reference_pixels = read_rgb(&self.device, &self.queue, reference)?;
+        let test_pixels = read_rgb(&self.device, &self.queue, test)?;
+        let (width, height) = (reference.width(), reference.height());
+
+        let heatmap = rgb_error_heatmap(&reference_pixels, &test_pixels, width, height, self.heatmap_scale);
+        let heatmap_texture = self.heatmap_texture.as_ref().unwrap();
+        self.queue.write_texture(
+            heatmap_texture.as_image_copy(),
+            &heatmap,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(width * 4),
+                rows_per_image: None,
+            },
+            heatmap_texture.size(),
+        );
+
+        Ok(compute_rgb_metrics(&reference_pixels, &test_pixels, width, height))
+    }
+}
+
+/// Compare two tightly packed RGBA8 images on the CPU, same results as the GPU backend (up to float precision).
+///
+/// Bytes are compared as stored, like textures in a linear (not sRGB) format.
+pub fn compute_metrics(reference: &[u8], test: &[u8], width: u32, height: u32) -> ImageMetrics {
+    compute_rgb_metrics(&decode_rgba8(reference, false), &decode_rgba8(test, false), width, height)
+}
+
+/// RGBA8 heatmap of the per-pixel error between two tightly packed RGBA8 images: black, red, yellow then white as the error grows.
+pub fn error_heatmap(reference: &[u8], test: &[u8], width: u32, height: u32, scale: f32) -> Vec<u8> {
+    rgb_error_heatmap(&decode_rgba8(reference, false), &decode_rgba8(test, false), width, height, scale)
+}
+
+fn compute_rgb_metrics(reference: &[[f64; 3]], test: &[[f64; 3]], width: u32, height: u32) -> ImageMetrics {
+    let (windows_x, windows_y) = (width.div_ceil(WINDOW_SIZE), height.div_ceil(WINDOW_SIZE));
+
+    let windows = (0..windows_y).flat_map(|wy| (0..windows_x).map(move |wx| (wx, wy))).map(|(wx, wy)| {
+        let (mut sum_x, mut sum_y, mut sum_xx, mut sum_yy, mut sum_xy, mut squared_error, mut n) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+
+        for y in wy * WINDOW_SIZE..((wy + 1) * WINDOW_SIZE).min(height) {
+            for x in wx * WINDOW_SIZE..((wx + 1) * WINDOW_SIZE).min(width) {
+                let i = (y * width + x) as usize;
+                let (r, t) = (reference[i], test[i]);
+
+                let (lx, ly) = (luma(r), luma(t));
+                sum_x += lx;
+                sum_y += ly;
+                sum_xx += lx * lx;
+                sum_yy += ly * ly;
+                sum_xy += lx * ly;
+                squared_error += (0..3).map(|c| (t[c] - r[c]) * (t[c] - r[c])).sum::<f64>();
+                n += 1.0;
+            }
+        }
+
+        let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+        let var_x = (sum_xx / n - mean_x * mean_x).max(0.0);
+        let var_y = (sum_yy / n - mean_y * mean_y).max(0.0);
+        let cov = sum_xy / n - mean_x * mean_y;
+        let ssim = ((2.0 * mean_x * mean_y + SSIM_C1) * (2.0 * cov + SSIM_C2)) / ((mean_x * mean_x + mean_y * mean_y + SSIM_C1) * (var_x + var_y + SSIM_C2));
+
+        (squared_error, ssim, n)
+    });
+
+    ImageMetrics::from_windows(windows)
+}
+
+fn rgb_error_heatmap(reference: &[[f64; 3]], test: &[[f64; 3]], width: u32, height: u32, scale: f32) -> Vec<u8> {
+    let mut heatmap = Vec::with_capacity((width * height * 4) as usize);
+
+    for (r, t) in reference.iter().zip(test).take((width * height) as usize) {
+        let error = ((0..3).map(|c| (t[c] - r[c]) * (t[c] - r[c])).sum::<f64>() / 3.0).sqrt() as f32 * scale;
+
+        for c in 0..3 {
+            heatmap.push(((error * 3.0 - c as f32).clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+        heatmap.push(255);
+    }
+
+    heatmap
+}
+
+/// RGB values in [0; 1] of tightly packed RGBA8 pixels, decoded from sRGB to linear like texture sampling does for sRGB formats.
+fn decode_rgba8(pixels: &[u8], srgb: bool) -> Vec<[f64; 3]> {
+    let decode = |value: u8| {
+        let value = value as f64 / 255.0;
+        if !srgb {
+            value
+        } else if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    pixels.chunks_exact(4).map(|pixel| [decode(pixel[0]), decode(pixel[1]), decode(pixel[2])]).collect()
+}
+
+fn luma(c: [f64; 3]) -> f64 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+/// Read back an 8 bits RGBA or BGRA texture as linear RGB, the values sampled by the GPU backend.
+fn read_rgb(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Result<Vec<[f64; 3]>, ImageMetricsError> {
+    let swap_red_blue = match texture.format() {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        format => return Err(ImageMetricsError::UnsupportedFormat(format)),
+    };
+
+    let mut pixels = read_texture(device, queue, texture);
+
+    if swap_red_blue {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Ok(decode_rgba8(&pixels, texture.format().describe().srgb))
+}
+
+fn create_gpu_resources(device: &wgpu::Device) -> GpuResources {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Image metrics shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/image_metrics.wgsl").into()),
+    });
+
+    let bind_group_layout = create_bind_group_layout(device, Some("Image metrics bind group layout"), vec![
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+    ], wgpu::ShaderStages::COMPUTE);
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Image metrics pipeline layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Image metrics pipeline"),
+        layout: Some(&pipeline_layout),
+        module: &shader,
+        entry_point: "cs_main",
+    });
+
+    let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Image metrics uniform buffer"),
+        size: std::mem::size_of::<UniformData>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    GpuResources {
+        pipeline,
+        bind_group_layout,
+        uniform_buffer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform_image(width: u32, height: u32, pixel: [u8; 4]) -> Vec<u8> {
+        pixel.repeat((width * height) as usize)
+    }
+
+    /// Deterministic image with some structure, so that windows have a variance.
+    fn pattern_image(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height).flat_map(|i| {
+            let (x, y) = (i % width, i / width);
+            [(x * 37 % 256) as u8, (y * 53 % 256) as u8, ((x + y) * 11 % 256) as u8, 255]
+        }).collect()
+    }
+
+    #[test]
+    fn identical_images() {
+        for (width, height) in [(16, 16), (13, 5), (1, 1)] {
+            let image = pattern_image(width, height);
+            let metrics = compute_metrics(&image, &image, width, height);
+
+            assert_eq!(metrics.mse, 0.0);
+            assert_eq!(metrics.psnr, f64::INFINITY);
+            assert!((metrics.ssim - 1.0).abs() < 1e-9, "SSIM {} for {}x{}", metrics.ssim, width, height);
+        }
+    }
+
+    #[test]
+    fn known_mse() {
+        // one channel off by the whole range: an error of 1 on a third of the values
+        let (width, height) = (16, 8);
+        let metrics = compute_metrics(&uniform_image(width, height, [0, 0, 0, 255]), &uniform_image(width, height, [255, 0, 0, 255]), width, height);
+
+        assert!((metrics.mse - 1.0 / 3.0).abs() < 1e-12);
+        assert!((metrics.psnr - 10.0 * 3.0f64.log10()).abs() < 1e-9);
+
+        // all channels off by 0.2
+        let metrics = compute_metrics(&uniform_image(width, height, [0, 0, 0, 255]), &uniform_image(width, height, [51, 51, 51, 255]), width, height);
+
+        assert!((metrics.mse - 0.04).abs() < 1e-12);
+        assert!((metrics.psnr - 10.0 * 25.0f64.log10()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn partial_windows_are_weighted_by_their_pixels() {
+        // 13x5: one full width window and one of 5x5 pixels, only the pixels of the second one differ
+        let (width, height) = (13, 5);
+        let reference = uniform_image(width, height, [0, 0, 0, 255]);
+        let mut test = reference.clone();
+
+        for y in 0..height {
+            for x in 8..width {
+                test[((y * width + x) * 4) as usize] = 255;
+            }
+        }
+
+        let metrics = compute_metrics(&reference, &test, width, height);
+        assert!((metrics.mse - 25.0 / (3.0 * 65.0)).abs() < 1e-12);
+        assert!(metrics.ssim < 1.0);
+    }
+
+    #[test]
+    fn alpha_is_ignored() {
+        let (width, height) = (9, 9);
+        let metrics = compute_metrics(&uniform_image(width, height, [10, 20, 30, 0]), &uniform_image(width, height, [10, 20, 30, 255]), width, height);
+
+        assert_eq!(metrics.mse, 0.0);
+    }
+
+    #[test]
+    fn heatmap() {
+        let (width, height) = (3, 2);
+        let reference = uniform_image(width, height, [0, 0, 0, 255]);
+        let mut test = reference.clone();
+        test[..4].copy_from_slice(&[255, 255, 255, 255]);
+
+        let heatmap = error_heatmap(&reference, &test, width, height, 1.0);
+
+        assert_eq!(heatmap.len(), (width * height * 4) as usize);
+        // maximum error is white, no error is black
+        assert_eq!(heatmap[..4], [255, 255, 255, 255]);
+        assert!(heatmap[4..].chunks_exact(4).all(|pixel| pixel == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn srgb_decoding() {
+        let decoded = decode_rgba8(&[0, 188, 255, 255], true);
+        assert_eq!(decoded[0][0], 0.0);
+        assert!((decoded[0][1] - 0.5).abs() < 0.005);
+        assert!((decoded[0][2] - 1.0).abs() < 1e-12);
+
+        let raw = decode_rgba8(&[0, 188, 255, 255], false);
+        assert!((raw[0][1] - 188.0 / 255.0).abs() < 1e-12);
+    }
+}
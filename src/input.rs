@@ -0,0 +1,438 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+use std::path::Path;
+
+use serde::Deserialize;
+use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+/// Pixels of a precise scroll (touchpads) counted as one line of a mouse wheel.
+const PIXELS_PER_SCROLL_LINE: f32 = 50.0;
+
+/// Physical input an action can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    ScrollUp,
+    ScrollDown,
+}
+
+/// Keyboard, mouse buttons, scroll wheel and cursor state, updated from window events.
+///
+/// "Pressed" and "released" states, the scroll and the cursor motion are accumulated since the last `end_frame`.
+pub struct InputState {
+    window_size: [u32; 2],
+    /// Last known cursor position, in pixels from the top left corner of the window.
+    cursor: [f32; 2],
+    cursor_in_window: bool,
+    cursor_delta: [f32; 2],
+    /// Scroll in lines, positive up and right.
+    scroll: [f32; 2],
+    modifiers: ModifiersState,
+    keys_down: HashSet<VirtualKeyCode>,
+    keys_pressed: HashSet<VirtualKeyCode>,
+    keys_released: HashSet<VirtualKeyCode>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+}
+
+/// Input state as read by shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShaderInput {
+    /// Cursor in normalized device coordinates: [-1; 1] inside of the window, y up.
+    pub cursor: [f32; 2],
+    /// Cursor in pixels from the top left corner of the window.
+    pub cursor_pixel: [f32; 2],
+    /// Scroll since the last frame, in lines.
+    pub scroll: [f32; 2],
+    /// Held mouse buttons: bit 0 left, bit 1 right, bit 2 middle.
+    pub buttons: u32,
+    /// Held modifiers: bit 0 shift, bit 1 ctrl, bit 2 alt, bit 3 logo.
+    pub modifiers: u32,
+}
+
+impl InputState {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            window_size: [width, height],
+            cursor: [0.0, 0.0],
+            cursor_in_window: false,
+            cursor_delta: [0.0, 0.0],
+            scroll: [0.0, 0.0],
+            modifiers: ModifiersState::empty(),
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+        }
+    }
+
+    pub fn set_window_size(&mut self, width: u32, height: u32) {
+        self.window_size = [width, height];
+    }
+
+    /// Update the state from a window event, returns whether it was an input event.
+    pub fn process_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = [position.x as f32, position.y as f32];
+                if self.cursor_in_window {
+                    self.cursor_delta[0] += position[0] - self.cursor[0];
+                    self.cursor_delta[1] += position[1] - self.cursor[1];
+                }
+                self.cursor = position;
+                self.cursor_in_window = true;
+                true
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_in_window = false;
+                true
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                match state {
+                    ElementState::Pressed => {
+                        if self.buttons_down.insert(*button) {
+                            self.buttons_pressed.insert(*button);
+                        }
+                    },
+                    ElementState::Released => {
+                        if self.buttons_down.remove(button) {
+                            self.buttons_released.insert(*button);
+                        }
+                    },
+                }
+                true
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (*x, *y),
+                    MouseScrollDelta::PixelDelta(position) => (position.x as f32 / PIXELS_PER_SCROLL_LINE, position.y as f32 / PIXELS_PER_SCROLL_LINE),
+                };
+                self.scroll[0] += x;
+                self.scroll[1] += y;
+                true
+            },
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+                ..
+            } => {
+                match state {
+                    // key repeats are not new presses
+                    ElementState::Pressed => {
+                        if self.keys_down.insert(*key) {
+                            self.keys_pressed.insert(*key);
+                        }
+                    },
+                    ElementState::Released => {
+                        if self.keys_down.remove(key) {
+                            self.keys_released.insert(*key);
+                        }
+                    },
+                }
+                true
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                true
+            },
+            WindowEvent::Focused(false) => {
+                // releases are not received while unfocused
+                self.keys_down.clear();
+                self.buttons_down.clear();
+                self.modifiers = ModifiersState::empty();
+                false
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether an event must reach `process_event` even when another consumer (like an overlay) used it.
+    ///
+    /// Releases, modifiers, the cursor leaving and focus loss keep the held state consistent: dropping them would leave keys or buttons held.
+    /// Presses, scroll and cursor motion can be filtered.
+    pub fn must_track(event: &WindowEvent) -> bool {
+        matches!(event,
+            WindowEvent::KeyboardInput { input: KeyboardInput { state: ElementState::Released, .. }, .. }
+            | WindowEvent::MouseInput { state: ElementState::Released, .. }
+            | WindowEvent::CursorLeft { .. }
+            | WindowEvent::ModifiersChanged(_)
+            | WindowEvent::Focused(false)
+        )
+    }
+
+    /// Forget the presses, releases, scroll and cursor motion of the frame.
+    pub fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.scroll = [0.0, 0.0];
+        self.cursor_delta = [0.0, 0.0];
+    }
+
+    /// Last known cursor position, in pixels from the top left corner of the window.
+    pub fn cursor_pixel(&self) -> [f32; 2] {
+        self.cursor
+    }
+
+    /// Last known cursor position in [0; 1] inside of the window, y down.
+    pub fn cursor_normalized(&self) -> [f32; 2] {
+        [self.cursor[0] / self.window_size[0].max(1) as f32, self.cursor[1] / self.window_size[1].max(1) as f32]
+    }
+
+    /// Last known cursor position in normalized device coordinates: [-1; 1] inside of the window, y up.
+    pub fn cursor_ndc(&self) -> [f32; 2] {
+        let [x, y] = self.cursor_normalized();
+        [x * 2.0 - 1.0, 1.0 - y * 2.0]
+    }
+
+    pub fn is_cursor_in_window(&self) -> bool {
+        self.cursor_in_window
+    }
+
+    /// Cursor motion in pixels since the last frame.
+    pub fn cursor_delta(&self) -> [f32; 2] {
+        self.cursor_delta
+    }
+
+    /// Scroll in lines since the last frame, positive up and right.
+    pub fn scroll(&self) -> [f32; 2] {
+        self.scroll
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    pub fn is_key_down(&self, key: VirtualKeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    pub fn was_key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    pub fn was_key_released(&self, key: VirtualKeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn was_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn was_button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    pub fn shader_input(&self) -> ShaderInput {
+        let buttons = [MouseButton::Left, MouseButton::Right, MouseButton::Middle].iter().enumerate()
+            .filter(|(_, button)| self.is_button_down(**button))
+            .fold(0, |bits, (bit, _)| bits | 1 << bit);
+        let modifiers = [self.modifiers.shift(), self.modifiers.ctrl(), self.modifiers.alt(), self.modifiers.logo()].iter().enumerate()
+            .filter(|(_, held)| **held)
+            .fold(0, |bits, (bit, _)| bits | 1 << bit);
+
+        ShaderInput {
+            cursor: self.cursor_ndc(),
+            cursor_pixel: self.cursor,
+            scroll: self.scroll,
+            buttons,
+            modifiers,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum InputConfigError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+}
+
+impl fmt::Display for InputConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputConfigError::Io(e) => write!(f, "can not read the bindings: {}", e),
+            InputConfigError::Parse(e) => write!(f, "invalid bindings: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for InputConfigError {}
+
+/// Bindings of application actions to physical inputs, several inputs can trigger the same action.
+///
+/// Bindings can be loaded from a RON map of actions to lists of bindings, such as:
+/// `{ TogglePause: [Key(Space)], ZoomIn: [ScrollUp, Key(Equals)], PickPixel: [Mouse(Right)] }`
+#[derive(Clone, Debug)]
+pub struct ActionBindings<A> {
+    bindings: HashMap<A, Vec<Binding>>,
+}
+
+impl<A: Copy + Eq + Hash> Default for ActionBindings<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Copy + Eq + Hash> ActionBindings<A> {
+    pub fn new() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    pub fn with_binding(mut self, action: A, binding: Binding) -> Self {
+        self.bindings.entry(action).or_default().push(binding);
+        self
+    }
+
+    /// Replace the bindings of the actions listed by `other`, the other actions are kept.
+    pub fn override_with(&mut self, other: ActionBindings<A>) {
+        self.bindings.extend(other.bindings);
+    }
+
+    pub fn bindings(&self, action: A) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Whether a binding of the action is held (or scrolled this frame).
+    pub fn is_active(&self, action: A, input: &InputState) -> bool {
+        self.bindings(action).iter().any(|binding| match *binding {
+            Binding::Key(key) => input.is_key_down(key),
+            Binding::Mouse(button) => input.is_button_down(button),
+            Binding::ScrollUp => input.scroll()[1] > 0.0,
+            Binding::ScrollDown => input.scroll()[1] < 0.0,
+        })
+    }
+
+    /// Whether a binding of the action was pressed (or scrolled) this frame.
+    pub fn was_triggered(&self, action: A, input: &InputState) -> bool {
+        self.amount(action, input) > 0.0
+    }
+
+    /// How much the action was triggered this frame: 1 per pressed key or button, and the count of scrolled lines.
+    pub fn amount(&self, action: A, input: &InputState) -> f32 {
+        self.bindings(action).iter().map(|binding| match *binding {
+            Binding::Key(key) => if input.was_key_pressed(key) { 1.0 } else { 0.0 },
+            Binding::Mouse(button) => if input.was_button_pressed(button) { 1.0 } else { 0.0 },
+            Binding::ScrollUp => input.scroll()[1].max(0.0),
+            Binding::ScrollDown => (-input.scroll()[1]).max(0.0),
+        }).sum()
+    }
+
+    /// Actions triggered this frame, in no particular order.
+    pub fn triggered_actions<'a>(&'a self, input: &'a InputState) -> impl Iterator<Item = A> + 'a {
+        self.bindings.keys().copied().filter(move |&action| self.was_triggered(action, input))
+    }
+}
+
+impl<A: Copy + Eq + Hash + for<'de> Deserialize<'de>> ActionBindings<A> {
+    pub fn from_ron_str(source: &str) -> Result<Self, InputConfigError> {
+        let bindings = ron::from_str(source).map_err(InputConfigError::Parse)?;
+        Ok(Self { bindings })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, InputConfigError> {
+        let source = std::fs::read_to_string(path).map_err(InputConfigError::Io)?;
+        Self::from_ron_str(&source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use winit::event::DeviceId;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+    enum TestAction {
+        Pause,
+        Zoom,
+        Pick,
+    }
+
+    #[allow(deprecated)] // modifiers are required by the event, they are tracked through ModifiersChanged
+    fn key_event(key: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::KeyboardInput {
+            // only used to tell devices apart
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput { scancode: 0, state, virtual_keycode: Some(key), modifiers: ModifiersState::empty() },
+            is_synthetic: false,
+        }
+    }
+
+    #[test]
+    fn ron_bindings() {
+        let bindings = ActionBindings::<TestAction>::from_ron_str("{ Pause: [Key(Space)], Zoom: [ScrollUp, Key(Equals)], Pick: [Mouse(Right)] }").unwrap();
+
+        assert_eq!(bindings.bindings(TestAction::Pause), &[Binding::Key(VirtualKeyCode::Space)]);
+        assert_eq!(bindings.bindings(TestAction::Zoom), &[Binding::ScrollUp, Binding::Key(VirtualKeyCode::Equals)]);
+        assert_eq!(bindings.bindings(TestAction::Pick), &[Binding::Mouse(MouseButton::Right)]);
+    }
+
+    #[test]
+    fn invalid_ron_bindings() {
+        for source in ["{ Pause: [Key(NotAKey)] }", "{ Unknown: [Key(Space)] }", "{ Pause: Key(Space) }"] {
+            assert!(matches!(ActionBindings::<TestAction>::from_ron_str(source), Err(InputConfigError::Parse(_))), "{}", source);
+        }
+
+        assert!(matches!(ActionBindings::<TestAction>::load("does/not/exist.ron"), Err(InputConfigError::Io(_))));
+    }
+
+    #[test]
+    fn override_replaces_listed_actions_only() {
+        let mut bindings = ActionBindings::new()
+            .with_binding(TestAction::Pause, Binding::Key(VirtualKeyCode::P))
+            .with_binding(TestAction::Zoom, Binding::ScrollUp)
+            .with_binding(TestAction::Zoom, Binding::Key(VirtualKeyCode::Equals));
+
+        bindings.override_with(ActionBindings::from_ron_str("{ Zoom: [Key(Z)], Pick: [Mouse(Left)] }").unwrap());
+
+        assert_eq!(bindings.bindings(TestAction::Pause), &[Binding::Key(VirtualKeyCode::P)]);
+        assert_eq!(bindings.bindings(TestAction::Zoom), &[Binding::Key(VirtualKeyCode::Z)]);
+        assert_eq!(bindings.bindings(TestAction::Pick), &[Binding::Mouse(MouseButton::Left)]);
+    }
+
+    #[test]
+    fn triggered_actions() {
+        let bindings = ActionBindings::new()
+            .with_binding(TestAction::Pause, Binding::Key(VirtualKeyCode::Space))
+            .with_binding(TestAction::Pick, Binding::Key(VirtualKeyCode::P));
+        let mut input = InputState::new(100, 100);
+
+        input.process_event(&key_event(VirtualKeyCode::Space, ElementState::Pressed));
+        assert_eq!(bindings.triggered_actions(&input).collect::<Vec<_>>(), vec![TestAction::Pause]);
+        assert!(bindings.is_active(TestAction::Pause, &input));
+
+        // held, but not pressed again
+        input.end_frame();
+        input.process_event(&key_event(VirtualKeyCode::Space, ElementState::Pressed));
+        assert_eq!(bindings.triggered_actions(&input).count(), 0);
+        assert!(bindings.is_active(TestAction::Pause, &input));
+
+        input.process_event(&key_event(VirtualKeyCode::Space, ElementState::Released));
+        assert!(!bindings.is_active(TestAction::Pause, &input));
+    }
+
+    #[test]
+    fn releases_are_always_tracked() {
+        assert!(InputState::must_track(&key_event(VirtualKeyCode::Space, ElementState::Released)));
+        assert!(!InputState::must_track(&key_event(VirtualKeyCode::Space, ElementState::Pressed)));
+        assert!(InputState::must_track(&WindowEvent::ModifiersChanged(ModifiersState::SHIFT)));
+        assert!(InputState::must_track(&WindowEvent::Focused(false)));
+        assert!(!InputState::must_track(&WindowEvent::Focused(true)));
+    }
+}
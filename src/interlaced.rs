@@ -1,8 +1,138 @@
-use std::{rc::Rc, cell::RefCell};
+use std::{rc::Rc, collections::HashMap};
 
 use wgpu::util::DeviceExt;
 
 use crate::utils::*;
+use crate::texture_pool::TexturePool;
+
+/// Field textures are both rendered to and read by the merge pass.
+/// Fields can be copied to inspect them, and the TAA merge copies the accumulated field back into its texture.
+const FIELD_TEXTURE_USAGE: wgpu::TextureUsages = wgpu::TextureUsages::RENDER_ATTACHMENT.union(wgpu::TextureUsages::TEXTURE_BINDING).union(wgpu::TextureUsages::COPY_SRC).union(wgpu::TextureUsages::COPY_DST);
+/// Format of the intermediate texture of the two pass merges (same as fields, so that it can be copied to them).
+const INTERMEDIATE_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Phosphor layout simulated by the CRT merge variant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrtMask {
+    None = 0,
+    /// RGB dot triads, shifted on every other line pair.
+    ShadowMask = 1,
+    /// Continuous vertical RGB stripes.
+    ApertureGrille = 2,
+}
+
+/// Parameters of the CRT merge variant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrtSettings {
+    /// Darkening of the rows belonging to the inactive field (0 = none, 1 = black).
+    pub scanline_intensity: f32,
+    /// How much of the older field is still lit on the inactive rows (0 = fully decayed, 1 = no decay).
+    pub persistence: f32,
+    pub mask: CrtMask,
+    /// Strength of the mask pattern (0 = invisible).
+    pub mask_intensity: f32,
+    /// Amount of barrel distortion (0 = flat screen).
+    pub curvature: f32,
+}
+
+impl Default for CrtSettings {
+    fn default() -> Self {
+        Self {
+            scanline_intensity: 0.4,
+            persistence: 0.6,
+            mask: CrtMask::ApertureGrille,
+            mask_intensity: 0.25,
+            curvature: 0.08,
+        }
+    }
+}
+
+/// Parameters of the temporal anti-aliasing merge variant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TaaSettings {
+    /// Weight of the accumulated history in the rows of the current field, in [0; 1].
+    pub feedback: f32,
+    /// Count of distinct jitter offsets before the sequence repeats.
+    pub jitter_sequence_length: u32,
+}
+
+impl Default for TaaSettings {
+    fn default() -> Self {
+        Self {
+            feedback: 0.5,
+            jitter_sequence_length: 8,
+        }
+    }
+}
+
+/// How the two fields are combined into the full frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MergeVariant {
+    /// Plain weave of both fields, using the shader given at creation.
+    Standard,
+    /// Retro TV look driven by the current field.
+    Crt(CrtSettings),
+    /// Temporal anti-aliasing: the scene is jittered (see `jitter_offset`) and each field is accumulated with the other field, clamped to its neighborhood.
+    ///
+    /// The field textures are the history: a field accumulated in a frame is blended into the next field.
+    Taa(TaaSettings),
+}
+
+/// How fields rendered at a lower resolution than the output are upscaled by the standard merge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReconstructionFilter {
+    /// Point sampling of each field, keeps the interlaced rows of the output.
+    Nearest,
+    /// Edge-adaptive spatial upscaling (EASU-like) of the woven fields.
+    Easu,
+    /// EASU followed by contrast-adaptive sharpening (RCAS-like), `sharpness` in [0; 1].
+    EasuRcas { sharpness: f32 },
+}
+
+/// Shaders used by the renderer, their pipelines are created when first needed for a target format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum MergeShader {
+    /// The shader given at creation.
+    Standard,
+    Crt,
+    /// Accumulation of the current field with the other field, at the field resolution.
+    Taa,
+    /// Weave of the accumulated fields to the output.
+    TaaResolve,
+    Easu,
+    Rcas,
+}
+
+impl MergeShader {
+    fn label(self) -> &'static str {
+        match self {
+            MergeShader::Standard => "Interlaced renderer shader",
+            MergeShader::Crt => "Interlaced renderer CRT shader",
+            MergeShader::Taa => "Interlaced renderer TAA shader",
+            MergeShader::TaaResolve => "Interlaced renderer TAA resolve shader",
+            MergeShader::Easu => "Interlaced renderer EASU shader",
+            MergeShader::Rcas => "Interlaced renderer RCAS shader",
+        }
+    }
+
+    /// Source of the shaders shipped with the renderer.
+    fn builtin_source(self) -> Option<&'static str> {
+        Some(match self {
+            MergeShader::Standard => return None,
+            MergeShader::Crt => include_str!("shaders/merge_crt.wgsl"),
+            MergeShader::Taa | MergeShader::TaaResolve => include_str!("shaders/merge_taa.wgsl"),
+            MergeShader::Easu => include_str!("shaders/easu.wgsl"),
+            MergeShader::Rcas => include_str!("shaders/rcas.wgsl"),
+        })
+    }
+
+    fn fragment_entry_point(self) -> &'static str {
+        match self {
+            MergeShader::TaaResolve => "fs_resolve",
+            _ => "fs_main",
+        }
+    }
+}
 
 pub struct InterlacedRendererState {
     /// Full width of the rendered frame.
@@ -11,39 +141,151 @@ pub struct InterlacedRendererState {
     height: u32,
     device: Rc<wgpu::Device>,
     queue: Rc<wgpu::Queue>,
+    /// Size of the fields relative to the full frame (the height of a field is always halved on top of this).
+    render_scale: f32,
     render_texture1: wgpu::Texture,
     render_texture2: wgpu::Texture,
-    pipeline: wgpu::RenderPipeline,
+    /// Sample count of the scene attachment, 1 when multisampling is disabled.
+    sample_count: u32,
+    /// Attachment the scene is rendered to when multisampling, resolved into the current field texture.
+    multisampled_texture: Option<wgpu::Texture>,
+    texture_pool: TexturePool,
+    target: wgpu::TextureFormat,
+    internal_shader_src: String,
+    pipelines: HashMap<(MergeShader, wgpu::TextureFormat), wgpu::RenderPipeline>,
+    merge_variant: MergeVariant,
+    reconstruction_filter: ReconstructionFilter,
+    /// Output of the first pass of the two pass merges: the upscaled frame read by the sharpening pass, or the accumulated field of the TAA merge.
+    ///
+    /// The TAA merge can not render to the field texture it reads, so the field is accumulated here and copied back: this saves a history set next to the fields.
+    intermediate_texture: Option<wgpu::Texture>,
+    upscale_bind_group: Option<wgpu::BindGroup>,
+    /// Whether the field texture not rendered to this frame holds the previous frame, so that the TAA merge can accumulate the current field with it.
+    taa_history_valid: bool,
     bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
     uniform_buffer: wgpu::Buffer,
     need_write_data: bool,
     frame_number: u64,
+    /// Frame of the last `draw`, drawn again by `redraw`.
+    merged_frame_number: u64,
+    /// Whether `scene_offset` includes the half row offset of the fields.
+    field_offset_enabled: bool,
     index_buffer: wgpu::Buffer,
 }
 
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
 struct UniformData {
     width: u32,
     height: u32,
+    field_width: u32,
+    field_height: u32,
+    /// Low 32 bits of the frame number, the current field is its parity.
+    frame_number: u32,
+    crt_mask: u32,
+    crt_scanline_intensity: f32,
+    crt_persistence: f32,
+    crt_mask_intensity: f32,
+    crt_curvature: f32,
+    rcas_sharpness: f32,
+    taa_feedback: f32,
+}
+
+fn create_merge_pipeline(device: &wgpu::Device, label: &str, shader_src: &str, fragment_entry_point: &'static str, bind_group_layout: &wgpu::BindGroupLayout, target: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+    });
+
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Interlaced renderer pipeline layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    create_render_pipeline_with_options(device, None, &[], &render_pipeline_layout, &shader, target, RenderPipelineOptions {
+        fragment_entry_point,
+        ..Default::default()
+    })
+}
+
+/// Record a pass drawing a merge shader over the whole output.
+fn record_merge_pass(encoder: &mut wgpu::CommandEncoder, label: &str, output_view: &wgpu::TextureView, pipeline: &wgpu::RenderPipeline, bind_group: &wgpu::BindGroup, index_buffer: &wgpu::Buffer) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.1,
+                    g: 0.2,
+                    b: 0.3,
+                    a: 1.0,
+                }),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+    render_pass.draw_indexed(0..6, 0, 0..1);
+}
+
+/// Texel of a field an output pixel is read from by the merge shaders (`merge.wgsl`, and the CRT and TAA variants).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldSource {
+    /// Index in `InterlacedRendererState::field_textures`: even output rows come from the first field, odd rows from the second one.
+    pub field: usize,
+    pub x: u32,
+    /// Row of the field.
+    pub y: u32,
+}
+
+/// Same mapping as the merge shaders, for an output pixel of a frame of `width` x `height` made of fields of `field_width` x `field_height`.
+pub fn field_source(x: u32, y: u32, width: u32, height: u32, field_width: u32, field_height: u32) -> FieldSource {
+    FieldSource {
+        field: (y & 1) as usize,
+        x: x * field_width / width.max(1),
+        // the last row of an odd height has no field row of its own, it repeats the last row of its field
+        y: ((y / 2) * field_height / (height / 2).max(1)).min(field_height.saturating_sub(1)),
+    }
+}
+
+/// Clip space offset of the samples of a field, so that its rows are sampled at the centers of the output rows they are woven into.
+///
+/// A field texture has half the rows of the output, rendering it with the output projection samples each field row between two output rows.
+/// The first field holds the even output rows, half an output row above, the second one the odd rows, half an output row below (clip space y goes up).
+pub fn field_offset(field: usize, height: u32) -> [f32; 2] {
+    let half_row = 1.0 / height.max(1) as f32;
+    [0.0, if field == 0 { half_row } else { -half_row }]
+}
+
+/// Size of each field texture for a full frame size and a render scale.
+fn field_size(width: u32, height: u32, render_scale: f32) -> (u32, u32) {
+    let field_width = (width as f32 * render_scale).round().max(1.0) as u32;
+    let field_height = ((height / 2) as f32 * render_scale).round().max(1.0) as u32;
+    (field_width, field_height)
 }
 
 impl InterlacedRendererState {
     /// Create a new interlaced renderer with an existing device.
     pub fn new(device: Rc<wgpu::Device>, queue: Rc<wgpu::Queue>, width: u32, height: u32, target: wgpu::TextureFormat, internal_shader_src: &str) -> Self {
-        let uniform_data = UniformData {
-            width,
-            height,
-        };
-
-        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Interlaced renderer uniform buffer"),
-            contents: unsafe { any_as_u8_slice(&uniform_data) },
+            size: std::mem::size_of::<UniformData>() as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
 
         // For render to texture, we use RENDER_ATTACHMENT to allow rendering to this texture, and TEXTURE_BINDING to allow reading it in another pass
-        let render_texture1 = create_texture(device.as_ref(), Some("Interlaced renderer first render texture"), width, height / 2, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING);
-        let render_texture2 = create_texture(device.as_ref(), Some("Interlaced renderer second render texture"), width, height / 2, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING);
+        let (field_width, field_height) = field_size(width, height, 1.0);
+        let render_texture1 = create_texture(device.as_ref(), Some("Interlaced renderer first render texture"), field_width, field_height, FIELD_TEXTURE_USAGE);
+        let render_texture2 = create_texture(device.as_ref(), Some("Interlaced renderer second render texture"), field_width, field_height, FIELD_TEXTURE_USAGE);
         let render_view1 = render_texture1.create_view(&wgpu::TextureViewDescriptor::default());
         let render_view2 = render_texture2.create_view(&wgpu::TextureViewDescriptor::default());
 
@@ -89,18 +331,7 @@ impl InterlacedRendererState {
             ],
         );
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Interlaced renderer shader"),
-            source: wgpu::ShaderSource::Wgsl(internal_shader_src.into()),
-        });
-
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Interlaced renderer pipeline layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let pipeline = create_render_pipeline(device.as_ref(), None, &[], &render_pipeline_layout, &shader, target);
+        let pipeline = create_merge_pipeline(device.as_ref(), MergeShader::Standard.label(), internal_shader_src, MergeShader::Standard.fragment_entry_point(), &bind_group_layout, target);
 
         let indices: &[u16; 6] = &[
             0, 1, 2,
@@ -120,32 +351,287 @@ impl InterlacedRendererState {
             height,
             device,
             queue,
+            render_scale: 1.0,
             render_texture1,
             render_texture2,
-            pipeline,
+            sample_count: 1,
+            multisampled_texture: None,
+            // enough to keep the fields of the two last scales around
+            texture_pool: TexturePool::new(4),
+            target,
+            internal_shader_src: internal_shader_src.to_string(),
+            pipelines: HashMap::from([((MergeShader::Standard, target), pipeline)]),
+            merge_variant: MergeVariant::Standard,
+            reconstruction_filter: ReconstructionFilter::Nearest,
+            intermediate_texture: None,
+            upscale_bind_group: None,
+            taa_history_valid: false,
             bind_group_layout,
             bind_group,
             uniform_buffer,
             need_write_data: false,
             frame_number: 0,
+            merged_frame_number: 0,
+            field_offset_enabled: true,
             index_buffer,
         }
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
-        println!("interlaced renderer resize to {}x{}", width, height);
+        log::info!("interlaced renderer resize to {}x{}", width, height);
         self.width = width;
         self.height = height;
-        self.render_texture1 = create_texture(self.device.as_ref(), None, self.width, self.height / 2, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING);
-        self.render_texture2 = create_texture(self.device.as_ref(), None, self.width, self.height / 2, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING);
+        self.reallocate_fields();
+        // the upscale texture follows the output size
         self.need_write_data = true;
     }
 
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Render the fields at a fraction of the full frame size, the merge pass upscales them to the output.
+    pub fn set_render_scale(&mut self, render_scale: f32) {
+        self.render_scale = render_scale.clamp(0.1, 1.0);
+        self.reallocate_fields();
+    }
+
+    /// Size of the textures the fields are rendered to.
+    pub fn field_size(&self) -> (u32, u32) {
+        (self.render_texture1.width(), self.render_texture1.height())
+    }
+
+    /// Swap the field textures for ones matching the current size and scale, going through the texture pool.
+    fn reallocate_fields(&mut self) {
+        let (field_width, field_height) = field_size(self.width, self.height, self.render_scale);
+
+        if self.field_size() == (field_width, field_height) {
+            return;
+        }
+
+        let render_texture1 = self.texture_pool.acquire(self.device.as_ref(), Some("Interlaced renderer first render texture"), field_width, field_height, FIELD_TEXTURE_USAGE);
+        let render_texture2 = self.texture_pool.acquire(self.device.as_ref(), Some("Interlaced renderer second render texture"), field_width, field_height, FIELD_TEXTURE_USAGE);
+        self.texture_pool.release(std::mem::replace(&mut self.render_texture1, render_texture1));
+        self.texture_pool.release(std::mem::replace(&mut self.render_texture2, render_texture2));
+        self.reallocate_multisampled_texture();
+        self.need_write_data = true;
+        // the new fields hold no previous frame to accumulate with
+        self.taa_history_valid = false;
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Enable multisampling of the scene with the given sample count (which must be supported, see `supported_sample_counts`), 1 disables it.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count.max(1);
+        self.reallocate_multisampled_texture();
+    }
+
+    fn reallocate_multisampled_texture(&mut self) {
+        if let Some(texture) = self.multisampled_texture.take() {
+            self.texture_pool.release(texture);
+        }
+
+        if self.sample_count > 1 {
+            let (field_width, field_height) = self.field_size();
+            let descriptor = wgpu::TextureDescriptor {
+                sample_count: self.sample_count,
+                ..texture_descriptor(Some("Interlaced renderer multisampled texture"), field_width, field_height, self.render_texture1.format(), wgpu::TextureUsages::RENDER_ATTACHMENT)
+            };
+
+            self.multisampled_texture = Some(self.texture_pool.acquire_with_descriptor(self.device.as_ref(), &descriptor));
+        }
+    }
+
+    pub fn merge_variant(&self) -> MergeVariant {
+        self.merge_variant
+    }
+
+    /// Select how fields are combined, the pipeline of a variant is only created the first time it is used.
+    pub fn set_merge_variant(&mut self, variant: MergeVariant) {
+        match variant {
+            MergeVariant::Standard => {},
+            MergeVariant::Crt(_) => self.prepare_pipeline(MergeShader::Crt, self.target),
+            MergeVariant::Taa(_) => {
+                self.prepare_pipeline(MergeShader::Taa, INTERMEDIATE_TEXTURE_FORMAT);
+                self.prepare_pipeline(MergeShader::TaaResolve, self.target);
+            },
+        }
+
+        self.merge_variant = variant;
+        // the intermediate texture follows the variant
+        self.need_write_data = true;
+    }
+
+    pub fn reconstruction_filter(&self) -> ReconstructionFilter {
+        self.reconstruction_filter
+    }
+
+    /// Select how the standard merge upscales fields rendered below the output resolution (see `set_render_scale`).
+    pub fn set_reconstruction_filter(&mut self, filter: ReconstructionFilter) {
+        match filter {
+            ReconstructionFilter::Nearest => {},
+            ReconstructionFilter::Easu => {
+                self.prepare_pipeline(MergeShader::Easu, self.target);
+            },
+            ReconstructionFilter::EasuRcas { .. } => {
+                self.prepare_pipeline(MergeShader::Easu, INTERMEDIATE_TEXTURE_FORMAT);
+                self.prepare_pipeline(MergeShader::Rcas, self.target);
+            },
+        }
+
+        self.reconstruction_filter = filter;
+        self.need_write_data = true;
+    }
+
+    pub fn target_format(&self) -> wgpu::TextureFormat {
+        self.target
+    }
+
+    /// Change the format of the texture given to `draw`, for example to render to an intermediate texture of a post-process.
+    pub fn set_target_format(&mut self, target: wgpu::TextureFormat) {
+        self.target = target;
+        self.prepare_pipeline(MergeShader::Standard, target);
+        self.set_merge_variant(self.merge_variant);
+        self.set_reconstruction_filter(self.reconstruction_filter);
+    }
+
+    fn prepare_pipeline(&mut self, shader: MergeShader, target: wgpu::TextureFormat) {
+        if !self.pipelines.contains_key(&(shader, target)) {
+            let source = shader.builtin_source().unwrap_or(&self.internal_shader_src);
+            let pipeline = create_merge_pipeline(self.device.as_ref(), shader.label(), source, shader.fragment_entry_point(), &self.bind_group_layout, target);
+            self.pipelines.insert((shader, target), pipeline);
+        }
+    }
+
+    fn pipeline(&self, shader: MergeShader, target: wgpu::TextureFormat) -> &wgpu::RenderPipeline {
+        self.pipelines.get(&(shader, target)).expect("pipeline should be prepared when its feature is selected")
+    }
+
+    /// Whether fields are smaller than half of the output, and need to be upscaled.
+    fn is_upscaling(&self) -> bool {
+        self.field_size() != (self.width, self.height / 2)
+    }
+
+    /// Keep the intermediate texture only while a two pass merge needs it, and the bind group of the sharpening pass.
+    fn update_intermediate_texture(&mut self) {
+        let size = match (self.merge_variant, self.reconstruction_filter) {
+            (MergeVariant::Taa(_), _) => Some(self.field_size()),
+            (_, ReconstructionFilter::EasuRcas { .. }) => Some((self.width, self.height)),
+            _ => None,
+        };
+
+        let up_to_date = matches!((&self.intermediate_texture, size), (Some(texture), Some(size)) if (texture.width(), texture.height()) == size);
+
+        if !up_to_date {
+            if let Some(texture) = self.intermediate_texture.take() {
+                self.texture_pool.release(texture);
+            }
+
+            if let Some((width, height)) = size {
+                self.intermediate_texture = Some(self.texture_pool.acquire(self.device.as_ref(), Some("Interlaced renderer intermediate texture"), width, height, FIELD_TEXTURE_USAGE));
+            }
+        }
+
+        self.upscale_bind_group = match (&self.intermediate_texture, self.merge_variant) {
+            (Some(texture), MergeVariant::Standard | MergeVariant::Crt(_)) => {
+                let upscale_view = texture.create_view(&Default::default());
+
+                // the sharpening pass reads the upscaled frame through the first texture binding
+                Some(create_bind_group(&self.device, Some("Interlaced renderer upscale bind group"), &self.bind_group_layout,
+                    vec![
+                        self.uniform_buffer.as_entire_binding(),
+                        wgpu::BindingResource::TextureView(&upscale_view),
+                        wgpu::BindingResource::TextureView(&upscale_view),
+                    ],
+                ))
+            },
+            _ => None,
+        };
+    }
+
+    /// Sub-pixel offset (in clip space units) the scene pass should apply to the field rendered this frame, zero unless TAA is enabled.
+    ///
+    /// Offsets follow the Halton (2, 3) sequence, in [-0.5; 0.5] field pixels.
+    pub fn jitter_offset(&self) -> [f32; 2] {
+        match self.merge_variant {
+            MergeVariant::Taa(settings) => {
+                let index = (self.frame_number % settings.jitter_sequence_length.max(1) as u64) as u32 + 1;
+                let (field_width, field_height) = self.field_size();
+
+                [
+                    (halton(index, 2) - 0.5) * 2.0 / field_width as f32,
+                    (halton(index, 3) - 0.5) * 2.0 / field_height as f32,
+                ]
+            },
+            _ => [0.0, 0.0],
+        }
+    }
+
+    pub fn field_offset_enabled(&self) -> bool {
+        self.field_offset_enabled
+    }
+
+    /// Disable the half row offset of the fields, to compare with fields rendered with the same projection.
+    pub fn set_field_offset_enabled(&mut self, enabled: bool) {
+        self.field_offset_enabled = enabled;
+    }
+
+    /// Clip space offset the scene pass should apply to the samples of the field rendered this frame: the half row offset of the field (see `field_offset`) plus the TAA jitter.
+    pub fn scene_offset(&self) -> [f32; 2] {
+        let jitter = self.jitter_offset();
+
+        if !self.field_offset_enabled {
+            return jitter;
+        }
+
+        let offset = field_offset(self.current_field(), self.height);
+        [jitter[0] + offset[0], jitter[1] + offset[1]]
+    }
+
+    fn uniform_data(&self, frame_number: u64) -> UniformData {
+        let crt = match self.merge_variant {
+            MergeVariant::Crt(settings) => settings,
+            _ => CrtSettings::default(),
+        };
+
+        let (field_width, field_height) = self.field_size();
+
+        UniformData {
+            width: self.width,
+            height: self.height,
+            field_width,
+            field_height,
+            frame_number: frame_number as u32,
+            crt_mask: crt.mask as u32,
+            crt_scanline_intensity: crt.scanline_intensity,
+            crt_persistence: crt.persistence,
+            crt_mask_intensity: crt.mask_intensity,
+            crt_curvature: crt.curvature,
+            rcas_sharpness: match self.reconstruction_filter {
+                ReconstructionFilter::EasuRcas { sharpness } => sharpness,
+                _ => 0.0,
+            },
+            taa_feedback: match self.merge_variant {
+                MergeVariant::Taa(settings) => settings.feedback,
+                _ => 0.0,
+            },
+        }
+    }
+
     /// Send necessary data to the GPU
     pub fn write_needed_data(&mut self) {
-        if self.need_write_data {
-            self.queue.write_buffer(&self.uniform_buffer, 0, unsafe { any_as_u8_slice(&UniformData { width: self.width, height: self.height }) });
+        self.update_resources();
 
+        // the frame number changes every frame, so the uniform buffer is always written
+        self.queue.write_buffer(&self.uniform_buffer, 0, unsafe { any_as_u8_slice(&self.uniform_data(self.frame_number)) });
+    }
+
+    /// Recreate the bind groups and textures which depend on the fields and the selected features.
+    fn update_resources(&mut self) {
+        if self.need_write_data {
             self.bind_group = create_bind_group(&self.device, Some("Interlaced renderer bind group"), &self.bind_group_layout, 
                 vec![
                     self.uniform_buffer.as_entire_binding(),
@@ -155,50 +641,188 @@ impl InterlacedRendererState {
                 ],
             );
 
+            self.update_intermediate_texture();
+
             self.need_write_data = false;
         }
     }
 
+    /// Both field textures: the first one holds the even rows of the output, the second one the odd rows.
+    pub fn field_textures(&self) -> [&wgpu::Texture; 2] {
+        [&self.render_texture1, &self.render_texture2]
+    }
+
+    /// Field texel the merge reads the output pixel (x, y) from. The upscaling filters also read the neighbours of that texel.
+    pub fn field_source(&self, x: u32, y: u32) -> FieldSource {
+        let (field_width, field_height) = self.field_size();
+        field_source(x, y, self.width, self.height, field_width, field_height)
+    }
+
+    /// Full resolution output of the upscaling pass, read by the sharpening pass. Only exists with the EASU + RCAS filter.
+    pub fn upscale_texture(&self) -> Option<&wgpu::Texture> {
+        self.upscale_bind_group.as_ref().and(self.intermediate_texture.as_ref())
+    }
+
+    /// Index in `field_textures` of the field rendered this frame (the one returned by `get_render_texture`).
+    pub fn current_field(&self) -> usize {
+        (self.frame_number & 1) as usize
+    }
+
+    /// Move to the next field without drawing the merged frame, when the output is not needed.
+    pub fn skip_draw(&mut self) {
+        self.frame_number += 1;
+
+        // the skipped field is not accumulated, the next field can not be accumulated with it
+        self.taa_history_valid = false;
+    }
+
     pub fn get_render_texture(&self) -> &wgpu::Texture {
         if (self.frame_number & 1) == 0 { &self.render_texture1 } else { &self.render_texture2 }
     }
 
+    /// Multisampled texture to render the scene to instead of `get_render_texture`, with the latter as resolve target. None when multisampling is disabled.
+    pub fn get_multisampled_texture(&self) -> Option<&wgpu::Texture> {
+        self.multisampled_texture.as_ref()
+    }
+
     /// Returns the command buffer necessary to render a full frame (by interlacing new frame with the old one) to a given texture.
     pub fn draw(&mut self, output_view: &wgpu::TextureView) {
+        // must happen before recording the pass, so that it uses this frame's data and textures
+        self.write_needed_data();
+
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Interlaced renderer Encoder"),
         });
 
-        {
-            // render to the full resolution texture given by caller, by interlacing new_half_frame and self.render_texture
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Interlaced renderer pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: output_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-    
-            render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..6, 0, 0..1);
-        }
+        self.merged_frame_number = self.frame_number;
+        self.record_merge(&mut encoder, output_view, self.target, self.taa_history_valid);
 
         let command_buffer = encoder.finish();
 
-        self.write_needed_data();
         self.frame_number += 1;
         self.queue.submit(std::iter::once(command_buffer));
+
+        self.taa_history_valid = true;
+    }
+
+    /// Draw the frame of the last `draw` again, to a target of another format (for captures), without advancing to the next field.
+    pub fn redraw(&mut self, output_view: &wgpu::TextureView, target: wgpu::TextureFormat) {
+        for shader in [MergeShader::Standard, MergeShader::Crt, MergeShader::TaaResolve, MergeShader::Easu, MergeShader::Rcas] {
+            self.prepare_pipeline(shader, target);
+        }
+
+        // features and settings selected since the last draw (while paused)
+        self.update_resources();
+        self.queue.write_buffer(&self.uniform_buffer, 0, unsafe { any_as_u8_slice(&self.uniform_data(self.merged_frame_number)) });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Interlaced renderer redraw Encoder"),
+        });
+
+        // the merged field was already accumulated by the draw
+        self.record_merge(&mut encoder, output_view, target, false);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// `accumulate` selects whether the TAA merge accumulates the merged field with the other field, or only weaves the fields to the output.
+    fn record_merge(&self, encoder: &mut wgpu::CommandEncoder, output_view: &wgpu::TextureView, target: wgpu::TextureFormat, accumulate: bool) {
+        // render to the full resolution texture given by caller, by interlacing new_half_frame and self.render_texture
+        match (self.merge_variant, self.reconstruction_filter) {
+            (MergeVariant::Crt(_), _) => {
+                record_merge_pass(encoder, "Interlaced renderer pass", output_view, self.pipeline(MergeShader::Crt, target), &self.bind_group, &self.index_buffer);
+            },
+            (MergeVariant::Taa(_), _) => {
+                if accumulate {
+                    let intermediate_texture = self.intermediate_texture.as_ref().unwrap();
+                    let intermediate_view = intermediate_texture.create_view(&Default::default());
+                    record_merge_pass(encoder, "Interlaced renderer TAA accumulation pass", &intermediate_view, self.pipeline(MergeShader::Taa, INTERMEDIATE_TEXTURE_FORMAT), &self.bind_group, &self.index_buffer);
+
+                    let field_texture = if (self.merged_frame_number & 1) == 0 { &self.render_texture1 } else { &self.render_texture2 };
+                    encoder.copy_texture_to_texture(intermediate_texture.as_image_copy(), field_texture.as_image_copy(), field_texture.size());
+                }
+
+                record_merge_pass(encoder, "Interlaced renderer TAA resolve pass", output_view, self.pipeline(MergeShader::TaaResolve, target), &self.bind_group, &self.index_buffer);
+            },
+            (MergeVariant::Standard, ReconstructionFilter::Easu) if self.is_upscaling() => {
+                record_merge_pass(encoder, "Interlaced renderer pass", output_view, self.pipeline(MergeShader::Easu, target), &self.bind_group, &self.index_buffer);
+            },
+            (MergeVariant::Standard, ReconstructionFilter::EasuRcas { .. }) if self.is_upscaling() => {
+                let upscale_view = self.intermediate_texture.as_ref().unwrap().create_view(&Default::default());
+                record_merge_pass(encoder, "Interlaced renderer upscale pass", &upscale_view, self.pipeline(MergeShader::Easu, INTERMEDIATE_TEXTURE_FORMAT), &self.bind_group, &self.index_buffer);
+                record_merge_pass(encoder, "Interlaced renderer sharpen pass", output_view, self.pipeline(MergeShader::Rcas, target), self.upscale_bind_group.as_ref().unwrap(), &self.index_buffer);
+            },
+            _ => {
+                record_merge_pass(encoder, "Interlaced renderer pass", output_view, self.pipeline(MergeShader::Standard, target), &self.bind_group, &self.index_buffer);
+            },
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Same integer arithmetic as the merge shaders.
+    fn shader_field_source(x: u32, y: u32, width: u32, height: u32, field_width: u32, field_height: u32) -> FieldSource {
+        FieldSource {
+            field: (y & 1) as usize,
+            x: x * field_width / width,
+            y: ((y / 2) * field_height / (height / 2).max(1)).min(field_height - 1),
+        }
+    }
+
+    #[test]
+    fn field_source_matches_merge_shader() {
+        for (width, height, render_scale) in [(1280, 720, 1.0), (1280, 720, 0.5), (801, 601, 0.73), (3, 3, 1.0), (1, 2, 0.25)] {
+            let (field_width, field_height) = field_size(width, height, render_scale);
+
+            for y in 0..height {
+                for x in (0..width).step_by(7).chain([width - 1]) {
+                    let source = field_source(x, y, width, height, field_width, field_height);
+                    assert_eq!(source, shader_field_source(x, y, width, height, field_width, field_height));
+                    assert!(source.x < field_width && source.y < field_height, "{:?} out of a {}x{} field", source, field_width, field_height);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn field_source_at_full_scale() {
+        let (field_width, field_height) = field_size(640, 480, 1.0);
+        assert_eq!((field_width, field_height), (640, 240));
+
+        assert_eq!(field_source(10, 20, 640, 480, field_width, field_height), FieldSource { field: 0, x: 10, y: 10 });
+        assert_eq!(field_source(10, 21, 640, 480, field_width, field_height), FieldSource { field: 1, x: 10, y: 10 });
+
+        // odd height: the last row has no row in the first field
+        let (field_width, field_height) = field_size(640, 481, 1.0);
+        assert_eq!(field_source(10, 480, 640, 481, field_width, field_height), FieldSource { field: 0, x: 10, y: 239 });
+    }
+
+    #[test]
+    fn field_offset_is_half_an_output_row() {
+        for height in [2, 480, 720, 1081] {
+            assert_eq!(field_offset(0, height), [0.0, 1.0 / height as f32]);
+            assert_eq!(field_offset(1, height), [0.0, -1.0 / height as f32]);
+        }
+
+        // no division by zero while minimized
+        assert_eq!(field_offset(0, 0), [0.0, 1.0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn field_offset_samples_output_rows() {
+        let height = 480;
+        let field_rows = height / 2;
+        // clip space y of the center of a row, with `rows` rows (y up)
+        let row_center = |row: u32, rows: u32| 1.0 - (2 * row + 1) as f32 / rows as f32;
+
+        for field in 0..2 {
+            for row in [0, 1, 100, field_rows - 1] {
+                let offset = field_offset(field, height)[1];
+                let output_row = 2 * row + field as u32;
+                assert!((row_center(row, field_rows) + offset - row_center(output_row, height)).abs() < 1e-6);
+            }
+        }
+    }
+}
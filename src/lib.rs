@@ -1,2 +1,19 @@
 pub mod utils;
-pub mod interlaced;
\ No newline at end of file
+pub mod interlaced;
+pub mod texture_pool;
+pub mod dynamic_resolution;
+pub mod mipmap;
+pub mod bloom;
+pub mod surface;
+pub mod frame_pacing;
+pub mod frame_stats;
+pub mod gpu_timer;
+pub mod image_metrics;
+pub mod time_control;
+pub mod overlay;
+pub mod field_inspector;
+pub mod pixel_picker;
+pub mod input;
+pub mod camera;
+pub mod sdf_scene;
+pub mod mesh;
\ No newline at end of file
@@ -1,6 +1,6 @@
 use std::rc::Rc;
 
-use wgpu::{util::DeviceExt, ShaderStages};
+use wgpu::util::DeviceExt;
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
@@ -8,34 +8,293 @@ use winit::{
     window::Window,
 };
 
-use test_wgpu::interlaced::InterlacedRendererState;
+use test_wgpu::interlaced::{InterlacedRendererState, MergeVariant, CrtSettings, ReconstructionFilter, TaaSettings};
+use test_wgpu::dynamic_resolution::DynamicResolutionController;
+use test_wgpu::surface::{SurfaceNegotiator, ColorSpace, next_present_mode};
+use test_wgpu::bloom::{BloomRenderer, BloomSettings, BLOOM_INPUT_FORMAT};
+use test_wgpu::frame_pacing::FramePacer;
+use test_wgpu::frame_stats::FrameStats;
+use test_wgpu::gpu_timer::GpuTimer;
+use test_wgpu::image_metrics::{ImageMetricsEvaluator, MetricsBackend};
+use test_wgpu::time_control::{TimeControl, FieldTime};
+use test_wgpu::overlay::EguiOverlay;
+use test_wgpu::field_inspector::{self, FieldInspector, InspectorView, TexelValues};
+use test_wgpu::pixel_picker::PixelPick;
+use test_wgpu::input::{InputState, ShaderInput, ActionBindings, Binding};
+use test_wgpu::camera::{Camera2d, Camera3d, CameraMode, Projection};
+use test_wgpu::sdf_scene::{SdfScene, SdfSceneBuffers, SdfSceneError};
+use test_wgpu::mesh::{Mesh, MeshData, MeshObject, MeshRenderer, Material, DepthBuffer};
+use serde::Deserialize;
+use egui_winit::egui;
+
+/// What is shown on the output, to compare interlaced rendering with progressive rendering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DisplayMode {
+    Interlaced,
+    /// The scene is rendered at full resolution straight to the output every frame.
+    Progressive,
+    /// Interlaced left of the cursor, progressive right of it.
+    Split,
+    /// Both field textures at native resolution instead of the merged frame.
+    FieldInspector,
+}
+
+impl DisplayMode {
+    const ALL: [DisplayMode; 4] = [DisplayMode::Interlaced, DisplayMode::Progressive, DisplayMode::Split, DisplayMode::FieldInspector];
+
+    fn next(self) -> Self {
+        match self {
+            DisplayMode::Interlaced => DisplayMode::Progressive,
+            DisplayMode::Progressive => DisplayMode::Split,
+            DisplayMode::Split => DisplayMode::FieldInspector,
+            DisplayMode::FieldInspector => DisplayMode::Interlaced,
+        }
+    }
+
+    /// Name of the CPU frame time series of the mode.
+    fn stats_series(self) -> &'static str {
+        match self {
+            DisplayMode::Interlaced => "cpu render interlaced",
+            DisplayMode::Progressive => "cpu render progressive",
+            DisplayMode::Split => "cpu render split",
+            DisplayMode::FieldInspector => "cpu render field inspector",
+        }
+    }
+}
+
+/// What the interlaced renderer does with the field of a frame. Every field advanced by the time control is drawn or skipped,
+/// so that the field index of the renderer stays the parity of the scene's frame number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FieldStep {
+    /// Merge the new field into the output.
+    Draw,
+    /// Move to the next field without merging.
+    Skip,
+    /// No new field (paused), the last merged frame may be drawn again.
+    Stay,
+}
+
+impl FieldStep {
+    /// `surface_acquired` is false when there is no output to merge to, the field rendered this frame is then skipped.
+    fn new(field_advanced: bool, display_mode: DisplayMode, surface_acquired: bool) -> Self {
+        match display_mode {
+            _ if !field_advanced => FieldStep::Stay,
+            DisplayMode::Interlaced | DisplayMode::Split if surface_acquired => FieldStep::Draw,
+            _ => FieldStep::Skip,
+        }
+    }
+}
+
+/// Scene drawn into the fields.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scene {
+    /// Circle hardcoded in `shader.wgsl`.
+    Builtin,
+    /// Shapes of an `SdfScene` evaluated by `sdf_scene.wgsl`.
+    Sdf,
+    /// 3D scene sphere traced by `raymarch.wgsl`, seen through the 3D camera. Hard-coded in the shader as it is the benchmark scene (see `raymarch.wgsl`).
+    Raymarch,
+    /// Meshes drawn by the `MeshRenderer` with a depth buffer, seen through the 3D camera.
+    Mesh,
+}
+
+impl Scene {
+    const ALL: [Scene; 4] = [Scene::Builtin, Scene::Sdf, Scene::Raymarch, Scene::Mesh];
+
+    fn next(self) -> Self {
+        match self {
+            Scene::Builtin => Scene::Sdf,
+            Scene::Sdf => Scene::Raymarch,
+            Scene::Raymarch => Scene::Mesh,
+            Scene::Mesh => Scene::Builtin,
+        }
+    }
+
+    fn is_3d(self) -> bool {
+        matches!(self, Scene::Raymarch | Scene::Mesh)
+    }
+}
+
+/// Meshes of the mesh scene.
+struct DemoMeshes {
+    plane: Rc<Mesh>,
+    cube: Rc<Mesh>,
+    sphere: Rc<Mesh>,
+}
+
+impl DemoMeshes {
+    fn new(device: &wgpu::Device) -> Self {
+        Self {
+            plane: Rc::new(Mesh::new(device, "Plane", &MeshData::plane(5.0))),
+            cube: Rc::new(Mesh::new(device, "Cube", &MeshData::cube())),
+            sphere: Rc::new(Mesh::new(device, "Sphere", &MeshData::sphere(48, 24))),
+        }
+    }
+
+    /// Ground, spinning cube and bouncing sphere at a scene time in seconds, laid out like the raymarched scene.
+    fn objects(&self, time: f32) -> Vec<MeshObject> {
+        vec![
+            MeshObject {
+                mesh: self.plane.clone(),
+                transform: glam::Mat4::from_translation(glam::Vec3::new(0.0, -1.0, 0.0)),
+                material: Material { color: glam::Vec3::splat(0.45), specular: 0.1, shininess: 8.0 },
+            },
+            MeshObject {
+                mesh: self.cube.clone(),
+                transform: glam::Mat4::from_translation(glam::Vec3::new(0.0, -0.6, 0.0)) * glam::Mat4::from_rotation_y(time * 0.7) * glam::Mat4::from_scale(glam::Vec3::new(1.4, 0.8, 1.4)),
+                material: Material { color: glam::Vec3::new(0.9, 0.6, 0.3), ..Default::default() },
+            },
+            MeshObject {
+                mesh: self.sphere.clone(),
+                transform: glam::Mat4::from_translation(glam::Vec3::new(1.8, 0.1 + 0.35 * (time * 1.5).sin(), -0.5)) * glam::Mat4::from_scale(glam::Vec3::splat(0.55)),
+                material: Material { color: glam::Vec3::new(0.65, 0.85, 1.0), specular: 0.8, shininess: 64.0 },
+            },
+        ]
+    }
+}
+
+/// Application actions, bound to inputs by `create_action_bindings`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+enum Action {
+    ToggleCrt,
+    ToggleTaa,
+    ToggleDynamicResolution,
+    CycleReconstructionFilter,
+    CycleSampleCount,
+    ToggleBloom,
+    CyclePresentMode,
+    /// Interlaced, progressive, split screen and field inspector.
+    CycleDisplayMode,
+    MeasureImageQuality,
+    /// Read back the pixel under the cursor.
+    PickPixel,
+    /// Cycle the fields side by side, stacked or their difference, in the field inspector.
+    ToggleInspectorView,
+    ResetInspectorZoom,
+    /// Zoom the field inspector around the cursor.
+    InspectorZoomIn,
+    InspectorZoomOut,
+    /// Pan the field inspector while held.
+    InspectorPan,
+    /// Move the camera along the axes of the view while held (forward and backward for the up and down actions of a flying 3D camera).
+    CameraLeft,
+    CameraRight,
+    CameraUp,
+    CameraDown,
+    CameraRotateClockwise,
+    CameraRotateCounterclockwise,
+    /// Zoom the camera around the cursor.
+    CameraZoomIn,
+    CameraZoomOut,
+    /// The scene follows the cursor while held, the 3D camera turns with it.
+    CameraDrag,
+    ResetCamera,
+    /// Orbit or fly 3D camera.
+    ToggleCameraMode,
+    ToggleOverlay,
+    TogglePause,
+    StepField,
+    ToggleFixedStep,
+    ResetTime,
+    CycleFieldRateCap,
+    /// Only render when a window event happens.
+    ToggleIdle,
+    CycleScene,
+    /// Load the SDF scene file again.
+    ReloadScene,
+}
+
+/// Field rate caps cycled through at runtime, two fields make a full frame.
+const FIELD_RATE_CAPS: [Option<f64>; 5] = [Some(120.0), Some(60.0), Some(240.0), Some(30.0), None];
+
+/// GPU timer scopes of the work depending on the field resolution, which drives the dynamic resolution.
+const GPU_SCENE_SCOPE: &str = "gpu scene";
+const GPU_MERGE_SCOPE: &str = "gpu merge";
 
 struct State {
     surface: wgpu::Surface,
     device: Rc<wgpu::Device>,
     queue: Rc<wgpu::Queue>,
     config: wgpu::SurfaceConfiguration,
+    supported_present_modes: Vec<wgpu::PresentMode>,
+    /// Present mode to switch to before the next frame.
+    pending_present_mode: Option<wgpu::PresentMode>,
     size: winit::dpi::PhysicalSize<u32>,
     window: Window,
     clear_color: wgpu::Color,
     render_pipeline: wgpu::RenderPipeline,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    /// Scene pipeline drawing to the output format, for the progressive mode.
+    progressive_pipeline: wgpu::RenderPipeline,
+    display_mode: DisplayMode,
+    /// Scene pipeline drawing to Rgba8Unorm without multisampling, for the reference of the image metrics.
+    reference_pipeline: wgpu::RenderPipeline,
+    image_metrics: ImageMetricsEvaluator,
+    /// Compare the interlaced output to a progressive reference at the next frame.
+    image_metrics_requested: bool,
+    shader: wgpu::ShaderModule,
+    sdf_shader: wgpu::ShaderModule,
+    raymarch_shader: wgpu::ShaderModule,
+    scene: Scene,
+    sdf_scene: SdfSceneBuffers,
+    mesh_renderer: MeshRenderer,
+    demo_meshes: DemoMeshes,
+    /// Depth of the fields and of the output, only allocated for the mesh scene.
+    field_depth: DepthBuffer,
+    output_depth: DepthBuffer,
+    /// Sample counts usable for the scene, in increasing order.
+    supported_sample_counts: Vec<u32>,
     interlaced_renderer: InterlacedRendererState,
+    bloom: BloomRenderer,
+    bloom_enabled: bool,
+    dynamic_resolution: DynamicResolutionController,
+    dynamic_resolution_enabled: bool,
+    frame_pacer: FramePacer,
+    frame_stats: FrameStats,
+    gpu_timer: GpuTimer,
+    /// Start of the previous frame, to measure the frame interval.
+    last_frame_start: Option<std::time::Instant>,
     uniform_buffer: wgpu::Buffer,
-    mouse_pos: [f32; 3],
-    frame_number: u64,
+    input: InputState,
+    action_bindings: ActionBindings<Action>,
+    camera: Camera2d,
+    camera_buffer: wgpu::Buffer,
+    camera3d: Camera3d,
+    camera3d_buffer: wgpu::Buffer,
+    /// Time of the last update, to move the camera at a steady speed.
+    last_update: Option<std::time::Instant>,
+    time_control: TimeControl,
+    /// Field rendered by the current frame.
+    current_field: FieldTime,
+    /// False while paused: the last frame is drawn again instead of a new field.
+    field_advanced: bool,
+    overlay: EguiOverlay,
+    /// Field textures as shown by the overlay, registered on first use.
+    field_texture_ids: [Option<egui::TextureId>; 2],
+    field_inspector: FieldInspector,
+    /// Read back the pixel under the cursor at the next frame.
+    pixel_pick_requested: bool,
+    last_pixel_pick: Option<PixelPick>,
     bind_group: wgpu::BindGroup,
 }
 
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
 struct MyUniform {
-    mouse_pos: [f32; 4],
+    input: ShaderInput,
     frame_number: u64,
     width: u32,
     height: u32,
+    /// Sub-pixel offset of the field, in clip space units (see `InterlacedRendererState::scene_offset`).
+    jitter: [f32; 2],
+    /// Scene time in seconds.
+    time: f32,
+    padding: f32,
 }
 
 impl State {
     // Creating some of the wgpu types requires async code
-    async fn new(window: Window) -> Self {
+    async fn new(window: Window, event_loop: &EventLoop<()>) -> Self {
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -61,7 +320,9 @@ impl State {
 
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: wgpu::Features::empty(),
+                // allows multisampling with other sample counts than 4, if the adapter supports it
+                // timestamp queries are optional, GPU times are simply not measured without them
+                features: adapter.features() & (wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES | wgpu::Features::TIMESTAMP_QUERY),
                 limits: wgpu::Limits::default(),
                 label: None,
             },
@@ -72,20 +333,18 @@ impl State {
         // Shader code in this tutorial assumes an sRGB surface texture. Using a different
         // one will result all the colors coming out darker. If you want to support non
         // sRGB surfaces, you'll need to account for that when drawing to the frame.
-        let surface_format = surface_caps.formats.iter()
-            .copied()
-            .filter(|f| f.describe().srgb)
-            .next()
-            .unwrap_or(surface_caps.formats[0]);
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: test_wgpu::utils::select_prefered_presentmode(&surface_caps.present_modes, &vec![wgpu::PresentMode::Mailbox, wgpu::PresentMode::Fifo]).unwrap(),
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-        };
+        let negotiated_surface = SurfaceNegotiator::new()
+            .with_color_spaces(vec![ColorSpace::Srgb])
+            .with_present_modes(vec![wgpu::PresentMode::Mailbox, wgpu::PresentMode::Fifo])
+            .negotiate(&surface_caps, size.width, size.height)
+            .unwrap();
+
+        for fallback in &negotiated_surface.fallbacks {
+            log::warn!("surface configuration fallback: {}", fallback);
+        }
+
+        let config = negotiated_surface.config;
+        let supported_present_modes = surface_caps.present_modes.clone();
 
         surface.configure(&device, &config);
 
@@ -96,9 +355,8 @@ impl State {
             a: 1.0,
         };
 
-        let mouse_pos = [0.0, 0.0, 0.0, 0.0]; // [3] is to tell shader code whether we need to draw mouse circle or not. The rest is useless but WGPU requires buffer size to be a power of 2.
 
-        let uniform_data = MyUniform { mouse_pos, frame_number: 0, width: size.width, height: size.height };
+        let uniform_data = MyUniform { input: ShaderInput::default(), frame_number: 0, width: size.width, height: size.height, jitter: [0.0, 0.0], time: 0.0, padding: 0.0 };
 
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Uniform Buffer"),
@@ -106,18 +364,43 @@ impl State {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let bind_group_layout = test_wgpu::utils::create_bind_group_layout(&device, Some("bind_group_layout"), vec![wgpu::BindingType::Buffer {
+        let camera = Camera2d::new(size.width, size.height);
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: unsafe { test_wgpu::utils::any_as_u8_slice(&camera.uniform()) },
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let camera3d = create_camera3d(size.width, size.height);
+        let camera3d_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera 3D Buffer"),
+            contents: unsafe { test_wgpu::utils::any_as_u8_slice(&camera3d.uniform()) },
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniform_binding = wgpu::BindingType::Buffer {
             ty: wgpu::BufferBindingType::Uniform,
             has_dynamic_offset: false,
             min_binding_size: None,
-        }], wgpu::ShaderStages::FRAGMENT);
+        };
+        let bind_group_layout = test_wgpu::utils::create_bind_group_layout(&device, Some("bind_group_layout"), vec![uniform_binding, uniform_binding, uniform_binding], wgpu::ShaderStages::VERTEX_FRAGMENT);
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: camera3d_buffer.as_entire_binding(),
+                },
+            ],
             label: Some("uniform_bind_group"),
         });
 
@@ -126,17 +409,48 @@ impl State {
             source: wgpu::ShaderSource::Wgsl(include_str!("shaders/shader.wgsl").into()),
         });
 
+        let sdf_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SDF Scene Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sdf_scene.wgsl").into()),
+        });
+
+        let raymarch_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Raymarch Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/raymarch.wgsl").into()),
+        });
+
+        let device_rc = Rc::new(device);
+        let queue_rc = Rc::new(queue);
+        let initial_scene = load_sdf_scene().unwrap_or_else(|e| {
+            log::error!("can not load the SDF scene: {}", e);
+            SdfScene::from_ron_str(DEFAULT_SDF_SCENE).unwrap()
+        });
+        let sdf_scene = SdfSceneBuffers::new(device_rc.clone(), queue_rc.clone(), &initial_scene);
+        let mesh_renderer = MeshRenderer::new(device_rc.clone(), queue_rc.clone(), &bind_group_layout);
+        let demo_meshes = DemoMeshes::new(&device_rc);
+        let device = &*device_rc;
+
+        // the builtin scene ignores the SDF scene group, both scenes share the layout
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout], // the final pass uses the texture as input to produce the on-screen image with some transform
+            bind_group_layouts: &[&bind_group_layout, sdf_scene.bind_group_layout()], // the final pass uses the texture as input to produce the on-screen image with some transform
             push_constant_ranges: &[],
         });
 
-        let render_pipeline = test_wgpu::utils::create_render_pipeline(&device, None, &[], &render_pipeline_layout, &shader, wgpu::TextureFormat::Rgba8Unorm);
+        let render_pipeline = test_wgpu::utils::create_render_pipeline(device, None, &[], &render_pipeline_layout, &shader, wgpu::TextureFormat::Rgba8Unorm);
+
+        let progressive_pipeline = test_wgpu::utils::create_render_pipeline(device, Some("Progressive Render Pipeline"), &[], &render_pipeline_layout, &shader, config.format);
+        let reference_pipeline = test_wgpu::utils::create_render_pipeline(device, Some("Reference Render Pipeline"), &[], &render_pipeline_layout, &shader, wgpu::TextureFormat::Rgba8Unorm);
+        let metrics_backend = MetricsBackend::for_adapter(&adapter);
+
+        let supported_sample_counts = test_wgpu::utils::supported_sample_counts(&adapter, device.features(), wgpu::TextureFormat::Rgba8Unorm);
 
-        let device_rc = Rc::new(device);
-        let queue_rc = Rc::new(queue);
         let interlaced_renderer = InterlacedRendererState::new(device_rc.clone(), queue_rc.clone(), size.width, size.height, config.format, include_str!("shaders/merge.wgsl"));
+        let image_metrics = ImageMetricsEvaluator::new(device_rc.clone(), queue_rc.clone(), metrics_backend);
+        let overlay = EguiOverlay::new(device_rc.clone(), queue_rc.clone(), event_loop, &window, config.format);
+        let gpu_timer = GpuTimer::new(device_rc.clone(), queue_rc.clone(), 4);
+        let bloom = BloomRenderer::new(device_rc.clone(), queue_rc.clone(), size.width, size.height, config.format, BloomSettings::default());
+        let field_inspector = FieldInspector::new(device_rc.clone(), queue_rc.clone(), config.format);
 
         Self {
             window,
@@ -144,13 +458,53 @@ impl State {
             device: device_rc,
             queue: queue_rc,
             config,
+            supported_present_modes,
+            pending_present_mode: None,
             size,
             clear_color,
             render_pipeline,
+            render_pipeline_layout,
+            progressive_pipeline,
+            display_mode: DisplayMode::Interlaced,
+            reference_pipeline,
+            image_metrics,
+            image_metrics_requested: false,
+            shader,
+            sdf_shader,
+            raymarch_shader,
+            scene: Scene::Builtin,
+            sdf_scene,
+            mesh_renderer,
+            demo_meshes,
+            field_depth: DepthBuffer::new("Field depth texture"),
+            output_depth: DepthBuffer::new("Output depth texture"),
+            supported_sample_counts,
             interlaced_renderer,
+            bloom,
+            bloom_enabled: false,
+            // 60 full frames per second
+            dynamic_resolution: DynamicResolutionController::new(std::time::Duration::from_micros(16_667)),
+            dynamic_resolution_enabled: false,
+            frame_pacer: FramePacer::new(FIELD_RATE_CAPS[0]),
+            frame_stats: create_frame_stats(),
+            gpu_timer,
+            last_frame_start: None,
             uniform_buffer,
-            frame_number: 0,
-            mouse_pos: [mouse_pos[0], mouse_pos[1], mouse_pos[2]],
+            time_control: TimeControl::new(),
+            current_field: FieldTime { frame_number: 0, time: 0.0 },
+            field_advanced: false,
+            overlay,
+            field_texture_ids: [None, None],
+            field_inspector,
+            pixel_pick_requested: false,
+            last_pixel_pick: None,
+            input: InputState::new(size.width, size.height),
+            action_bindings: create_action_bindings(),
+            camera,
+            camera_buffer,
+            camera3d,
+            camera3d_buffer,
+            last_update: None,
             bind_group,
         }
     }
@@ -159,6 +513,33 @@ impl State {
         &self.window
     }
 
+    /// Show the active settings in the window title.
+    fn update_title(&self) {
+        let field_rate = match self.frame_pacer.target_fps() {
+            Some(fps) => format!("{:.0} fields/s", fps),
+            None => "uncapped".to_string(),
+        };
+        let idle = if self.frame_pacer.is_idle() { " (idle)" } else { "" };
+        let time = match (self.time_control.is_paused(), self.time_control.fixed_step()) {
+            (true, _) => " - paused",
+            (false, Some(_)) => " - fixed time step",
+            (false, None) => "",
+        };
+        self.window.set_title(&format!("test-wgpu - {:?} - present mode: {:?} - {}{}{}", self.display_mode, self.config.present_mode, field_rate, idle, time));
+    }
+
+    /// Reconfigure the surface if a new present mode was requested, must be called between frames.
+    fn apply_present_mode(&mut self) {
+        if let Some(present_mode) = self.pending_present_mode.take() {
+            if present_mode != self.config.present_mode {
+                log::info!("present mode: {:?}", present_mode);
+                self.config.present_mode = present_mode;
+                self.surface.configure(&self.device, &self.config);
+                self.update_title();
+            }
+        }
+    }
+
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
@@ -166,59 +547,343 @@ impl State {
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
             self.interlaced_renderer.resize(new_size.width, new_size.height);
+            self.bloom.resize(new_size.width, new_size.height);
+            self.input.set_window_size(new_size.width, new_size.height);
+            self.camera.set_viewport(new_size.width, new_size.height);
+            self.camera3d.set_viewport(new_size.width, new_size.height);
         }
     }
 
+    /// Track input events, the actions they trigger are handled by `update`.
     fn input(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::CursorMoved { position, .. } => {
-                self.mouse_pos[0] = (position.x / (self.size.width as f64)) as f32;
-                self.mouse_pos[1] = (position.y / (self.size.height as f64)) as f32;
+        self.input.process_event(event)
+    }
 
-                true
-            },
-            WindowEvent::MouseInput { 
-                state: ElementState::Pressed,
-                ..
-            } => {
-                self.mouse_pos[2] = 2.0;
-                false
-            },
-            WindowEvent::MouseInput { 
-                state: ElementState::Released,
-                .. 
-            } => {
-                self.mouse_pos[2] = 0.0;
-                false
+    /// Apply the actions triggered by the input of the frame.
+    fn handle_actions(&mut self) {
+        let triggered: Vec<Action> = self.action_bindings.triggered_actions(&self.input).collect();
+
+        for action in triggered {
+            match action {
+                Action::ToggleCrt => {
+                    let variant = match self.interlaced_renderer.merge_variant() {
+                        MergeVariant::Crt(_) => MergeVariant::Standard,
+                        _ => MergeVariant::Crt(CrtSettings::default()),
+                    };
+                    self.interlaced_renderer.set_merge_variant(variant);
+                },
+                Action::ToggleTaa => {
+                    let variant = match self.interlaced_renderer.merge_variant() {
+                        MergeVariant::Taa(_) => MergeVariant::Standard,
+                        _ => MergeVariant::Taa(TaaSettings::default()),
+                    };
+                    self.interlaced_renderer.set_merge_variant(variant);
+                },
+                Action::ToggleDynamicResolution => {
+                    // going back to full resolution when disabled
+                    self.dynamic_resolution_enabled = !self.dynamic_resolution_enabled;
+                    self.dynamic_resolution.reset();
+                    self.interlaced_renderer.set_render_scale(self.dynamic_resolution.scale());
+                },
+                Action::CycleReconstructionFilter => {
+                    // upscaling filter used when fields are rendered at a lower resolution
+                    let filter = match self.interlaced_renderer.reconstruction_filter() {
+                        ReconstructionFilter::Nearest => ReconstructionFilter::Easu,
+                        ReconstructionFilter::Easu => ReconstructionFilter::EasuRcas { sharpness: 0.8 },
+                        ReconstructionFilter::EasuRcas { .. } => ReconstructionFilter::Nearest,
+                    };
+                    log::info!("reconstruction filter: {:?}", filter);
+                    self.interlaced_renderer.set_reconstruction_filter(filter);
+                },
+                Action::CycleSampleCount => {
+                    let current = self.interlaced_renderer.sample_count();
+                    let next = self.supported_sample_counts.iter().copied().find(|&count| count > current).unwrap_or(1);
+                    self.set_sample_count(next);
+                },
+                Action::ToggleBloom => self.set_bloom_enabled(!self.bloom_enabled),
+                Action::CyclePresentMode => {
+                    // vsync on/off, applied before the next frame
+                    let current = self.pending_present_mode.unwrap_or(self.config.present_mode);
+                    self.pending_present_mode = Some(next_present_mode(&self.supported_present_modes, current));
+                },
+                Action::CycleDisplayMode => {
+                    self.display_mode = self.display_mode.next();
+                    self.update_title();
+                },
+                Action::MeasureImageQuality => {
+                    // compare the next interlaced frame to a progressive reference
+                    if matches!(self.display_mode, DisplayMode::Progressive | DisplayMode::FieldInspector) {
+                        log::warn!("image quality: there is no interlaced output to measure in {:?} mode", self.display_mode);
+                    } else {
+                        self.image_metrics_requested = true;
+                    }
+                },
+                Action::PickPixel => self.pixel_pick_requested = true,
+                Action::ToggleInspectorView if self.display_mode == DisplayMode::FieldInspector => {
+                    let view = match self.field_inspector.view() {
+                        InspectorView::SideBySide => InspectorView::Stacked,
+                        InspectorView::Stacked => InspectorView::Difference,
+                        InspectorView::Difference => InspectorView::SideBySide,
+                    };
+                    self.field_inspector.set_view(view);
+                },
+                Action::ResetInspectorZoom if self.display_mode == DisplayMode::FieldInspector => self.field_inspector.reset_zoom(),
+                Action::ResetCamera if self.display_mode != DisplayMode::FieldInspector => {
+                    if self.scene.is_3d() {
+                        self.camera3d = create_camera3d(self.size.width, self.size.height);
+                    } else {
+                        self.camera.reset();
+                    }
+                },
+                Action::ToggleCameraMode if self.scene.is_3d() => {
+                    let mode = match self.camera3d.mode() {
+                        CameraMode::Orbit => CameraMode::Fly,
+                        CameraMode::Fly => CameraMode::Orbit,
+                    };
+                    self.camera3d.set_mode(mode);
+                },
+                Action::ToggleOverlay => self.overlay.set_visible(!self.overlay.is_visible()),
+                Action::TogglePause => {
+                    self.time_control.set_paused(!self.time_control.is_paused());
+                    self.update_title();
+                },
+                // single step of one field while paused
+                Action::StepField => self.time_control.step(),
+                Action::ToggleFixedStep => {
+                    // one field at 120 fields per second, for reproducible field sequences
+                    let fixed_step = match self.time_control.fixed_step() {
+                        Some(_) => None,
+                        None => Some(std::time::Duration::from_micros(8_333)),
+                    };
+                    self.time_control.set_fixed_step(fixed_step);
+                    self.update_title();
+                },
+                Action::ResetTime => self.time_control.set_time(0.0),
+                Action::CycleFieldRateCap => {
+                    let current = FIELD_RATE_CAPS.iter().position(|&cap| cap == self.frame_pacer.target_fps()).unwrap_or(0);
+                    self.frame_pacer.set_target_fps(FIELD_RATE_CAPS[(current + 1) % FIELD_RATE_CAPS.len()]);
+                    self.update_title();
+                },
+                Action::ToggleIdle => {
+                    // in idle mode, only render when a window event happens
+                    self.frame_pacer.set_idle(!self.frame_pacer.is_idle());
+                    self.update_title();
+                },
+                Action::CycleScene => self.set_scene(self.scene.next()),
+                Action::ReloadScene => self.reload_sdf_scene(),
+                _ => {},
             }
-            _ => {
-                false
+        }
+
+        if self.display_mode != DisplayMode::FieldInspector {
+            self.move_camera();
+        } else {
+            let zoom = self.action_bindings.amount(Action::InspectorZoomIn, &self.input) - self.action_bindings.amount(Action::InspectorZoomOut, &self.input);
+            if zoom != 0.0 {
+                self.field_inspector.zoom_at(self.input.cursor_pixel(), [self.size.width, self.size.height], 1.25f32.powf(zoom));
+            }
+
+            if self.action_bindings.is_active(Action::InspectorPan, &self.input) {
+                self.field_inspector.pan_by(self.input.cursor_delta());
             }
         }
     }
 
+    /// Pan, zoom and rotate the camera from the input of the frame.
+    fn move_camera(&mut self) {
+        let now = std::time::Instant::now();
+        let elapsed = self.last_update.replace(now).map_or(0.0, |last_update| (now - last_update).as_secs_f32()).min(0.1);
+
+        let axis = |bindings: &ActionBindings<Action>, negative: Action, positive: Action| {
+            let value = |action| if bindings.is_active(action, &self.input) { 1.0 } else { 0.0 };
+            value(positive) - value(negative)
+        };
+
+        let pan = glam::Vec2::new(axis(&self.action_bindings, Action::CameraLeft, Action::CameraRight), axis(&self.action_bindings, Action::CameraDown, Action::CameraUp));
+        let rotation = axis(&self.action_bindings, Action::CameraRotateClockwise, Action::CameraRotateCounterclockwise) * std::f32::consts::FRAC_PI_2 * elapsed;
+        let zoom = self.action_bindings.amount(Action::CameraZoomIn, &self.input) - self.action_bindings.amount(Action::CameraZoomOut, &self.input);
+        let drag = self.action_bindings.is_active(Action::CameraDrag, &self.input).then(|| glam::Vec2::from(self.input.cursor_delta()));
+
+        if self.scene.is_3d() {
+            self.move_camera3d(pan, rotation, zoom, drag, elapsed);
+            return;
+        }
+
+        // half a view height per second
+        self.camera.pan(pan * 0.5 * elapsed);
+        self.camera.rotate(rotation);
+
+        if zoom != 0.0 {
+            self.camera.zoom_at(glam::Vec2::from(self.input.cursor_ndc()), 1.25f32.powf(zoom));
+        }
+
+        if let Some(delta) = drag {
+            self.camera.drag(delta);
+        }
+    }
+
+    /// Orbit: pan the target, turn around it and get closer. Fly: move forward and sideways, and look around.
+    fn move_camera3d(&mut self, pan: glam::Vec2, rotation: f32, zoom: f32, drag: Option<glam::Vec2>, elapsed: f32) {
+        // radians per dragged pixel
+        const DRAG_SENSITIVITY: f32 = 0.005;
+
+        self.camera3d.rotate(rotation, 0.0);
+
+        match self.camera3d.mode() {
+            CameraMode::Orbit => {
+                // half the orbit distance per second
+                let speed = self.camera3d.distance * 0.5 * elapsed;
+                self.camera3d.translate(glam::Vec3::new(pan.x * speed, pan.y * speed, 0.0));
+                if zoom != 0.0 {
+                    self.camera3d.zoom(0.8f32.powf(zoom));
+                }
+                // the scene follows the cursor
+                if let Some(delta) = drag {
+                    self.camera3d.rotate(-delta.x * DRAG_SENSITIVITY, -delta.y * DRAG_SENSITIVITY);
+                }
+            },
+            CameraMode::Fly => {
+                // 2 units per second, or half a unit per scrolled line
+                let speed = 2.0 * elapsed;
+                self.camera3d.translate(glam::Vec3::new(pan.x * speed, 0.0, pan.y * speed + zoom * 0.5));
+                if let Some(delta) = drag {
+                    self.camera3d.rotate(delta.x * DRAG_SENSITIVITY, delta.y * DRAG_SENSITIVITY);
+                }
+            },
+        }
+    }
+
     fn update(&mut self) {
-        // mouse position data is [0; 1] but shader use the [-1; 1] format (with Y being 1 at top and -1 at bottom).
-        let mut mouse_pos = self.mouse_pos.clone();
-        mouse_pos[0] *= 2.0;
-        mouse_pos[0] -= 1.0;
-        mouse_pos[1] *= 2.0;
-        mouse_pos[1] -= 1.0;
-        mouse_pos[1] = -mouse_pos[1];
+        self.handle_actions();
+
+        self.field_advanced = match self.time_control.advance(std::time::Instant::now()) {
+            Some(field) => {
+                self.current_field = field;
+                true
+            },
+            None => false,
+        };
+
+        if self.field_advanced {
+            // every advanced field is drawn or skipped by the interlaced renderer, the scene parity is the one of the field texture written
+            debug_assert_eq!((self.current_field.frame_number & 1) as usize, self.interlaced_renderer.current_field());
+
+            // the progressive scene is neither jittered, as it is not accumulated, nor split in fields
+            let jitter = if self.display_mode == DisplayMode::Progressive { [0.0, 0.0] } else { self.interlaced_renderer.scene_offset() };
+            self.write_uniform(jitter);
+
+            if self.scene == Scene::Mesh {
+                self.mesh_renderer.set_objects(self.demo_meshes.objects(self.current_field.time as f32));
+            }
+        }
+
+        self.input.end_frame();
+    }
 
+    /// Write the scene uniform for the current field.
+    fn write_uniform(&mut self, jitter: [f32; 2]) {
         let uniform_data = MyUniform {
-            mouse_pos: [mouse_pos[0], mouse_pos[1], mouse_pos[2], 0.0],
-            frame_number: self.frame_number,
+            input: self.input.shader_input(),
+            frame_number: self.current_field.frame_number,
             height: self.size.height,
             width: self.size.width,
+            jitter,
+            time: self.current_field.time as f32,
+            padding: 0.0,
         };
 
         self.queue.write_buffer(&self.uniform_buffer, 0, unsafe { test_wgpu::utils::any_as_u8_slice(&uniform_data) });
+        self.queue.write_buffer(&self.camera_buffer, 0, unsafe { test_wgpu::utils::any_as_u8_slice(&self.camera.uniform()) });
+
+        // the projection carries the offset of the field
+        self.camera3d.set_jitter(glam::Vec2::from(jitter));
+        self.queue.write_buffer(&self.camera3d_buffer, 0, unsafe { test_wgpu::utils::any_as_u8_slice(&self.camera3d.uniform()) });
+    }
+
+    /// Multisample the scene with the highest supported sample count up to the desired one.
+    fn set_sample_count(&mut self, desired_count: u32) {
+        let sample_count = test_wgpu::utils::select_sample_count(&self.supported_sample_counts, desired_count);
+        log::info!("MSAA: {}x", sample_count);
+
+        self.interlaced_renderer.set_sample_count(sample_count);
+        self.rebuild_scene_pipelines();
+    }
+
+    fn scene_shader(&self) -> &wgpu::ShaderModule {
+        match self.scene {
+            Scene::Builtin => &self.shader,
+            Scene::Sdf => &self.sdf_shader,
+            Scene::Raymarch => &self.raymarch_shader,
+            // drawn by the mesh renderer, see `rebuild_scene_pipelines`
+            Scene::Mesh => &self.shader,
+        }
+    }
+
+    /// Create the scene pipelines again for the current scene, sample count and output format.
+    fn rebuild_scene_pipelines(&mut self) {
+        let target = if self.bloom_enabled { BLOOM_INPUT_FORMAT } else { self.config.format };
+
+        if self.scene == Scene::Mesh {
+            self.render_pipeline = self.mesh_renderer.create_pipeline(None, wgpu::TextureFormat::Rgba8Unorm, self.interlaced_renderer.sample_count());
+            self.progressive_pipeline = self.mesh_renderer.create_pipeline(Some("Progressive Mesh Pipeline"), target, 1);
+            self.reference_pipeline = self.mesh_renderer.create_pipeline(Some("Reference Mesh Pipeline"), wgpu::TextureFormat::Rgba8Unorm, 1);
+            return;
+        }
+
+        self.render_pipeline = test_wgpu::utils::create_render_pipeline_with_options(&self.device, None, &[], &self.render_pipeline_layout, self.scene_shader(), wgpu::TextureFormat::Rgba8Unorm, test_wgpu::utils::RenderPipelineOptions {
+            sample_count: self.interlaced_renderer.sample_count(),
+            ..Default::default()
+        });
+        self.progressive_pipeline = test_wgpu::utils::create_render_pipeline(&self.device, Some("Progressive Render Pipeline"), &[], &self.render_pipeline_layout, self.scene_shader(), target);
+        self.reference_pipeline = test_wgpu::utils::create_render_pipeline(&self.device, Some("Reference Render Pipeline"), &[], &self.render_pipeline_layout, self.scene_shader(), wgpu::TextureFormat::Rgba8Unorm);
+    }
+
+    fn set_scene(&mut self, scene: Scene) {
+        if scene != self.scene {
+            log::info!("scene: {:?}", scene);
+            self.scene = scene;
+            self.rebuild_scene_pipelines();
+
+            // objects are otherwise only updated when a field is rendered
+            if scene == Scene::Mesh {
+                self.mesh_renderer.set_objects(self.demo_meshes.objects(self.current_field.time as f32));
+            }
+        }
+    }
+
+    /// Load the SDF scene again, the current one is kept if the file is invalid.
+    fn reload_sdf_scene(&mut self) {
+        match load_sdf_scene() {
+            Ok(scene) => {
+                self.sdf_scene.set_scene(&scene);
+                log::info!("SDF scene: {} shapes", self.sdf_scene.shape_count());
+            },
+            Err(e) => log::error!("can not load the SDF scene: {}", e),
+        }
+    }
 
-        self.frame_number += 1;
+    /// With bloom, the interlaced renderer draws to the bloom input instead of the surface.
+    fn set_bloom_enabled(&mut self, enabled: bool) {
+        self.bloom_enabled = enabled;
+        let target = if self.bloom_enabled { BLOOM_INPUT_FORMAT } else { self.config.format };
+        self.interlaced_renderer.set_target_format(target);
+        self.rebuild_scene_pipelines();
     }
 
-    fn render_to_texture(&self, view: &wgpu::TextureView) {
+    /// Adapt the field resolution to the time taken to render and merge the last field.
+    fn update_render_scale(&mut self, frame_time: std::time::Duration) {
+        if !self.dynamic_resolution_enabled {
+            return;
+        }
+
+        if let Some(scale) = self.dynamic_resolution.update(frame_time) {
+            log::info!("dynamic resolution: render scale {}", scale);
+            self.interlaced_renderer.set_render_scale(scale);
+        }
+    }
+
+    /// Render the scene to a texture, resolving it into another one when multisampling.
+    fn render_to_texture(&self, view: &wgpu::TextureView, resolve_target: Option<&wgpu::TextureView>) {
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
@@ -227,35 +892,564 @@ impl State {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: view,
-                    resolve_target: None,
+                    view,
+                    resolve_target,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(self.clear_color),
                         store: true,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: if self.scene == Scene::Mesh { self.field_depth.attachment() } else { None },
             });
     
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
+            self.draw_scene(&mut render_pass, &self.render_pipeline);
         }
     
         self.queue.submit(std::iter::once(encoder.finish()));
     }
 
+    /// Render the scene at full resolution to the output, only right of `split_x` (the rest of the output is kept).
+    fn render_progressive(&self, view: &wgpu::TextureView, pipeline: &wgpu::RenderPipeline, split_x: u32) {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Progressive Render Encoder"),
+        });
+
+        {
+            let load = if split_x == 0 { wgpu::LoadOp::Clear(self.clear_color) } else { wgpu::LoadOp::Load };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Progressive Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: if self.scene == Scene::Mesh { self.output_depth.attachment() } else { None },
+            });
+
+            render_pass.set_scissor_rect(split_x, 0, self.size.width - split_x, self.size.height);
+            self.draw_scene(&mut render_pass, pipeline);
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Record the draws of the current scene, with one of the scene pipelines.
+    fn draw_scene<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, pipeline: &'a wgpu::RenderPipeline) {
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+
+        if self.scene == Scene::Mesh {
+            self.mesh_renderer.draw(render_pass, pipeline);
+        } else {
+            // a single triangle: the builtin scene is drawn inside of it, the other scenes cover the whole target
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(1, self.sdf_scene.bind_group(), &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    /// Allocate the depth buffers of the mesh scene for the current field and output sizes.
+    fn prepare_depth_buffers(&mut self) {
+        if self.scene != Scene::Mesh {
+            return;
+        }
+
+        let (field_width, field_height) = self.interlaced_renderer.field_size();
+        self.field_depth.prepare(&self.device, field_width, field_height, self.interlaced_renderer.sample_count());
+        self.output_depth.prepare(&self.device, self.size.width, self.size.height, 1);
+    }
+
+    /// Debug overlay: renderer settings, frame time graphs and field textures.
+    fn overlay_ui(&mut self, context: &egui::Context) {
+        egui::Window::new("Interlaced renderer").default_pos([10.0, 10.0]).show(context, |ui| {
+            egui::CollapsingHeader::new("Settings").default_open(true).show(ui, |ui| {
+                let previous_mode = self.display_mode;
+                ui.horizontal(|ui| {
+                    ui.label("Mode");
+                    for mode in DisplayMode::ALL {
+                        ui.selectable_value(&mut self.display_mode, mode, format!("{:?}", mode));
+                    }
+                });
+                if self.display_mode != previous_mode {
+                    self.update_title();
+                }
+
+                let field = if self.current_field.frame_number & 1 == 0 { "even rows" } else { "odd rows" };
+                ui.label(format!("Fields rendered: {} (current: {})", self.time_control.field_count(), field));
+                ui.label(format!("Scene time: {:.3} s", self.current_field.time));
+
+                let mut paused = self.time_control.is_paused();
+                ui.horizontal(|ui| {
+                    if ui.checkbox(&mut paused, "Paused").changed() {
+                        self.time_control.set_paused(paused);
+                        self.update_title();
+                    }
+                    if ui.add_enabled(paused, egui::Button::new("Step field")).clicked() {
+                        self.time_control.step();
+                    }
+                });
+
+                let variant = self.interlaced_renderer.merge_variant();
+                let mut new_variant = variant;
+                egui::ComboBox::from_label("Deinterlace").selected_text(merge_variant_name(variant)).show_ui(ui, |ui| {
+                    for candidate in [MergeVariant::Standard, MergeVariant::Crt(CrtSettings::default()), MergeVariant::Taa(TaaSettings::default())] {
+                        if ui.selectable_label(merge_variant_name(variant) == merge_variant_name(candidate), merge_variant_name(candidate)).clicked() {
+                            new_variant = candidate;
+                        }
+                    }
+                });
+                if new_variant != variant {
+                    self.interlaced_renderer.set_merge_variant(new_variant);
+                }
+
+                let filter = self.interlaced_renderer.reconstruction_filter();
+                let mut new_filter = filter;
+                egui::ComboBox::from_label("Reconstruction").selected_text(format!("{:?}", filter)).show_ui(ui, |ui| {
+                    for candidate in [ReconstructionFilter::Nearest, ReconstructionFilter::Easu, ReconstructionFilter::EasuRcas { sharpness: 0.8 }] {
+                        ui.selectable_value(&mut new_filter, candidate, format!("{:?}", candidate));
+                    }
+                });
+                if new_filter != filter {
+                    self.interlaced_renderer.set_reconstruction_filter(new_filter);
+                }
+
+                let present_mode = self.pending_present_mode.unwrap_or(self.config.present_mode);
+                let mut new_present_mode = present_mode;
+                egui::ComboBox::from_label("Present mode").selected_text(format!("{:?}", present_mode)).show_ui(ui, |ui| {
+                    for &candidate in &self.supported_present_modes {
+                        ui.selectable_value(&mut new_present_mode, candidate, format!("{:?}", candidate));
+                    }
+                });
+                if new_present_mode != present_mode {
+                    self.pending_present_mode = Some(new_present_mode);
+                }
+
+                let mut render_scale = self.interlaced_renderer.render_scale();
+                ui.add_enabled_ui(!self.dynamic_resolution_enabled, |ui| {
+                    if ui.add(egui::Slider::new(&mut render_scale, 0.25..=1.0).text("Render scale")).changed() {
+                        self.interlaced_renderer.set_render_scale(render_scale);
+                    }
+                });
+
+                let mut field_offset_enabled = self.interlaced_renderer.field_offset_enabled();
+                if ui.checkbox(&mut field_offset_enabled, "Field row offset").on_hover_text("Sample each field at the centers of the output rows it is woven into").changed() {
+                    self.interlaced_renderer.set_field_offset_enabled(field_offset_enabled);
+                }
+
+                let mut bloom_enabled = self.bloom_enabled;
+                if ui.checkbox(&mut bloom_enabled, "Bloom").changed() {
+                    self.set_bloom_enabled(bloom_enabled);
+                }
+            });
+
+            egui::CollapsingHeader::new("Frame times").default_open(true).show(ui, |ui| {
+                for series in self.frame_stats.all_series() {
+                    if let Some(summary) = series.summary() {
+                        ui.label(format!("{}: mean {:.2} ms, p95 {:.2} ms, p99 {:.2} ms", series.name(), summary.mean.as_secs_f64() * 1000.0, summary.p95.as_secs_f64() * 1000.0, summary.p99.as_secs_f64() * 1000.0));
+                    }
+                }
+
+                egui::plot::Plot::new("frame times").height(120.0).include_y(0.0).allow_drag(false).allow_zoom(false).allow_scroll(false).legend(egui::plot::Legend::default()).show(ui, |plot_ui| {
+                    for series in self.frame_stats.all_series() {
+                        let points: egui::plot::PlotPoints = series.times().enumerate().map(|(i, time)| [i as f64, time.as_secs_f64() * 1000.0]).collect();
+                        plot_ui.line(egui::plot::Line::new(points).name(series.name()));
+                    }
+                });
+            });
+
+            if self.display_mode == DisplayMode::FieldInspector {
+                egui::CollapsingHeader::new("Field inspector").default_open(true).show(ui, |ui| {
+                    self.field_inspector_ui(ui);
+                });
+            }
+
+            egui::CollapsingHeader::new("Scene").default_open(true).show(ui, |ui| {
+                let mut scene = self.scene;
+                ui.horizontal(|ui| {
+                    for candidate in Scene::ALL {
+                        ui.selectable_value(&mut scene, candidate, format!("{:?}", candidate));
+                    }
+                });
+                self.set_scene(scene);
+
+                if self.scene == Scene::Mesh {
+                    ui.label(format!("{} objects", self.mesh_renderer.objects().len()));
+                }
+
+                if self.scene == Scene::Sdf {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} shapes", self.sdf_scene.shape_count()));
+                        if ui.button("Reload").clicked() {
+                            self.reload_sdf_scene();
+                        }
+                    });
+                }
+            });
+
+            egui::CollapsingHeader::new("Camera").show(ui, |ui| {
+                ui.label(format!("Position: ({:.3}, {:.3})", self.camera.position.x, self.camera.position.y));
+                ui.add(egui::Slider::new(&mut self.camera.zoom, 0.1..=100.0).logarithmic(true).text("Zoom"));
+                ui.add(egui::Slider::new(&mut self.camera.rotation, -std::f32::consts::PI..=std::f32::consts::PI).text("Rotation"));
+                if ui.button("Reset").clicked() {
+                    self.camera.reset();
+                }
+            });
+
+            egui::CollapsingHeader::new("3D camera").show(ui, |ui| {
+                let mut mode = self.camera3d.mode();
+                ui.horizontal(|ui| {
+                    ui.label("Mode");
+                    ui.selectable_value(&mut mode, CameraMode::Orbit, "Orbit");
+                    ui.selectable_value(&mut mode, CameraMode::Fly, "Fly");
+                });
+                if mode != self.camera3d.mode() {
+                    self.camera3d.set_mode(mode);
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Projection");
+                    let perspective = matches!(self.camera3d.projection, Projection::Perspective { .. });
+                    if ui.selectable_label(perspective, "Perspective").clicked() && !perspective {
+                        self.camera3d.projection = Projection::Perspective { fov_y: 60f32.to_radians() };
+                    }
+                    if ui.selectable_label(!perspective, "Orthographic").clicked() && perspective {
+                        self.camera3d.projection = Projection::Orthographic { height: 4.0 };
+                    }
+                });
+                match &mut self.camera3d.projection {
+                    Projection::Perspective { fov_y } => ui.add(egui::Slider::new(fov_y, 0.1..=2.5).text("Vertical field of view")),
+                    Projection::Orthographic { height } => ui.add(egui::Slider::new(height, 0.1..=100.0).logarithmic(true).text("View height")),
+                };
+
+                let eye = self.camera3d.eye();
+                ui.label(format!("Eye: ({:.2}, {:.2}, {:.2}), yaw {:.1}°, pitch {:.1}°", eye.x, eye.y, eye.z, self.camera3d.yaw.to_degrees(), self.camera3d.pitch.to_degrees()));
+                if self.camera3d.mode() == CameraMode::Orbit {
+                    ui.add(egui::Slider::new(&mut self.camera3d.distance, 0.5..=50.0).logarithmic(true).text("Distance"));
+                }
+                if ui.button("Reset").clicked() {
+                    self.camera3d = create_camera3d(self.size.width, self.size.height);
+                }
+            });
+
+            egui::CollapsingHeader::new("Pixel picker").show(ui, |ui| {
+                match &self.last_pixel_pick {
+                    Some(pick) => {
+                        ui.monospace(pick.to_string());
+                    },
+                    None => {
+                        ui.label("Right click a pixel to read it back");
+                    },
+                }
+            });
+
+            egui::CollapsingHeader::new("Fields").show(ui, |ui| {
+                let [texture1, texture2] = self.interlaced_renderer.field_textures();
+                let views = [texture1.create_view(&Default::default()), texture2.create_view(&Default::default())];
+                let size = egui::vec2(256.0, 256.0 * texture1.height() as f32 / texture1.width() as f32);
+
+                ui.horizontal(|ui| {
+                    for (index, view) in views.iter().enumerate() {
+                        // views are registered again every frame, as field textures are recreated on resize and render scale changes
+                        let painter = self.overlay.painter_mut();
+                        let id = match self.field_texture_ids[index] {
+                            Some(id) => {
+                                painter.update_native_texture(id, view, wgpu::FilterMode::Nearest);
+                                id
+                            },
+                            None => painter.register_native_texture(view, wgpu::FilterMode::Nearest),
+                        };
+                        self.field_texture_ids[index] = Some(id);
+
+                        ui.vertical(|ui| {
+                            ui.label(if index == 0 { "Even rows" } else { "Odd rows" });
+                            ui.image(id, size);
+                        });
+                    }
+                });
+            });
+        });
+    }
+
+    /// Settings of the field inspector, and values of the texel under the cursor.
+    fn field_inspector_ui(&mut self, ui: &mut egui::Ui) {
+        let mut view = self.field_inspector.view();
+        ui.horizontal(|ui| {
+            ui.label("View");
+            ui.selectable_value(&mut view, InspectorView::SideBySide, "Side by side");
+            ui.selectable_value(&mut view, InspectorView::Stacked, "Stacked");
+            ui.selectable_value(&mut view, InspectorView::Difference, "Difference");
+        });
+        self.field_inspector.set_view(view);
+
+        let mut zoom = self.field_inspector.zoom();
+        ui.horizontal(|ui| {
+            if ui.add(egui::Slider::new(&mut zoom, field_inspector::MIN_ZOOM..=field_inspector::MAX_ZOOM).logarithmic(true).text("Zoom")).changed() {
+                self.field_inspector.set_zoom(zoom);
+            }
+            if ui.button("Reset").clicked() {
+                self.field_inspector.reset_zoom();
+            }
+        });
+
+        if view == InspectorView::Difference {
+            let mut difference_scale = self.field_inspector.difference_scale();
+            if ui.add(egui::Slider::new(&mut difference_scale, 1.0..=64.0).logarithmic(true).text("Difference scale")).changed() {
+                self.field_inspector.set_difference_scale(difference_scale);
+            }
+        }
+
+        let fields = self.interlaced_renderer.field_textures();
+        match self.field_inspector.texel_at(self.input.cursor_pixel(), [self.size.width, self.size.height], [fields[0].width(), fields[0].height()]) {
+            // read back without blocking: the values shown can be of the previously hovered texel for a frame
+            Some(texel) => match self.field_inspector.read_texels(fields, texel.x, texel.y) {
+                Some(TexelValues { x, y, fields: [even, odd] }) => {
+                    ui.label(format!("Texel ({}, {})", x, y));
+                    ui.monospace(format!("Even rows: {:3} {:3} {:3} {:3}", even[0], even[1], even[2], even[3]));
+                    ui.monospace(format!("Odd rows:  {:3} {:3} {:3} {:3}", odd[0], odd[1], odd[2], odd[3]));
+                    ui.monospace(format!("Difference: {:3} {:3} {:3} {:3}", even[0].abs_diff(odd[0]), even[1].abs_diff(odd[1]), even[2].abs_diff(odd[2]), even[3].abs_diff(odd[3])));
+                },
+                None => {
+                    ui.label(format!("Reading texel ({}, {})", texel.x, texel.y));
+                },
+            },
+            None => {
+                ui.label("No texel under the cursor");
+            },
+        }
+        ui.label("Scroll to zoom, drag with the middle button to pan, G to toggle the view, Home to reset");
+    }
+
+    /// Draw the frame just shown again to a copyable texture of the surface format (without the overlay), as surface textures can not be read back.
+    fn capture_output(&mut self) -> wgpu::Texture {
+        let capture = self.device.create_texture(&test_wgpu::utils::texture_descriptor(Some("Pixel picker output capture"), self.size.width, self.size.height, self.config.format, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC));
+        let view = capture.create_view(&Default::default());
+
+        if self.display_mode == DisplayMode::FieldInspector {
+            let current_field = 1 - self.interlaced_renderer.current_field();
+            self.field_inspector.draw(&view, [self.config.width, self.config.height], self.interlaced_renderer.field_textures(), current_field, Some(self.input.cursor_pixel()));
+            return capture;
+        }
+
+        let bloom_input_view = self.bloom.input_texture().create_view(&Default::default());
+        let (target_view, target) = if self.bloom_enabled { (&bloom_input_view, BLOOM_INPUT_FORMAT) } else { (&view, self.config.format) };
+
+        if self.display_mode != DisplayMode::Progressive {
+            self.interlaced_renderer.redraw(target_view, target);
+        }
+
+        match self.display_mode {
+            DisplayMode::Progressive => self.render_progressive(target_view, &self.progressive_pipeline, 0),
+            DisplayMode::Split => {
+                let split_x = ((self.input.cursor_normalized()[0].clamp(0.0, 1.0) * self.size.width as f32) as u32).min(self.size.width - 1);
+                self.render_progressive(target_view, &self.progressive_pipeline, split_x);
+            },
+            _ => {},
+        }
+
+        if self.bloom_enabled {
+            self.bloom.draw(&view);
+        }
+
+        capture
+    }
+
+    /// Read back the pixel under the cursor from the output, the intermediate textures and the field it comes from, and print it.
+    fn pick_pixel(&mut self) {
+        let [x, y] = self.input.cursor_pixel();
+        if x < 0.0 || y < 0.0 || x >= self.size.width as f32 || y >= self.size.height as f32 {
+            return;
+        }
+        let (x, y) = (x as u32, y as u32);
+
+        let output = self.capture_output();
+        let device = self.device.clone();
+        let queue = self.queue.clone();
+        let fields = self.interlaced_renderer.field_textures();
+
+        // only the interlaced mode shows fields merged row by row (the split screen is progressive under the cursor)
+        let source = if self.display_mode == DisplayMode::Interlaced { Some(self.interlaced_renderer.field_source(x, y)) } else { None };
+        let mut pick = PixelPick::new(x, y, source);
+
+        pick.read(&device, &queue, "output", &output, x, y);
+
+        if self.bloom_enabled && self.display_mode != DisplayMode::FieldInspector {
+            pick.read(&device, &queue, "bloom input", self.bloom.input_texture(), x, y);
+        }
+
+        match self.display_mode {
+            DisplayMode::Interlaced => {
+                if let Some(upscale_texture) = self.interlaced_renderer.upscale_texture() {
+                    pick.read(&device, &queue, "upscaled frame", upscale_texture, x, y);
+                }
+
+                let source = self.interlaced_renderer.field_source(x, y);
+                let name = if source.field == 0 { "even rows field" } else { "odd rows field" };
+                pick.read(&device, &queue, name, fields[source.field], source.x, source.y);
+            },
+            DisplayMode::FieldInspector => {
+                if let Some(texel) = self.field_inspector.texel_at([x as f32, y as f32], [self.size.width, self.size.height], [fields[0].width(), fields[0].height()]) {
+                    pick.read(&device, &queue, "even rows field", fields[0], texel.x, texel.y);
+                    pick.read(&device, &queue, "odd rows field", fields[1], texel.x, texel.y);
+                }
+            },
+            DisplayMode::Progressive | DisplayMode::Split => {},
+        }
+
+        print!("{}", pick);
+        self.last_pixel_pick = Some(pick);
+    }
+
+    /// Compare the interlaced frame just drawn to the same frame rendered progressively at full resolution, and print the results.
+    fn measure_image_quality(&mut self) {
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC;
+        let reference = test_wgpu::utils::create_texture(&self.device, Some("Image metrics reference texture"), self.size.width, self.size.height, usage);
+        let interlaced = test_wgpu::utils::create_texture(&self.device, Some("Image metrics interlaced texture"), self.size.width, self.size.height, usage);
+
+        self.interlaced_renderer.redraw(&interlaced.create_view(&Default::default()), wgpu::TextureFormat::Rgba8Unorm);
+
+        // the reference is not jittered
+        self.write_uniform([0.0, 0.0]);
+        self.render_progressive(&reference.create_view(&Default::default()), &self.reference_pipeline, 0);
+
+        match self.image_metrics.evaluate(&reference, &interlaced) {
+            Ok(metrics) => log::info!("image quality ({:?}): {}", self.image_metrics.backend(), metrics),
+            Err(e) => log::error!("image quality: {}", e),
+        }
+    }
+
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.apply_present_mode();
+
+        // the field resolution follows the GPU time of the fields, read back a few frames later
+        for frame in self.gpu_timer.collect(&mut self.frame_stats) {
+            if let Some(time) = frame.total(&[GPU_SCENE_SCOPE, GPU_MERGE_SCOPE]) {
+                self.update_render_scale(time);
+            }
+        }
+        self.gpu_timer.begin_frame();
+
+        // without timestamp queries, the CPU time of the submissions of the scene and the merge is used instead (without acquire nor present)
+        let mut cpu_scene_time = None;
+        let mut cpu_merge_time = None;
+
+        self.prepare_depth_buffers();
+
         // Step 1: render a half frame
-        let render_texture = self.interlaced_renderer.get_render_texture();
-        let render_view = render_texture.create_view(&wgpu::TextureViewDescriptor::default());
-        self.render_to_texture(&render_view);
+        if self.field_advanced && self.display_mode != DisplayMode::Progressive {
+            let render_texture = self.interlaced_renderer.get_render_texture();
+            let render_view = render_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+            let start = std::time::Instant::now();
+            let scope = self.gpu_timer.begin_scope(GPU_SCENE_SCOPE);
+            match self.interlaced_renderer.get_multisampled_texture() {
+                Some(multisampled_texture) => {
+                    let multisampled_view = multisampled_texture.create_view(&wgpu::TextureViewDescriptor::default());
+                    self.render_to_texture(&multisampled_view, Some(&render_view));
+                },
+                None => self.render_to_texture(&render_view, None),
+            }
+            self.gpu_timer.end_scope(scope);
+            cpu_scene_time = Some(start.elapsed());
+        }
 
         // Step 2: render a full frame by using the last rendered frame combined with the previous frame saved internally by the interlaced renderer. That means the very first frame will be half black.
-        let output = self.surface.get_current_texture()?;
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            Err(e) => {
+                // the field rendered above is not merged, but the interlaced renderer must still move past it
+                if FieldStep::new(self.field_advanced, self.display_mode, false) == FieldStep::Skip {
+                    self.interlaced_renderer.skip_draw();
+                }
+
+                self.gpu_timer.end_frame();
+                return Err(e);
+            }
+        };
+        let field_step = FieldStep::new(self.field_advanced, self.display_mode, true);
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-        self.interlaced_renderer.draw(&view);
 
+        // with bloom, everything is drawn to the bloom input which is then composited to the surface
+        let bloom_input_view = self.bloom.input_texture().create_view(&wgpu::TextureViewDescriptor::default());
+        let target_view = if self.bloom_enabled { &bloom_input_view } else { &view };
+
+        if self.display_mode == DisplayMode::FieldInspector {
+            // the fields are drawn as they are, straight to the surface
+            if field_step == FieldStep::Skip {
+                self.interlaced_renderer.skip_draw();
+            }
+
+            // the field just rendered is the one before the next render texture
+            let current_field = 1 - self.interlaced_renderer.current_field();
+            self.field_inspector.draw(&view, [self.config.width, self.config.height], self.interlaced_renderer.field_textures(), current_field, Some(self.input.cursor_pixel()));
+        } else if self.display_mode != DisplayMode::Progressive {
+            let scope = self.gpu_timer.begin_scope(GPU_MERGE_SCOPE);
+            if field_step == FieldStep::Draw {
+                let start = std::time::Instant::now();
+                self.interlaced_renderer.draw(target_view);
+                cpu_merge_time = Some(start.elapsed());
+            } else {
+                // paused: draw the same frame again, without advancing the interlaced renderer
+                let target = if self.bloom_enabled { BLOOM_INPUT_FORMAT } else { self.config.format };
+                self.interlaced_renderer.redraw(target_view, target);
+            }
+            self.gpu_timer.end_scope(scope);
+
+            if self.image_metrics_requested {
+                self.image_metrics_requested = false;
+                self.measure_image_quality();
+            }
+        }
+
+        match self.display_mode {
+            DisplayMode::Interlaced | DisplayMode::FieldInspector => {},
+            DisplayMode::Progressive => {
+                // no merge, but the interlaced renderer must still follow the fields so that its parity matches the scene's
+                if field_step == FieldStep::Skip {
+                    self.interlaced_renderer.skip_draw();
+                }
+
+                let scope = self.gpu_timer.begin_scope("gpu progressive scene");
+                self.render_progressive(target_view, &self.progressive_pipeline, 0);
+                self.gpu_timer.end_scope(scope);
+            },
+            DisplayMode::Split => {
+                // the progressive scene is drawn over the right side of the interlaced output, without jitter
+                self.write_uniform([0.0, 0.0]);
+                let split_x = ((self.input.cursor_normalized()[0].clamp(0.0, 1.0) * self.size.width as f32) as u32).min(self.size.width - 1);
+
+                let scope = self.gpu_timer.begin_scope("gpu progressive scene");
+                self.render_progressive(target_view, &self.progressive_pipeline, split_x);
+                self.gpu_timer.end_scope(scope);
+            },
+        }
+
+        if self.bloom_enabled && self.display_mode != DisplayMode::FieldInspector {
+            let scope = self.gpu_timer.begin_scope("gpu bloom");
+            self.bloom.draw(&view);
+            self.gpu_timer.end_scope(scope);
+        }
+
+        if self.pixel_pick_requested {
+            self.pixel_pick_requested = false;
+            self.pick_pixel();
+        }
+
+        // the overlay is drawn last, straight to the surface
+        self.overlay.begin_frame(&self.window);
+        if self.overlay.is_visible() {
+            let context = self.overlay.context().clone();
+            self.overlay_ui(&context);
+        }
+        self.overlay.end_frame(&self.window, &view, [self.config.width, self.config.height]);
+
+        if let (false, Some(scene_time), Some(merge_time)) = (self.gpu_timer.is_supported(), cpu_scene_time, cpu_merge_time) {
+            self.update_render_scale(scene_time + merge_time);
+        }
+
+        self.gpu_timer.end_frame();
         output.present();
         
         Ok(())
@@ -268,7 +1462,8 @@ async fn run() {
     let window = WindowBuilder::new().build(&event_loop).unwrap();
 
     // State::new uses async code, so we're going to wait for it to finish
-    let mut state = State::new(window).await;
+    let mut state = State::new(window, &event_loop).await;
+    state.update_title();
 
     event_loop.run(move |event, _, control_flow| {
         match event {
@@ -276,7 +1471,11 @@ async fn run() {
                 ref event,
                 window_id,
             } if window_id == state.window.id() => {
-                if !state.input(event) {
+                // input used by the overlay does not reach the application, except for what keeps the held keys and buttons consistent
+                // window events are always handled
+                let consumed = state.overlay.on_event(event);
+                let is_input = (!consumed || InputState::must_track(event)) && state.input(event);
+                if !is_input {
                     match event {
                         WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                         WindowEvent::Resized(physical_size) => {
@@ -290,35 +1489,209 @@ async fn run() {
                     }
                 }
 
-                state.window().request_redraw();
+                state.frame_pacer.request_frame();
             }
             Event::RedrawRequested(window_id) if window_id == state.window().id() => {
+                state.frame_pacer.wait_for_frame();
                 state.update();
 
                 let start = std::time::Instant::now();
-                match state.render() {
+                if let Some(last_frame_start) = state.last_frame_start.replace(start) {
+                    state.frame_stats.record("frame interval", start - last_frame_start);
+                }
+
+                let result = state.render();
+                state.frame_pacer.frame_rendered(std::time::Instant::now());
+
+                match result {
+                    // frames drawn again while paused are not measured
+                    Ok(_) if !state.field_advanced => {}
                     Ok(_) => {
-                        let render_time = start.elapsed().as_nanos() as f32 / 1000000f32;
-                        println!("frame render time: {} ms, fps: {}", render_time, 1000.0 / render_time);
+                        let elapsed = start.elapsed();
+                        state.frame_stats.record(state.display_mode.stats_series(), elapsed);
+                        state.frame_stats.report_if_due(std::time::Instant::now());
                     }
                     // Reconfigure the surface if lost
                     Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
                     // The system is out of memory, we should probably quit
                     Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
                     // All other errors (Outdated, Timeout) should be resolved by the next frame
-                    Err(e) => eprintln!("{:?}", e),
+                    Err(e) => log::warn!("{:?}", e),
                 }
             }
-            Event::MainEventsCleared => {
-                // RedrawRequested will only trigger once, unless we manually
-                // request it.
-                // state.window().request_redraw();
+            // render at a steady field rate, RedrawRequested only triggers once otherwise
+            Event::MainEventsCleared if state.frame_pacer.should_render(std::time::Instant::now()) => {
+                state.window().request_redraw();
+            }
+            Event::RedrawEventsCleared if !matches!(*control_flow, ControlFlow::ExitWithCode(_)) => {
+                *control_flow = state.frame_pacer.control_flow();
             }
             _ => {}
         };
     });
 }
 
+fn merge_variant_name(variant: MergeVariant) -> &'static str {
+    match variant {
+        MergeVariant::Standard => "Standard",
+        MergeVariant::Crt(_) => "CRT",
+        MergeVariant::Taa(_) => "TAA",
+    }
+}
+
+/// Default bindings, overridden by the RON file named by `INPUT_BINDINGS` if set (see `ActionBindings`).
+fn create_action_bindings() -> ActionBindings<Action> {
+    let mut bindings = ActionBindings::new()
+        .with_binding(Action::ToggleCrt, Binding::Key(VirtualKeyCode::C))
+        .with_binding(Action::ToggleTaa, Binding::Key(VirtualKeyCode::T))
+        .with_binding(Action::ToggleDynamicResolution, Binding::Key(VirtualKeyCode::D))
+        .with_binding(Action::CycleReconstructionFilter, Binding::Key(VirtualKeyCode::F))
+        .with_binding(Action::CycleSampleCount, Binding::Key(VirtualKeyCode::M))
+        .with_binding(Action::ToggleBloom, Binding::Key(VirtualKeyCode::B))
+        .with_binding(Action::CyclePresentMode, Binding::Key(VirtualKeyCode::V))
+        .with_binding(Action::CycleDisplayMode, Binding::Key(VirtualKeyCode::P))
+        .with_binding(Action::MeasureImageQuality, Binding::Key(VirtualKeyCode::Q))
+        .with_binding(Action::PickPixel, Binding::Mouse(MouseButton::Right))
+        .with_binding(Action::ToggleInspectorView, Binding::Key(VirtualKeyCode::G))
+        .with_binding(Action::ResetInspectorZoom, Binding::Key(VirtualKeyCode::Home))
+        .with_binding(Action::InspectorZoomIn, Binding::ScrollUp)
+        .with_binding(Action::InspectorZoomOut, Binding::ScrollDown)
+        .with_binding(Action::InspectorPan, Binding::Mouse(MouseButton::Middle))
+        .with_binding(Action::CameraLeft, Binding::Key(VirtualKeyCode::Left))
+        .with_binding(Action::CameraRight, Binding::Key(VirtualKeyCode::Right))
+        .with_binding(Action::CameraUp, Binding::Key(VirtualKeyCode::Up))
+        .with_binding(Action::CameraDown, Binding::Key(VirtualKeyCode::Down))
+        .with_binding(Action::CameraRotateClockwise, Binding::Key(VirtualKeyCode::RBracket))
+        .with_binding(Action::CameraRotateCounterclockwise, Binding::Key(VirtualKeyCode::LBracket))
+        .with_binding(Action::CameraZoomIn, Binding::ScrollUp)
+        .with_binding(Action::CameraZoomOut, Binding::ScrollDown)
+        .with_binding(Action::CameraDrag, Binding::Mouse(MouseButton::Middle))
+        .with_binding(Action::ResetCamera, Binding::Key(VirtualKeyCode::Home))
+        .with_binding(Action::ToggleCameraMode, Binding::Key(VirtualKeyCode::O))
+        .with_binding(Action::ToggleOverlay, Binding::Key(VirtualKeyCode::F1))
+        .with_binding(Action::TogglePause, Binding::Key(VirtualKeyCode::Space))
+        .with_binding(Action::StepField, Binding::Key(VirtualKeyCode::N))
+        .with_binding(Action::ToggleFixedStep, Binding::Key(VirtualKeyCode::X))
+        .with_binding(Action::ResetTime, Binding::Key(VirtualKeyCode::R))
+        .with_binding(Action::CycleFieldRateCap, Binding::Key(VirtualKeyCode::L))
+        .with_binding(Action::ToggleIdle, Binding::Key(VirtualKeyCode::I))
+        .with_binding(Action::CycleScene, Binding::Key(VirtualKeyCode::S))
+        .with_binding(Action::ReloadScene, Binding::Key(VirtualKeyCode::F5));
+
+    if let Some(path) = std::env::var_os("INPUT_BINDINGS") {
+        match ActionBindings::load(&path) {
+            Ok(custom) => bindings.override_with(custom),
+            Err(e) => log::error!("can not load input bindings from {:?}: {}", path, e),
+        }
+    }
+
+    bindings
+}
+
+/// Orbit camera looking slightly down at the raymarched scene.
+fn create_camera3d(width: u32, height: u32) -> Camera3d {
+    let mut camera = Camera3d::new(width, height);
+    camera.pitch = -20f32.to_radians();
+    camera
+}
+
+/// Shapes of the SDF scene when `SDF_SCENE` is not set.
+const DEFAULT_SDF_SCENE: &str = include_str!("../scenes/default.ron");
+
+/// SDF scene loaded from the RON file named by `SDF_SCENE` if set, the default scene otherwise (see `SdfScene`).
+fn load_sdf_scene() -> Result<SdfScene, SdfSceneError> {
+    match std::env::var_os("SDF_SCENE") {
+        Some(path) => SdfScene::load(path),
+        None => SdfScene::from_ron_str(DEFAULT_SDF_SCENE),
+    }
+}
+
+/// Frame statistics reported every second with `RUST_LOG=info`, and exported to the CSV file named by `FRAME_STATS_CSV` if set.
+fn create_frame_stats() -> FrameStats {
+    let frame_stats = FrameStats::new(600);
+
+    match std::env::var_os("FRAME_STATS_CSV") {
+        Some(path) => match frame_stats.with_csv_export(&path) {
+            Ok(frame_stats) => frame_stats,
+            Err(e) => {
+                log::error!("can not export frame stats to {:?}: {}", path, e);
+                FrameStats::new(600)
+            }
+        },
+        None => frame_stats,
+    }
+}
+
 fn main() {
     pollster::block_on(run());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Field index of the interlaced renderer, as moved by `draw` and `skip_draw`.
+    struct RendererFields {
+        frame_number: u64,
+    }
+
+    impl RendererFields {
+        fn apply(&mut self, step: FieldStep) {
+            if step != FieldStep::Stay {
+                self.frame_number += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn field_parity_survives_failed_acquires() {
+        let mut time_control = TimeControl::new();
+        let mut renderer = RendererFields { frame_number: 0 };
+        let start = std::time::Instant::now();
+
+        // (display mode, paused, surface acquired) of each frame
+        let frames = [
+            (DisplayMode::Interlaced, false, true),
+            (DisplayMode::Interlaced, false, false),
+            (DisplayMode::Interlaced, false, true),
+            (DisplayMode::Split, false, false),
+            (DisplayMode::Split, false, false),
+            (DisplayMode::Progressive, false, false),
+            (DisplayMode::Interlaced, true, false),
+            (DisplayMode::Interlaced, true, true),
+            (DisplayMode::FieldInspector, false, false),
+            (DisplayMode::FieldInspector, false, true),
+            (DisplayMode::Interlaced, false, true),
+        ];
+
+        for (i, &(display_mode, paused, surface_acquired)) in frames.iter().enumerate() {
+            if paused != time_control.is_paused() {
+                time_control.set_paused(paused);
+            }
+
+            // State::update
+            let field = time_control.advance(start + std::time::Duration::from_millis(i as u64 * 16));
+            if let Some(field) = field {
+                assert_eq!(field.frame_number & 1, renderer.frame_number & 1, "frame {}", i);
+            }
+
+            // State::render
+            renderer.apply(FieldStep::new(field.is_some(), display_mode, surface_acquired));
+            assert_eq!(renderer.frame_number, time_control.field_count(), "frame {}", i);
+        }
+    }
+
+    #[test]
+    fn field_steps() {
+        assert_eq!(FieldStep::new(true, DisplayMode::Interlaced, true), FieldStep::Draw);
+        assert_eq!(FieldStep::new(true, DisplayMode::Split, true), FieldStep::Draw);
+        assert_eq!(FieldStep::new(true, DisplayMode::Interlaced, false), FieldStep::Skip);
+        assert_eq!(FieldStep::new(true, DisplayMode::Progressive, true), FieldStep::Skip);
+        assert_eq!(FieldStep::new(true, DisplayMode::FieldInspector, true), FieldStep::Skip);
+
+        for display_mode in DisplayMode::ALL {
+            assert_eq!(FieldStep::new(false, display_mode, true), FieldStep::Stay);
+            assert_eq!(FieldStep::new(false, display_mode, false), FieldStep::Stay);
+        }
+    }
+}
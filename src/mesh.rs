@@ -0,0 +1,468 @@
+use std::num::NonZeroU64;
+use std::rc::Rc;
+
+use glam::{Mat3, Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::utils::{slice_as_u8_slice, any_as_u8_slice, create_bind_group, create_bind_group_layout, create_attachment_texture, create_render_pipeline_with_options, RenderPipelineOptions};
+
+/// Format of the depth buffers of the mesh pipelines.
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Indexed triangle list on the CPU, counterclockwise triangles face outwards.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    /// Cube of size 1 centered on the origin, with flat faces.
+    pub fn cube() -> Self {
+        let mut data = Self::default();
+        // (normal, u, v) with u x v = normal, so that the quads are counterclockwise seen from outside
+        for (normal, u, v) in [(Vec3::X, Vec3::Y, Vec3::Z), (Vec3::NEG_X, Vec3::Z, Vec3::Y), (Vec3::Y, Vec3::Z, Vec3::X), (Vec3::NEG_Y, Vec3::X, Vec3::Z), (Vec3::Z, Vec3::X, Vec3::Y), (Vec3::NEG_Z, Vec3::Y, Vec3::X)] {
+            data.push_quad(normal * 0.5, u * 0.5, v * 0.5);
+        }
+        data
+    }
+
+    /// Square in the xz plane centered on the origin, facing up.
+    pub fn plane(half_size: f32) -> Self {
+        let mut data = Self::default();
+        data.push_quad(Vec3::ZERO, Vec3::Z * half_size, Vec3::X * half_size);
+        data
+    }
+
+    /// Sphere of radius 1 centered on the origin, made of `segments` around the y axis and `rings` from pole to pole.
+    pub fn sphere(segments: u32, rings: u32) -> Self {
+        let (segments, rings) = (segments.max(3), rings.max(2));
+        let mut data = Self::default();
+
+        for ring in 0..=rings {
+            let phi = std::f32::consts::PI * ring as f32 / rings as f32;
+            for segment in 0..=segments {
+                let theta = std::f32::consts::TAU * segment as f32 / segments as f32;
+                let position = [phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin()];
+                data.vertices.push(Vertex { position, normal: position });
+            }
+        }
+
+        // a ring goes down, a segment goes towards +z at the front
+        let index = |ring: u32, segment: u32| ring * (segments + 1) + segment;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let (a, b, c, d) = (index(ring, segment), index(ring + 1, segment), index(ring, segment + 1), index(ring + 1, segment + 1));
+
+                // a and c are both on the pole in the first ring, b and d in the last one: that triangle would be empty
+                if ring > 0 {
+                    data.indices.extend_from_slice(&[a, c, b]);
+                }
+                if ring + 1 < rings {
+                    data.indices.extend_from_slice(&[c, d, b]);
+                }
+            }
+        }
+
+        data
+    }
+
+    /// Add a flat quad spanning `center` +/- `u` +/- `v`, facing `u` x `v`.
+    fn push_quad(&mut self, center: Vec3, u: Vec3, v: Vec3) {
+        let normal = u.cross(v).normalize().to_array();
+        let first = self.vertices.len() as u32;
+
+        for corner in [center - u - v, center + u - v, center + u + v, center - u + v] {
+            self.vertices.push(Vertex { position: corner.to_array(), normal });
+        }
+        self.indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+}
+
+/// Vertex and index buffers of a mesh.
+pub struct Mesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+}
+
+impl Mesh {
+    pub fn new(device: &wgpu::Device, label: &str, data: &MeshData) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} vertex buffer", label)),
+            contents: unsafe { slice_as_u8_slice(&data.vertices) },
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} index buffer", label)),
+            contents: unsafe { slice_as_u8_slice(&data.indices) },
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: data.indices.len() as u32,
+        }
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+}
+
+/// Lambert diffuse and Blinn-Phong specular reflection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    /// Linear RGB.
+    pub color: Vec3,
+    /// Intensity of the highlights, 0 for a matte surface.
+    pub specular: f32,
+    /// Blinn-Phong exponent, higher for smaller highlights.
+    pub shininess: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            color: Vec3::ONE,
+            specular: 0.5,
+            shininess: 32.0,
+        }
+    }
+}
+
+/// Instance of a mesh in the world.
+#[derive(Clone)]
+pub struct MeshObject {
+    pub mesh: Rc<Mesh>,
+    /// Model to world transform.
+    pub transform: Mat4,
+    pub material: Material,
+}
+
+/// Directional light with a constant ambient term.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    /// Direction to the light, normalized by the shader.
+    pub direction: Vec3,
+    /// Linear RGB.
+    pub color: Vec3,
+    pub ambient: Vec3,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::new(0.6, 0.7, 0.4),
+            color: Vec3::new(1.0, 0.95, 0.85),
+            ambient: Vec3::new(0.1, 0.12, 0.16),
+        }
+    }
+}
+
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+struct ObjectUniform {
+    model: [f32; 16],
+    /// Inverse transpose of the model transform, columns of a mat3x3 padded to 4 floats.
+    normal_matrix: [[f32; 4]; 3],
+    color: [f32; 4],
+    specular: f32,
+    shininess: f32,
+    padding: [f32; 2],
+}
+
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+struct LightUniform {
+    direction: [f32; 3],
+    padding0: f32,
+    color: [f32; 3],
+    padding1: f32,
+    ambient: [f32; 3],
+    padding2: f32,
+}
+
+/// Depth texture of a render target, allocated again when the target changes size or sample count.
+pub struct DepthBuffer {
+    label: &'static str,
+    texture: Option<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl DepthBuffer {
+    /// Nothing is allocated before `prepare`.
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            texture: None,
+        }
+    }
+
+    /// Make sure the depth texture matches a color target.
+    pub fn prepare(&mut self, device: &wgpu::Device, width: u32, height: u32, sample_count: u32) {
+        if let Some((texture, _)) = &self.texture {
+            if texture.width() == width && texture.height() == height && texture.sample_count() == sample_count {
+                return;
+            }
+        }
+
+        let texture = create_attachment_texture(device, Some(self.label), width, height, DEPTH_FORMAT, sample_count);
+        let view = texture.create_view(&Default::default());
+        self.texture = Some((texture, view));
+    }
+
+    /// None before the first `prepare`.
+    pub fn view(&self) -> Option<&wgpu::TextureView> {
+        self.texture.as_ref().map(|(_, view)| view)
+    }
+
+    /// Attachment cleared to the far plane, the depth is not kept after the pass.
+    pub fn attachment(&self) -> Option<wgpu::RenderPassDepthStencilAttachment<'_>> {
+        self.view().map(|view| wgpu::RenderPassDepthStencilAttachment {
+            view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: false,
+            }),
+            stencil_ops: None,
+        })
+    }
+}
+
+/// Draws mesh objects with a depth buffer, shaded by a directional light (see `mesh.wgsl`).
+///
+/// The scene bind group (group 0) provides the 3D camera at binding 2, the renderer binds the object and the light as group 1.
+pub struct MeshRenderer {
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    object_bind_group_layout: wgpu::BindGroupLayout,
+    /// Uniforms of all the objects, each one at a multiple of `object_stride`.
+    object_buffer: wgpu::Buffer,
+    object_stride: u64,
+    light_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    objects: Vec<MeshObject>,
+}
+
+impl MeshRenderer {
+    pub fn new(device: Rc<wgpu::Device>, queue: Rc<wgpu::Queue>, scene_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mesh shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/mesh.wgsl").into()),
+        });
+
+        let object_bind_group_layout = create_bind_group_layout(&device, Some("Mesh object bind group layout"), vec![
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: NonZeroU64::new(std::mem::size_of::<ObjectUniform>() as u64),
+            },
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+        ], wgpu::ShaderStages::VERTEX_FRAGMENT);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mesh pipeline layout"),
+            bind_group_layouts: &[scene_bind_group_layout, &object_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let object_stride = (std::mem::size_of::<ObjectUniform>() as u64).div_ceil(alignment) * alignment;
+        let object_buffer = create_object_buffer(&device, object_stride);
+
+        let light_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Mesh light buffer"),
+            size: std::mem::size_of::<LightUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = create_object_bind_group(&device, &object_bind_group_layout, &object_buffer, &light_buffer);
+
+        let mut renderer = Self {
+            device,
+            queue,
+            shader,
+            pipeline_layout,
+            object_bind_group_layout,
+            object_buffer,
+            object_stride,
+            light_buffer,
+            bind_group,
+            objects: vec![],
+        };
+        renderer.set_light(&Light::default());
+        renderer
+    }
+
+    /// Pipeline drawing to a color target of the given format and sample count, with a `DEPTH_FORMAT` depth attachment of the same sample count.
+    pub fn create_pipeline(&self, label: Option<&str>, target: wgpu::TextureFormat, sample_count: u32) -> wgpu::RenderPipeline {
+        create_render_pipeline_with_options(&self.device, label, &[Vertex::layout()], &self.pipeline_layout, &self.shader, target, RenderPipelineOptions {
+            sample_count,
+            depth_format: Some(DEPTH_FORMAT),
+            ..Default::default()
+        })
+    }
+
+    pub fn set_light(&mut self, light: &Light) {
+        let uniform = LightUniform {
+            direction: light.direction.to_array(),
+            padding0: 0.0,
+            color: light.color.to_array(),
+            padding1: 0.0,
+            ambient: light.ambient.to_array(),
+            padding2: 0.0,
+        };
+        self.queue.write_buffer(&self.light_buffer, 0, unsafe { any_as_u8_slice(&uniform) });
+    }
+
+    pub fn objects(&self) -> &[MeshObject] {
+        &self.objects
+    }
+
+    /// Replace the objects drawn, the uniform buffer grows when needed.
+    pub fn set_objects(&mut self, objects: Vec<MeshObject>) {
+        let size = objects.len().max(1) as u64 * self.object_stride;
+        if size > self.object_buffer.size() {
+            self.object_buffer = create_object_buffer(&self.device, size);
+            self.bind_group = create_object_bind_group(&self.device, &self.object_bind_group_layout, &self.object_buffer, &self.light_buffer);
+        }
+
+        let mut data = vec![0u8; objects.len() * self.object_stride as usize];
+        for (object, chunk) in objects.iter().zip(data.chunks_exact_mut(self.object_stride as usize)) {
+            let normal_matrix = Mat3::from_mat4(object.transform).inverse().transpose();
+            let uniform = ObjectUniform {
+                model: object.transform.to_cols_array(),
+                normal_matrix: [normal_matrix.x_axis.extend(0.0).to_array(), normal_matrix.y_axis.extend(0.0).to_array(), normal_matrix.z_axis.extend(0.0).to_array()],
+                color: object.material.color.extend(1.0).to_array(),
+                specular: object.material.specular,
+                shininess: object.material.shininess,
+                padding: [0.0; 2],
+            };
+            let bytes = unsafe { any_as_u8_slice(&uniform) };
+            chunk[..bytes.len()].copy_from_slice(bytes);
+        }
+        if !data.is_empty() {
+            self.queue.write_buffer(&self.object_buffer, 0, &data);
+        }
+
+        self.objects = objects;
+    }
+
+    /// Draw the objects with a pipeline from `create_pipeline`, the scene bind group must be set.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, pipeline: &'a wgpu::RenderPipeline) {
+        render_pass.set_pipeline(pipeline);
+
+        for (index, object) in self.objects.iter().enumerate() {
+            render_pass.set_bind_group(1, &self.bind_group, &[(index as u64 * self.object_stride) as u32]);
+            render_pass.set_vertex_buffer(0, object.mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(object.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..object.mesh.index_count, 0, 0..1);
+        }
+    }
+}
+
+fn create_object_buffer(device: &wgpu::Device, size: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Mesh object buffer"),
+        size,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_object_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, object_buffer: &wgpu::Buffer, light_buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+    create_bind_group(device, Some("Mesh object bind group"), layout, vec![
+        // a single object is visible at a time, at the dynamic offset
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: object_buffer,
+            offset: 0,
+            size: NonZeroU64::new(std::mem::size_of::<ObjectUniform>() as u64),
+        }),
+        light_buffer.as_entire_binding(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every triangle is counterclockwise seen from the side its vertex normals point to, and the indices are in range.
+    fn assert_valid(data: &MeshData) {
+        assert_eq!(data.indices.len() % 3, 0);
+        assert!(data.indices.iter().all(|&index| (index as usize) < data.vertices.len()));
+
+        for vertex in &data.vertices {
+            assert!((Vec3::from(vertex.normal).length() - 1.0).abs() < 1e-5);
+        }
+
+        for triangle in data.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| data.vertices[triangle[i] as usize]);
+            let face = (Vec3::from(b.position) - Vec3::from(a.position)).cross(Vec3::from(c.position) - Vec3::from(a.position));
+
+            for vertex in [a, b, c] {
+                assert!(face.dot(Vec3::from(vertex.normal)) > 0.0, "triangle {:?} faces away from its normals", triangle);
+            }
+        }
+    }
+
+    #[test]
+    fn cube() {
+        let data = MeshData::cube();
+        assert_valid(&data);
+        assert_eq!((data.vertices.len(), data.indices.len()), (24, 36));
+
+        // faces point outward
+        for vertex in &data.vertices {
+            assert!(Vec3::from(vertex.position).dot(Vec3::from(vertex.normal)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn plane() {
+        let data = MeshData::plane(2.0);
+        assert_valid(&data);
+        assert!(data.vertices.iter().all(|vertex| vertex.normal == [0.0, 1.0, 0.0] && vertex.position[1] == 0.0));
+    }
+
+    #[test]
+    fn sphere() {
+        for (segments, rings) in [(3, 2), (16, 8), (7, 5), (0, 0)] {
+            let data = MeshData::sphere(segments, rings);
+            assert_valid(&data);
+
+            // two triangles per quad, one at each pole
+            let (segments, rings) = (segments.max(3), rings.max(2));
+            assert_eq!(data.indices.len() as u32, 3 * segments * (2 * rings - 2));
+
+            for vertex in &data.vertices {
+                assert!((Vec3::from(vertex.position).length() - 1.0).abs() < 1e-5);
+            }
+        }
+    }
+}
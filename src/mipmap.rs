@@ -0,0 +1,179 @@
+use std::{collections::HashMap, num::NonZeroU32};
+
+use crate::utils::*;
+
+/// Count of mip levels of a full mip chain, down to 1x1.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Weights of the source texels 2i, 2i + 1 and 2i + 2 averaged into the texel i of the next mip level, along one axis.
+///
+/// Odd sizes are filtered over 3 texels so that no row or column is dropped. Same weights as `box_filter_weights` in mipmap.wgsl.
+pub fn box_filter_weights(source_size: u32, index: u32) -> [f32; 3] {
+    if source_size == 1 {
+        return [1.0, 0.0, 0.0];
+    }
+
+    if source_size.is_multiple_of(2) {
+        return [0.5, 0.5, 0.0];
+    }
+
+    // odd size 2n + 1 to n texels: each destination texel covers 2 + 1 / n source texels
+    let n = (source_size / 2) as f32;
+    let i = index as f32;
+    [(n - i) / source_size as f32, n / source_size as f32, (i + 1.0) / source_size as f32]
+}
+
+/// Create a 2D texture with a full mip chain, which can be filled by `MipmapGenerator`.
+pub fn create_texture_with_mips(device: &wgpu::Device, label: Option<&str>, width: u32, height: u32, format: wgpu::TextureFormat, usage: wgpu::TextureUsages) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        mip_level_count: mip_level_count(width, height),
+        // levels are rendered from the previous one
+        ..texture_descriptor(label, width, height, format, usage | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+    })
+}
+
+/// Fills the mip chain of textures on the GPU from their first level, by rendering each level from the previous one.
+///
+/// Works with any color format which can be rendered to, including non filterable float formats.
+pub struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// One pipeline per target format, created when first needed.
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap generator shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/mipmap.wgsl").into()),
+        });
+
+        let bind_group_layout = create_bind_group_layout(device, Some("Mipmap generator bind group layout"),
+            vec![
+                wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+            ],
+        wgpu::ShaderStages::FRAGMENT);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap generator pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Record the passes rendering every mip level of a texture (except the first one) from the previous level.
+    pub fn generate(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        let format = texture.format();
+
+        if !self.pipelines.contains_key(&format) {
+            let pipeline = create_render_pipeline_with_options(device, Some("Mipmap generator pipeline"), &[], &self.pipeline_layout, &self.shader, format, RenderPipelineOptions {
+                // plain overwrite, without requiring a blendable format
+                blend: None,
+                ..Default::default()
+            });
+            self.pipelines.insert(format, pipeline);
+        }
+
+        let pipeline = &self.pipelines[&format];
+
+        for level in 1..texture.mip_level_count() {
+            let source_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: level - 1,
+                mip_level_count: NonZeroU32::new(1),
+                ..Default::default()
+            });
+            let target_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: NonZeroU32::new(1),
+                ..Default::default()
+            });
+
+            let bind_group = create_bind_group(device, Some("Mipmap generator bind group"), &self.bind_group_layout, vec![
+                wgpu::BindingResource::TextureView(&source_view),
+            ]);
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mipmap generator pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    /// Generate the mip chain of a texture in its own submission.
+    pub fn generate_now(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap generator encoder"),
+        });
+
+        self.generate(device, &mut encoder, texture);
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_counts() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(2, 1), 2);
+        assert_eq!(mip_level_count(5, 3), 3);
+        assert_eq!(mip_level_count(1024, 1), 11);
+        assert_eq!(mip_level_count(1025, 7), 11);
+    }
+
+    #[test]
+    fn box_filter_covers_every_source_texel_equally() {
+        for source_size in 1..=33 {
+            // same size as the next mip level given by wgpu
+            let size = (source_size / 2).max(1);
+            let mut coverage = vec![0.0; source_size as usize];
+
+            for index in 0..size {
+                let weights = box_filter_weights(source_size, index);
+                assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-6, "weights of texel {} of size {}", index, source_size);
+
+                for (offset, weight) in weights.iter().enumerate() {
+                    let texel = (2 * index) as usize + offset;
+                    if *weight > 0.0 {
+                        assert!(texel < coverage.len(), "texel {} out of a size of {}", texel, source_size);
+                        coverage[texel] += weight;
+                    }
+                }
+            }
+
+            // the average is kept, and the last row or column is not dropped on odd sizes
+            for (texel, total) in coverage.iter().enumerate() {
+                assert!((total - size as f32 / source_size as f32).abs() < 1e-6, "texel {} of size {} has a weight of {}", texel, source_size, total);
+            }
+        }
+    }
+}
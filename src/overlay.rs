@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use egui_winit::egui;
+use winit::event::WindowEvent;
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::Window;
+
+use crate::utils::{any_as_u8_slice, create_bind_group, create_bind_group_layout, create_render_pipeline_with_options, create_sampler, texture_descriptor, RenderPipelineOptions};
+
+/// Position, texture coordinates, then sRGB color with premultiplied alpha.
+const VERTEX_SIZE: u64 = 4 * 4 + 4;
+
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+struct UniformData {
+    screen_size: [f32; 2],
+    output_srgb: u32,
+    padding: u32,
+}
+
+struct PainterTexture {
+    /// None for native textures, which are owned by the caller.
+    texture: Option<wgpu::Texture>,
+    bind_group: wgpu::BindGroup,
+}
+
+/// A mesh of the frame, in the shared vertex and index buffers.
+struct MeshDraw {
+    clip_rect: egui::Rect,
+    texture_id: egui::TextureId,
+    indices: std::ops::Range<u32>,
+    base_vertex: i32,
+}
+
+/// Draws the output of egui over a render target.
+pub struct EguiPainter {
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    output_srgb: bool,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    linear_sampler: wgpu::Sampler,
+    nearest_sampler: wgpu::Sampler,
+    textures: HashMap<egui::TextureId, PainterTexture>,
+    next_native_texture_id: u64,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+}
+
+impl EguiPainter {
+    pub fn new(device: Rc<wgpu::Device>, queue: Rc<wgpu::Queue>, target: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Egui shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/egui.wgsl").into()),
+        });
+
+        let uniform_bind_group_layout = create_bind_group_layout(&device, Some("Egui uniform bind group layout"), vec![
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+        ], wgpu::ShaderStages::VERTEX_FRAGMENT);
+
+        let texture_bind_group_layout = create_bind_group_layout(&device, Some("Egui texture bind group layout"), vec![
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        ], wgpu::ShaderStages::FRAGMENT);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Egui pipeline layout"),
+            bind_group_layouts: &[&uniform_bind_group_layout, &texture_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let vertex_layout = wgpu::VertexBufferLayout {
+            array_stride: VERTEX_SIZE,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Unorm8x4],
+        };
+
+        // egui colors have premultiplied alpha
+        let pipeline = create_render_pipeline_with_options(&device, Some("Egui pipeline"), &[vertex_layout], &pipeline_layout, &shader, target, RenderPipelineOptions {
+            blend: Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            cull_mode: None,
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Egui uniform buffer"),
+            size: std::mem::size_of::<UniformData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_bind_group = create_bind_group(&device, Some("Egui uniform bind group"), &uniform_bind_group_layout, vec![uniform_buffer.as_entire_binding()]);
+
+        let linear_sampler = create_sampler(&device, Some("Egui linear sampler"), wgpu::FilterMode::Linear);
+        let nearest_sampler = create_sampler(&device, Some("Egui nearest sampler"), wgpu::FilterMode::Nearest);
+
+        let vertex_buffer = create_buffer(&device, "Egui vertex buffer", wgpu::BufferUsages::VERTEX, 1024 * VERTEX_SIZE);
+        let index_buffer = create_buffer(&device, "Egui index buffer", wgpu::BufferUsages::INDEX, 3 * 1024 * 4);
+
+        Self {
+            device,
+            queue,
+            output_srgb: target.describe().srgb,
+            pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+            texture_bind_group_layout,
+            linear_sampler,
+            nearest_sampler,
+            textures: HashMap::new(),
+            next_native_texture_id: 0,
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    /// Make a texture view usable in egui images. The view must be of a filterable float format.
+    pub fn register_native_texture(&mut self, view: &wgpu::TextureView, filter: wgpu::FilterMode) -> egui::TextureId {
+        let id = egui::TextureId::User(self.next_native_texture_id);
+        self.next_native_texture_id += 1;
+        self.update_native_texture(id, view, filter);
+        id
+    }
+
+    /// Replace the view of a native texture, for instance after its texture was recreated.
+    pub fn update_native_texture(&mut self, id: egui::TextureId, view: &wgpu::TextureView, filter: wgpu::FilterMode) {
+        let bind_group = self.create_texture_bind_group(view, filter);
+        self.textures.insert(id, PainterTexture { texture: None, bind_group });
+    }
+
+    pub fn free_texture(&mut self, id: egui::TextureId) {
+        self.textures.remove(&id);
+    }
+
+    /// Apply the texture changes of an egui frame, to call before `paint`.
+    pub fn update_textures(&mut self, textures_delta: &egui::TexturesDelta) {
+        for (id, delta) in &textures_delta.set {
+            let (size, pixels): ([usize; 2], Vec<u8>) = match &delta.image {
+                egui::ImageData::Color(image) => (image.size, image.pixels.iter().flat_map(|color| color.to_array()).collect()),
+                egui::ImageData::Font(image) => (image.size, image.srgba_pixels(None).flat_map(|color| color.to_array()).collect()),
+            };
+            let extent = wgpu::Extent3d { width: size[0] as u32, height: size[1] as u32, depth_or_array_layers: 1 };
+
+            let origin = match delta.pos {
+                Some(pos) => wgpu::Origin3d { x: pos[0] as u32, y: pos[1] as u32, z: 0 },
+                None => {
+                    // egui colors are sRGB, sampling converts them to linear
+                    let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+                    let texture = self.device.create_texture(&texture_descriptor(Some("Egui texture"), extent.width, extent.height, wgpu::TextureFormat::Rgba8UnormSrgb, usage));
+                    let filter = match delta.options.magnification {
+                        egui::TextureFilter::Linear => wgpu::FilterMode::Linear,
+                        egui::TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+                    };
+                    let bind_group = self.create_texture_bind_group(&texture.create_view(&Default::default()), filter);
+                    self.textures.insert(*id, PainterTexture { texture: Some(texture), bind_group });
+                    wgpu::Origin3d::ZERO
+                },
+            };
+
+            let Some(texture) = self.textures.get(id).and_then(|texture| texture.texture.as_ref()) else { continue };
+
+            self.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &pixels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(extent.width * 4),
+                    rows_per_image: None,
+                },
+                extent,
+            );
+        }
+    }
+
+    /// Free the textures egui no longer uses, to call after `paint`.
+    pub fn free_textures(&mut self, textures_delta: &egui::TexturesDelta) {
+        for id in &textures_delta.free {
+            self.free_texture(*id);
+        }
+    }
+
+    /// Draw tessellated egui primitives over the output, `size_in_pixels` being the size of the output.
+    pub fn paint(&mut self, output_view: &wgpu::TextureView, primitives: &[egui::ClippedPrimitive], size_in_pixels: [u32; 2], pixels_per_point: f32) {
+        let mut vertices: Vec<u8> = vec![];
+        let mut indices: Vec<u32> = vec![];
+        let mut draws = vec![];
+
+        for primitive in primitives {
+            // paint callbacks are not supported
+            let egui::epaint::Primitive::Mesh(mesh) = &primitive.primitive else { continue };
+
+            draws.push(MeshDraw {
+                clip_rect: primitive.clip_rect,
+                texture_id: mesh.texture_id,
+                indices: indices.len() as u32..(indices.len() + mesh.indices.len()) as u32,
+                base_vertex: (vertices.len() as u64 / VERTEX_SIZE) as i32,
+            });
+
+            for vertex in &mesh.vertices {
+                vertices.extend_from_slice(bytemuck::cast_slice(&[vertex.pos.x, vertex.pos.y, vertex.uv.x, vertex.uv.y]));
+                vertices.extend_from_slice(&vertex.color.to_array());
+            }
+            indices.extend_from_slice(&mesh.indices);
+        }
+
+        if draws.is_empty() {
+            return;
+        }
+
+        self.reserve_buffers(vertices.len() as u64, (indices.len() * 4) as u64);
+        self.queue.write_buffer(&self.vertex_buffer, 0, &vertices);
+        self.queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indices));
+
+        let uniform_data = UniformData {
+            screen_size: [size_in_pixels[0] as f32 / pixels_per_point, size_in_pixels[1] as f32 / pixels_per_point],
+            output_srgb: self.output_srgb as u32,
+            padding: 0,
+        };
+        self.queue.write_buffer(&self.uniform_buffer, 0, unsafe { any_as_u8_slice(&uniform_data) });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Egui encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Egui pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..vertices.len() as u64));
+            render_pass.set_index_buffer(self.index_buffer.slice(..(indices.len() * 4) as u64), wgpu::IndexFormat::Uint32);
+
+            for draw in &draws {
+                let Some(texture) = self.textures.get(&draw.texture_id) else { continue };
+
+                // clip rectangles are in points, scissor rectangles in pixels inside of the output
+                let min_x = ((draw.clip_rect.min.x * pixels_per_point).round().max(0.0) as u32).min(size_in_pixels[0]);
+                let min_y = ((draw.clip_rect.min.y * pixels_per_point).round().max(0.0) as u32).min(size_in_pixels[1]);
+                let max_x = ((draw.clip_rect.max.x * pixels_per_point).round().max(0.0) as u32).min(size_in_pixels[0]);
+                let max_y = ((draw.clip_rect.max.y * pixels_per_point).round().max(0.0) as u32).min(size_in_pixels[1]);
+
+                if max_x <= min_x || max_y <= min_y {
+                    continue;
+                }
+
+                render_pass.set_scissor_rect(min_x, min_y, max_x - min_x, max_y - min_y);
+                render_pass.set_bind_group(1, &texture.bind_group, &[]);
+                render_pass.draw_indexed(draw.indices.clone(), draw.base_vertex, 0..1);
+            }
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    fn create_texture_bind_group(&self, view: &wgpu::TextureView, filter: wgpu::FilterMode) -> wgpu::BindGroup {
+        let sampler = match filter {
+            wgpu::FilterMode::Linear => &self.linear_sampler,
+            wgpu::FilterMode::Nearest => &self.nearest_sampler,
+        };
+
+        create_bind_group(&self.device, Some("Egui texture bind group"), &self.texture_bind_group_layout, vec![
+            wgpu::BindingResource::TextureView(view),
+            wgpu::BindingResource::Sampler(sampler),
+        ])
+    }
+
+    /// Grow the vertex and index buffers to hold at least the given sizes.
+    fn reserve_buffers(&mut self, vertex_size: u64, index_size: u64) {
+        if self.vertex_buffer.size() < vertex_size {
+            self.vertex_buffer = create_buffer(&self.device, "Egui vertex buffer", wgpu::BufferUsages::VERTEX, vertex_size.next_power_of_two());
+        }
+
+        if self.index_buffer.size() < index_size {
+            self.index_buffer = create_buffer(&self.device, "Egui index buffer", wgpu::BufferUsages::INDEX, index_size.next_power_of_two());
+        }
+    }
+}
+
+fn create_buffer(device: &wgpu::Device, label: &str, usage: wgpu::BufferUsages, size: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// egui debug overlay: gathers winit events, runs the UI between `begin_frame` and `end_frame`, and paints it over the output.
+pub struct EguiOverlay {
+    context: egui::Context,
+    winit_state: egui_winit::State,
+    painter: EguiPainter,
+    visible: bool,
+}
+
+impl EguiOverlay {
+    pub fn new<T>(device: Rc<wgpu::Device>, queue: Rc<wgpu::Queue>, event_loop: &EventLoopWindowTarget<T>, window: &Window, target: wgpu::TextureFormat) -> Self {
+        let mut winit_state = egui_winit::State::new(event_loop);
+        winit_state.set_pixels_per_point(window.scale_factor() as f32);
+        winit_state.set_max_texture_side(device.limits().max_texture_dimension_2d as usize);
+
+        Self {
+            context: egui::Context::default(),
+            winit_state,
+            painter: EguiPainter::new(device, queue, target),
+            visible: true,
+        }
+    }
+
+    /// The egui context, to build the UI between `begin_frame` and `end_frame`.
+    pub fn context(&self) -> &egui::Context {
+        &self.context
+    }
+
+    pub fn painter_mut(&mut self) -> &mut EguiPainter {
+        &mut self.painter
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Give a window event to egui. Returns true if the UI consumed it, in which case the application should ignore it.
+    pub fn on_event(&mut self, event: &WindowEvent) -> bool {
+        let response = self.winit_state.on_event(&self.context, event);
+        self.visible && response.consumed
+    }
+
+    pub fn begin_frame(&mut self, window: &Window) {
+        let raw_input = self.winit_state.take_egui_input(window);
+        self.context.begin_frame(raw_input);
+    }
+
+    /// Finish the UI of the frame and paint it over the output if the overlay is visible.
+    pub fn end_frame(&mut self, window: &Window, output_view: &wgpu::TextureView, size_in_pixels: [u32; 2]) {
+        let output = self.context.end_frame();
+        self.winit_state.handle_platform_output(window, &self.context, output.platform_output);
+
+        self.painter.update_textures(&output.textures_delta);
+
+        if self.visible {
+            let primitives = self.context.tessellate(output.shapes);
+            self.painter.paint(output_view, &primitives, size_in_pixels, self.context.pixels_per_point());
+        }
+
+        self.painter.free_textures(&output.textures_delta);
+    }
+}
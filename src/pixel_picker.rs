@@ -0,0 +1,234 @@
+use std::fmt;
+
+use crate::interlaced::FieldSource;
+use crate::utils::read_texture_region;
+
+/// Value of a pixel exactly as stored in its texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelValue {
+    /// 8 bits normalized channels in RGBA order (BGRA textures are swizzled), sRGB encoded for sRGB formats.
+    Unorm8([u8; 4]),
+    /// 16 or 32 bits float channels.
+    Float([f32; 4]),
+}
+
+impl fmt::Display for PixelValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PixelValue::Unorm8([r, g, b, a]) => write!(f, "RGBA {:3} {:3} {:3} {:3} ({:.4}, {:.4}, {:.4}, {:.4})", r, g, b, a, *r as f32 / 255.0, *g as f32 / 255.0, *b as f32 / 255.0, *a as f32 / 255.0),
+            PixelValue::Float([r, g, b, a]) => write!(f, "RGBA ({:.4}, {:.4}, {:.4}, {:.4})", r, g, b, a),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PixelReadError {
+    /// Only 8 bits RGBA and BGRA, and 16 and 32 bits float RGBA formats can be decoded.
+    UnsupportedFormat(wgpu::TextureFormat),
+    /// The texture was not created with the COPY_SRC usage.
+    NotCopyable,
+    OutOfBounds,
+}
+
+impl fmt::Display for PixelReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PixelReadError::UnsupportedFormat(format) => write!(f, "pixels of {:?} textures can not be decoded", format),
+            PixelReadError::NotCopyable => write!(f, "the texture can not be copied (no COPY_SRC usage)"),
+            PixelReadError::OutOfBounds => write!(f, "the pixel is outside of the texture"),
+        }
+    }
+}
+
+impl std::error::Error for PixelReadError {}
+
+/// Read back the value of a single pixel of the first mip level. Blocks until the GPU is done with the texture.
+pub fn read_pixel(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, x: u32, y: u32) -> Result<PixelValue, PixelReadError> {
+    let format = texture.format();
+
+    if !matches!(format, wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb | wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb | wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float) {
+        return Err(PixelReadError::UnsupportedFormat(format));
+    }
+    if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+        return Err(PixelReadError::NotCopyable);
+    }
+    if x >= texture.width() || y >= texture.height() {
+        return Err(PixelReadError::OutOfBounds);
+    }
+
+    let data = read_texture_region(device, queue, texture, x, y, 1, 1);
+
+    Ok(match format {
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => PixelValue::Unorm8([data[2], data[1], data[0], data[3]]),
+        wgpu::TextureFormat::Rgba16Float => {
+            let channel = |i: usize| f16_to_f32(u16::from_le_bytes([data[2 * i], data[2 * i + 1]]));
+            PixelValue::Float([channel(0), channel(1), channel(2), channel(3)])
+        },
+        wgpu::TextureFormat::Rgba32Float => {
+            let channel = |i: usize| f32::from_le_bytes([data[4 * i], data[4 * i + 1], data[4 * i + 2], data[4 * i + 3]]);
+            PixelValue::Float([channel(0), channel(1), channel(2), channel(3)])
+        },
+        _ => PixelValue::Unorm8([data[0], data[1], data[2], data[3]]),
+    })
+}
+
+/// Convert IEEE 754 half precision bits to a float.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    match exponent {
+        // subnormal
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Value of a pixel of one of the textures of a pick.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PixelReadout {
+    /// Name of the texture in the pipeline ("output", "field"...).
+    pub name: &'static str,
+    pub x: u32,
+    pub y: u32,
+    pub value: Result<PixelValue, PixelReadError>,
+}
+
+/// Values of an output pixel through the render targets it went through.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PixelPick {
+    /// Picked pixel of the output.
+    pub x: u32,
+    pub y: u32,
+    /// Field texel the output pixel was merged from, None when the output does not come from the fields.
+    pub source: Option<FieldSource>,
+    pub readouts: Vec<PixelReadout>,
+}
+
+impl PixelPick {
+    pub fn new(x: u32, y: u32, source: Option<FieldSource>) -> Self {
+        Self {
+            x,
+            y,
+            source,
+            readouts: vec![],
+        }
+    }
+
+    /// Read the pixel (x, y) of a texture, errors are kept in the readout.
+    pub fn read(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, name: &'static str, texture: &wgpu::Texture, x: u32, y: u32) {
+        self.readouts.push(PixelReadout {
+            name,
+            x,
+            y,
+            value: read_pixel(device, queue, texture, x, y),
+        });
+    }
+}
+
+impl fmt::Display for PixelPick {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pixel ({}, {})", self.x, self.y)?;
+
+        match self.source {
+            Some(source) => writeln!(f, ": output row {} comes from {} field, row {} (texel {}, {})", self.y, if source.field == 0 { "the even rows" } else { "the odd rows" }, source.y, source.x, source.y)?,
+            None => writeln!(f, ": not merged from the fields")?,
+        }
+
+        for readout in &self.readouts {
+            match &readout.value {
+                Ok(value) => writeln!(f, "  {} ({}, {}): {}", readout.name, readout.x, readout.y, value)?,
+                Err(e) => writeln!(f, "  {} ({}, {}): {}", readout.name, readout.x, readout.y, e)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::{test_device, texture_descriptor};
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(f16_to_f32(0x0000), 0.0);
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3800), 0.5);
+        assert_eq!(f16_to_f32(0x3555), 1365.0 / 4096.0);
+        // largest normal, smallest normal
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x0400), 2f32.powi(-14));
+
+        // negative zero keeps its sign
+        assert!(f16_to_f32(0x8000) == 0.0 && f16_to_f32(0x8000).is_sign_negative());
+    }
+
+    #[test]
+    fn half_float_subnormals() {
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x03ff), 1023.0 * 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x8200), -512.0 * 2f32.powi(-24));
+    }
+
+    #[test]
+    fn half_float_infinities_and_nans() {
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+        assert!(f16_to_f32(0x7c01).is_nan());
+        assert!(f16_to_f32(0xfe00).is_nan());
+    }
+
+    fn texture_with_pixel(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat, usage: wgpu::TextureUsages, pixel: &[u8]) -> wgpu::Texture {
+        let texture = device.create_texture(&texture_descriptor(None, 4, 2, format, usage | wgpu::TextureUsages::COPY_DST));
+        let layout = wgpu::ImageDataLayout { offset: 0, bytes_per_row: None, rows_per_image: None };
+        let origin = wgpu::ImageCopyTexture { origin: wgpu::Origin3d { x: 3, y: 1, z: 0 }, ..texture.as_image_copy() };
+        queue.write_texture(origin, pixel, layout, wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 });
+        texture
+    }
+
+    #[test]
+    fn decodes_formats() {
+        let Some((device, queue)) = test_device() else { return };
+        let usage = wgpu::TextureUsages::COPY_SRC;
+
+        let rgba = texture_with_pixel(&device, &queue, wgpu::TextureFormat::Rgba8Unorm, usage, &[10, 20, 30, 40]);
+        assert_eq!(read_pixel(&device, &queue, &rgba, 3, 1), Ok(PixelValue::Unorm8([10, 20, 30, 40])));
+        // the other pixels are untouched
+        assert_eq!(read_pixel(&device, &queue, &rgba, 2, 1), Ok(PixelValue::Unorm8([0, 0, 0, 0])));
+
+        // stored in BGRA order, returned in RGBA order
+        let bgra = texture_with_pixel(&device, &queue, wgpu::TextureFormat::Bgra8Unorm, usage, &[30, 20, 10, 40]);
+        assert_eq!(read_pixel(&device, &queue, &bgra, 3, 1), Ok(PixelValue::Unorm8([10, 20, 30, 40])));
+
+        let halves: Vec<u8> = [0x3c00u16, 0xc000, 0x0001, 0x7c00].iter().flat_map(|half| half.to_le_bytes()).collect();
+        let rgba16 = texture_with_pixel(&device, &queue, wgpu::TextureFormat::Rgba16Float, usage, &halves);
+        assert_eq!(read_pixel(&device, &queue, &rgba16, 3, 1), Ok(PixelValue::Float([1.0, -2.0, 2f32.powi(-24), f32::INFINITY])));
+
+        let floats: Vec<u8> = [0.25f32, -1.5, 1e-3, 7.0].iter().flat_map(|float| float.to_le_bytes()).collect();
+        let rgba32 = texture_with_pixel(&device, &queue, wgpu::TextureFormat::Rgba32Float, usage, &floats);
+        assert_eq!(read_pixel(&device, &queue, &rgba32, 3, 1), Ok(PixelValue::Float([0.25, -1.5, 1e-3, 7.0])));
+    }
+
+    #[test]
+    fn read_errors() {
+        let Some((device, queue)) = test_device() else { return };
+
+        let r8 = texture_with_pixel(&device, &queue, wgpu::TextureFormat::R8Unorm, wgpu::TextureUsages::COPY_SRC, &[1]);
+        assert_eq!(read_pixel(&device, &queue, &r8, 0, 0), Err(PixelReadError::UnsupportedFormat(wgpu::TextureFormat::R8Unorm)));
+
+        let not_copyable = texture_with_pixel(&device, &queue, wgpu::TextureFormat::Rgba8Unorm, wgpu::TextureUsages::TEXTURE_BINDING, &[0; 4]);
+        assert_eq!(read_pixel(&device, &queue, &not_copyable, 0, 0), Err(PixelReadError::NotCopyable));
+
+        let texture = texture_with_pixel(&device, &queue, wgpu::TextureFormat::Rgba8Unorm, wgpu::TextureUsages::COPY_SRC, &[0; 4]);
+        assert_eq!(read_pixel(&device, &queue, &texture, 4, 0), Err(PixelReadError::OutOfBounds));
+        assert_eq!(read_pixel(&device, &queue, &texture, 0, 2), Err(PixelReadError::OutOfBounds));
+    }
+}
@@ -0,0 +1,423 @@
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::Deserialize;
+
+use crate::utils::{any_as_u8_slice, slice_as_u8_slice, create_bind_group, create_bind_group_layout};
+
+/// Distance field of a shape, in its local space.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Primitive {
+    Circle { radius: f32 },
+    Box { half_size: [f32; 2] },
+    RoundedBox { half_size: [f32; 2], radius: f32 },
+    /// Capsule between two points.
+    Segment { a: [f32; 2], b: [f32; 2], thickness: f32 },
+    /// Closed polygon, convex or not, at least 3 points.
+    Polygon { points: Vec<[f32; 2]> },
+}
+
+/// Placement of a shape: scaled, then rotated, then translated.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: [f32; 2],
+    /// Counterclockwise, in degrees.
+    pub rotation: f32,
+    /// Uniform, so that distances stay exact.
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: [0.0, 0.0],
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+/// How a shape is combined with the shapes before it. Smooth operations blend over a distance of `k` world units.
+///
+/// Only unions bring the color of the shape, subtractions and intersections keep the colors of the shapes before it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum Operation {
+    #[default]
+    Union,
+    /// Cut the shape out of the previous ones.
+    Subtraction,
+    Intersection,
+    SmoothUnion(f32),
+    SmoothSubtraction(f32),
+    SmoothIntersection(f32),
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Shape {
+    pub primitive: Primitive,
+    #[serde(default)]
+    pub transform: Transform,
+    /// Linear RGB, white by default.
+    #[serde(default = "white")]
+    pub color: [f32; 3],
+    #[serde(default)]
+    pub operation: Operation,
+}
+
+fn white() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+/// 2D scene made of signed distance field shapes, combined in order: each shape is combined with the result of the shapes before it.
+///
+/// Scenes are loaded from RON files (see `scenes/default.ron`), with world units matching `Camera2d`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct SdfScene {
+    /// Linear RGB.
+    #[serde(default)]
+    pub background: [f32; 3],
+    /// Draw iso-distance bands outside of the shapes.
+    #[serde(default)]
+    pub show_distance: bool,
+    pub shapes: Vec<Shape>,
+}
+
+#[derive(Debug)]
+pub enum SdfSceneError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    /// A shape can not be evaluated, with the index of the shape.
+    InvalidShape(usize, &'static str),
+}
+
+impl fmt::Display for SdfSceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SdfSceneError::Io(e) => write!(f, "can not read the scene: {}", e),
+            SdfSceneError::Parse(e) => write!(f, "invalid scene: {}", e),
+            SdfSceneError::InvalidShape(index, reason) => write!(f, "invalid shape {}: {}", index, reason),
+        }
+    }
+}
+
+impl std::error::Error for SdfSceneError {}
+
+impl SdfScene {
+    pub fn from_ron_str(source: &str) -> Result<Self, SdfSceneError> {
+        let scene: SdfScene = ron::from_str(source).map_err(SdfSceneError::Parse)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SdfSceneError> {
+        let source = std::fs::read_to_string(path).map_err(SdfSceneError::Io)?;
+        Self::from_ron_str(&source)
+    }
+
+    fn validate(&self) -> Result<(), SdfSceneError> {
+        // NaNs fail every comparison: sizes are checked to be positive, rather than to not be zero or negative
+        let positive = |x: f32| x > 0.0 && x.is_finite();
+        let finite = |points: &[[f32; 2]]| points.iter().flatten().all(|x| x.is_finite());
+
+        for (index, shape) in self.shapes.iter().enumerate() {
+            let invalid = |reason| Err(SdfSceneError::InvalidShape(index, reason));
+
+            match &shape.primitive {
+                Primitive::Circle { radius } if !positive(*radius) => return invalid("the radius of a circle must be positive"),
+                Primitive::Box { half_size } | Primitive::RoundedBox { half_size, .. } if !half_size.iter().all(|&x| positive(x)) => return invalid("the half size of a box must be positive"),
+                Primitive::RoundedBox { radius, .. } if radius.is_nan() || *radius < 0.0 => return invalid("the radius of a rounded box can not be negative"),
+                Primitive::RoundedBox { half_size, radius } if *radius > half_size[0].min(half_size[1]) => return invalid("the radius of a rounded box can not exceed its half size"),
+                Primitive::Segment { thickness, .. } if !positive(*thickness) => return invalid("the thickness of a segment must be positive"),
+                Primitive::Segment { a, b, .. } if !finite(&[*a, *b]) => return invalid("the points of a segment must be finite"),
+                Primitive::Polygon { points } if points.len() < 3 => return invalid("a polygon needs at least 3 points"),
+                Primitive::Polygon { points } if !finite(points) => return invalid("the points of a polygon must be finite"),
+                _ => {},
+            }
+
+            if !positive(shape.transform.scale) {
+                return invalid("the scale must be positive");
+            }
+
+            if !finite(&[shape.transform.translation]) || !shape.transform.rotation.is_finite() {
+                return invalid("the translation and rotation must be finite");
+            }
+
+            match shape.operation {
+                Operation::SmoothUnion(k) | Operation::SmoothSubtraction(k) | Operation::SmoothIntersection(k) if !positive(k) => return invalid("the blend distance of a smooth operation must be positive"),
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shapes and polygon points as read by `sdf_scene.wgsl`.
+    fn encode(&self) -> (Vec<GpuShape>, Vec<[f32; 2]>) {
+        let mut shapes = Vec::with_capacity(self.shapes.len());
+        let mut points = vec![];
+
+        for shape in &self.shapes {
+            let (kind, params, radius, first_point, point_count) = match &shape.primitive {
+                Primitive::Circle { radius } => (0, [0.0; 4], *radius, 0, 0),
+                Primitive::Box { half_size } => (1, [half_size[0], half_size[1], 0.0, 0.0], 0.0, 0, 0),
+                Primitive::RoundedBox { half_size, radius } => (1, [half_size[0], half_size[1], 0.0, 0.0], *radius, 0, 0),
+                Primitive::Segment { a, b, thickness } => (2, [a[0], a[1], b[0], b[1]], thickness / 2.0, 0, 0),
+                Primitive::Polygon { points: polygon } => {
+                    let first_point = points.len() as u32;
+                    points.extend_from_slice(polygon);
+                    (3, [0.0; 4], 0.0, first_point, polygon.len() as u32)
+                },
+            };
+
+            let (operation, smoothness) = match shape.operation {
+                Operation::Union => (0, 0.0),
+                Operation::Subtraction => (1, 0.0),
+                Operation::Intersection => (2, 0.0),
+                Operation::SmoothUnion(k) => (3, k),
+                Operation::SmoothSubtraction(k) => (4, k),
+                Operation::SmoothIntersection(k) => (5, k),
+            };
+
+            let rotation = shape.transform.rotation.to_radians();
+
+            shapes.push(GpuShape {
+                params,
+                color: [shape.color[0], shape.color[1], shape.color[2], 1.0],
+                translation: shape.transform.translation,
+                rotation: [rotation.cos(), rotation.sin()],
+                scale: shape.transform.scale,
+                radius,
+                smoothness,
+                kind,
+                operation,
+                first_point,
+                point_count,
+                padding: 0,
+            });
+        }
+
+        (shapes, points)
+    }
+}
+
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+struct GpuShape {
+    /// Box: half size; segment: end points.
+    params: [f32; 4],
+    color: [f32; 4],
+    translation: [f32; 2],
+    /// Cosine and sine of the rotation.
+    rotation: [f32; 2],
+    scale: f32,
+    /// Circle radius, rounding of boxes or half thickness of segments.
+    radius: f32,
+    smoothness: f32,
+    /// 0 circle, 1 (rounded) box, 2 segment, 3 polygon.
+    kind: u32,
+    /// 0 union, 1 subtraction, 2 intersection, then their smooth versions.
+    operation: u32,
+    first_point: u32,
+    point_count: u32,
+    padding: u32,
+}
+
+#[repr(C)]
+#[allow(dead_code)] // only read by the GPU
+struct SceneUniform {
+    background: [f32; 4],
+    shape_count: u32,
+    show_distance: u32,
+    padding: [u32; 2],
+}
+
+/// GPU buffers of an `SdfScene`, bound as a bind group for `sdf_scene.wgsl` (group 1).
+pub struct SdfSceneBuffers {
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    shape_buffer: wgpu::Buffer,
+    point_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    shape_count: usize,
+}
+
+fn create_storage_buffer(device: &wgpu::Device, label: &str, size: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        // bound buffers can not be empty
+        size: size.max(16) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+impl SdfSceneBuffers {
+    pub fn new(device: Rc<wgpu::Device>, queue: Rc<wgpu::Queue>, scene: &SdfScene) -> Self {
+        let storage_binding = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+
+        let bind_group_layout = create_bind_group_layout(&device, Some("SDF scene bind group layout"),
+            vec![
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                storage_binding,
+                storage_binding,
+            ],
+        wgpu::ShaderStages::FRAGMENT);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SDF scene uniform buffer"),
+            size: std::mem::size_of::<SceneUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shape_buffer = create_storage_buffer(&device, "SDF scene shape buffer", 0);
+        let point_buffer = create_storage_buffer(&device, "SDF scene point buffer", 0);
+        let bind_group = create_bind_group(&device, Some("SDF scene bind group"), &bind_group_layout,
+            vec![uniform_buffer.as_entire_binding(), shape_buffer.as_entire_binding(), point_buffer.as_entire_binding()]
+        );
+
+        let mut buffers = Self {
+            device,
+            queue,
+            bind_group_layout,
+            uniform_buffer,
+            shape_buffer,
+            point_buffer,
+            bind_group,
+            shape_count: 0,
+        };
+        buffers.set_scene(scene);
+        buffers
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn shape_count(&self) -> usize {
+        self.shape_count
+    }
+
+    /// Upload another scene, the buffers grow when needed.
+    pub fn set_scene(&mut self, scene: &SdfScene) {
+        let (shapes, points) = scene.encode();
+        let shape_size = shapes.len() * std::mem::size_of::<GpuShape>();
+        let point_size = points.len() * std::mem::size_of::<[f32; 2]>();
+
+        if shape_size as u64 > self.shape_buffer.size() || point_size as u64 > self.point_buffer.size() {
+            self.shape_buffer = create_storage_buffer(&self.device, "SDF scene shape buffer", shape_size.max(self.shape_buffer.size() as usize));
+            self.point_buffer = create_storage_buffer(&self.device, "SDF scene point buffer", point_size.max(self.point_buffer.size() as usize));
+            self.bind_group = create_bind_group(&self.device, Some("SDF scene bind group"), &self.bind_group_layout,
+                vec![self.uniform_buffer.as_entire_binding(), self.shape_buffer.as_entire_binding(), self.point_buffer.as_entire_binding()]
+            );
+        }
+
+        let uniform = SceneUniform {
+            background: [scene.background[0], scene.background[1], scene.background[2], 1.0],
+            shape_count: shapes.len() as u32,
+            show_distance: scene.show_distance as u32,
+            padding: [0; 2],
+        };
+        self.queue.write_buffer(&self.uniform_buffer, 0, unsafe { any_as_u8_slice(&uniform) });
+
+        if !shapes.is_empty() {
+            self.queue.write_buffer(&self.shape_buffer, 0, unsafe { slice_as_u8_slice(&shapes) });
+        }
+        if !points.is_empty() {
+            self.queue.write_buffer(&self.point_buffer, 0, unsafe { slice_as_u8_slice(&points) });
+        }
+
+        self.shape_count = shapes.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_shape(source: &str) -> (usize, &'static str) {
+        match SdfScene::from_ron_str(source) {
+            Err(SdfSceneError::InvalidShape(index, reason)) => (index, reason),
+            result => panic!("{} should be an invalid shape, got {:?}", source, result),
+        }
+    }
+
+    #[test]
+    fn default_scene_is_valid() {
+        SdfScene::load("scenes/default.ron").unwrap();
+    }
+
+    #[test]
+    fn validate_errors() {
+        assert_eq!(invalid_shape("(shapes: [(primitive: Circle(radius: 1.0)), (primitive: Polygon(points: [(0.0, 0.0), (1.0, 0.0)]))])"), (1, "a polygon needs at least 3 points"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: RoundedBox(half_size: (1.0, 0.5), radius: 0.6))])").0, 0);
+        assert_eq!(invalid_shape("(shapes: [(primitive: Circle(radius: 1.0), transform: (scale: 0.0))])"), (0, "the scale must be positive"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: Circle(radius: 1.0)), (primitive: Box(half_size: (1.0, 1.0)), operation: SmoothUnion(0.0))])").0, 1);
+
+        // the radius of a rounded box may reach its half size
+        SdfScene::from_ron_str("(shapes: [(primitive: RoundedBox(half_size: (1.0, 0.5), radius: 0.5))])").unwrap();
+
+        assert!(matches!(SdfScene::from_ron_str("(shapes: [(primitive: Triangle)])"), Err(SdfSceneError::Parse(_))));
+    }
+
+    #[test]
+    fn validate_sizes() {
+        assert_eq!(invalid_shape("(shapes: [(primitive: Circle(radius: -1.0))])"), (0, "the radius of a circle must be positive"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: Box(half_size: (1.0, -0.5)))])"), (0, "the half size of a box must be positive"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: RoundedBox(half_size: (0.0, 1.0), radius: 0.0))])"), (0, "the half size of a box must be positive"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: RoundedBox(half_size: (1.0, 1.0), radius: -0.1))])"), (0, "the radius of a rounded box can not be negative"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: Segment(a: (0.0, 0.0), b: (1.0, 1.0), thickness: 0.0))])"), (0, "the thickness of a segment must be positive"));
+    }
+
+    #[test]
+    fn validate_nans() {
+        assert_eq!(invalid_shape("(shapes: [(primitive: Circle(radius: 1.0), transform: (scale: NaN))])"), (0, "the scale must be positive"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: Circle(radius: 1.0), operation: SmoothUnion(NaN))])"), (0, "the blend distance of a smooth operation must be positive"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: Circle(radius: NaN))])"), (0, "the radius of a circle must be positive"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: Box(half_size: (inf, 1.0)))])"), (0, "the half size of a box must be positive"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: RoundedBox(half_size: (1.0, 1.0), radius: NaN))])"), (0, "the radius of a rounded box can not be negative"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: Segment(a: (0.0, NaN), b: (1.0, 1.0), thickness: 0.1))])"), (0, "the points of a segment must be finite"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: Polygon(points: [(0.0, 0.0), (1.0, 0.0), (NaN, 1.0)]))])"), (0, "the points of a polygon must be finite"));
+        assert_eq!(invalid_shape("(shapes: [(primitive: Circle(radius: 1.0), transform: (rotation: NaN))])"), (0, "the translation and rotation must be finite"));
+    }
+
+    #[test]
+    fn gpu_shape_layout() {
+        // size of `Shape` in sdf_scene.wgsl: 76 bytes of fields, rounded up to the 16 bytes alignment of its vec4s
+        assert_eq!(std::mem::size_of::<GpuShape>(), 80);
+        assert_eq!(std::mem::size_of::<SceneUniform>(), 32);
+    }
+
+    #[test]
+    fn polygon_points_are_packed() {
+        let scene = SdfScene::from_ron_str(
+            "(shapes: [
+                (primitive: Polygon(points: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)])),
+                (primitive: Segment(a: (0.0, 0.0), b: (1.0, 1.0), thickness: 0.2), operation: Subtraction),
+                (primitive: Polygon(points: [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]), operation: SmoothUnion(0.1)),
+            ])",
+        ).unwrap();
+        let (shapes, points) = scene.encode();
+
+        assert_eq!(points.len(), 7);
+        assert_eq!((shapes[0].kind, shapes[0].first_point, shapes[0].point_count), (3, 0, 3));
+        assert_eq!((shapes[1].kind, shapes[1].operation, shapes[1].radius), (2, 1, 0.1));
+        assert_eq!((shapes[2].first_point, shapes[2].point_count, shapes[2].operation, shapes[2].smoothness), (3, 4, 3, 0.1));
+        assert_eq!(points[3], [0.0, 0.0]);
+    }
+}
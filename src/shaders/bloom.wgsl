@@ -0,0 +1,100 @@
+// Bloom passes: threshold prefilter, dual filter downsample and upsample, composite.
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    // single triangle covering the whole target
+    var out: VertexOutput;
+    let x = f32(i32(in_vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(in_vertex_index & 2u) * 2 - 1);
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+    return out;
+}
+
+
+// Fragment shaders
+
+struct BloomUniform {
+    threshold: f32,
+    soft_knee: f32,
+    intensity: f32,
+    radius: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> bloom: BloomUniform;
+
+@group(0) @binding(1)
+var texture_sampler: sampler;
+
+// texture read by the pass (frame for the prefilter and composite, previous level of the chain otherwise)
+@group(0) @binding(2)
+var source: texture_2d<f32>;
+
+// bloom chain, only read by the composite
+@group(0) @binding(3)
+var bloom_texture: texture_2d<f32>;
+
+fn source_texel_size() -> vec2<f32> {
+    return 1.0 / vec2<f32>(textureDimensions(source));
+}
+
+// 5 taps dual filter downsample
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let o = source_texel_size();
+    var sum = textureSample(source, texture_sampler, uv).rgb * 4.0;
+    sum += textureSample(source, texture_sampler, uv - o).rgb;
+    sum += textureSample(source, texture_sampler, uv + o).rgb;
+    sum += textureSample(source, texture_sampler, uv + vec2<f32>(o.x, -o.y)).rgb;
+    sum += textureSample(source, texture_sampler, uv - vec2<f32>(o.x, -o.y)).rgb;
+    return sum / 8.0;
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let col = downsample(in.uv);
+
+    // soft threshold: quadratic curve around the threshold, linear above it
+    let brightness = max(max(col.r, col.g), col.b);
+    var soft = clamp(brightness - bloom.threshold + bloom.soft_knee, 0.0, 2.0 * bloom.soft_knee);
+    soft = soft * soft / (4.0 * bloom.soft_knee + 0.0001);
+    let contribution = max(soft, brightness - bloom.threshold) / max(brightness, 0.0001);
+
+    return vec4<f32>(col * contribution, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// 8 taps dual filter upsample, the result is added to the level being rendered to
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let o = source_texel_size() * bloom.radius;
+    var sum = textureSample(source, texture_sampler, in.uv + vec2<f32>(-o.x * 2.0, 0.0)).rgb;
+    sum += textureSample(source, texture_sampler, in.uv + vec2<f32>(-o.x, o.y)).rgb * 2.0;
+    sum += textureSample(source, texture_sampler, in.uv + vec2<f32>(0.0, o.y * 2.0)).rgb;
+    sum += textureSample(source, texture_sampler, in.uv + vec2<f32>(o.x, o.y)).rgb * 2.0;
+    sum += textureSample(source, texture_sampler, in.uv + vec2<f32>(o.x * 2.0, 0.0)).rgb;
+    sum += textureSample(source, texture_sampler, in.uv + vec2<f32>(o.x, -o.y)).rgb * 2.0;
+    sum += textureSample(source, texture_sampler, in.uv + vec2<f32>(0.0, -o.y * 2.0)).rgb;
+    sum += textureSample(source, texture_sampler, in.uv + vec2<f32>(-o.x, -o.y)).rgb * 2.0;
+    return vec4<f32>(sum / 12.0, 1.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let col = textureSample(source, texture_sampler, in.uv).rgb;
+    let glow = textureSample(bloom_texture, texture_sampler, in.uv).rgb;
+    return vec4<f32>(col + glow * bloom.intensity, 1.0);
+}
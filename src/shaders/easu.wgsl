@@ -0,0 +1,194 @@
+// Edge-adaptive spatial upscaling, derived from the EASU pass of AMD FidelityFX Super Resolution 1.0 (MIT license).
+// The input is the woven frame: both fields interleaved row by row, at the field resolution.
+
+fn norm_to_coord(f: f32) -> f32 {
+    // scale [0; 1] to [-1; 1]
+    return (f * 2.0) - 1.0;
+}
+
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) vert_pos: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let x = norm_to_coord(f32(in_vertex_index & 1u));
+    let y = norm_to_coord(f32(in_vertex_index & 2u));
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.vert_pos = out.clip_position.xyz;
+    return out;
+}
+
+
+// Fragment shader
+
+struct GlobalUniform {
+    width: u32,
+    height: u32,
+    field_width: u32,
+    field_height: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> global: GlobalUniform;
+
+@group(0) @binding(1)
+var input_texture1: texture_2d<f32>;
+
+@group(0) @binding(2)
+var input_texture2: texture_2d<f32>;
+
+// Texel of the woven frame, clamped to its borders. Even rows are in the first field.
+fn load_woven(x: i32, y: i32) -> vec3<f32> {
+    let cx = clamp(x, 0, i32(global.field_width) - 1);
+    let cy = clamp(y, 0, i32(global.field_height) * 2 - 1);
+    let coord = vec2<i32>(cx, cy / 2);
+
+    if ((cy & 1) == 0) {
+        return textureLoad(input_texture1, coord, 0).rgb;
+    }
+
+    return textureLoad(input_texture2, coord, 0).rgb;
+}
+
+// Approximate luma (times 2).
+fn luma(c: vec3<f32>) -> f32 {
+    return c.b * 0.5 + (c.r * 0.5 + c.g);
+}
+
+// Accumulate direction and length of the edge for one of the 4 bilinear quadrants around the position.
+//    a
+//  b c d
+//    e
+fn edge(w: f32, la: f32, lb: f32, lc: f32, ld: f32, le: f32) -> vec3<f32> {
+    let dir_x = ld - lb;
+    let max_x = max(abs(ld - lc), abs(lc - lb));
+    var len_x = 0.0;
+    if (max_x > 0.0) {
+        len_x = clamp(abs(dir_x) / max_x, 0.0, 1.0);
+    }
+
+    let dir_y = le - la;
+    let max_y = max(abs(le - lc), abs(lc - la));
+    var len_y = 0.0;
+    if (max_y > 0.0) {
+        len_y = clamp(abs(dir_y) / max_y, 0.0, 1.0);
+    }
+
+    // (dir.x, dir.y, len)
+    return vec3<f32>(dir_x, dir_y, len_x * len_x + len_y * len_y) * w;
+}
+
+// Weight of a tap, from an approximation of lanczos2 stretched along the edge.
+fn tap_weight(off: vec2<f32>, dir: vec2<f32>, len: vec2<f32>, lob: f32, clp: f32) -> f32 {
+    // rotate offset by direction, then apply anisotropy
+    let v = vec2<f32>(off.x * dir.x + off.y * dir.y, -off.x * dir.y + off.y * dir.x) * len;
+    let d2 = min(dot(v, v), clp);
+
+    //  (25/16 * (2/5 * x^2 - 1)^2 - (25/16 - 1)) * (lob * x^2 - 1)^2
+    var wb = 2.0 / 5.0 * d2 - 1.0;
+    var wa = lob * d2 - 1.0;
+    wb *= wb;
+    wa *= wa;
+    wb = 25.0 / 16.0 * wb - (25.0 / 16.0 - 1.0);
+    return wb * wa;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // position of the output pixel center in the woven frame
+    let scale = vec2<f32>(f32(global.field_width) / f32(global.width), f32(global.field_height * 2u) / f32(global.height));
+    var pp = in.clip_position.xy * scale - 0.5;
+    let fp = floor(pp);
+    pp -= fp;
+    let x = i32(fp.x);
+    let y = i32(fp.y);
+
+    // 12-tap kernel
+    //    b c
+    //  e f g h
+    //  i j k l
+    //    n o
+    let b = load_woven(x, y - 1);
+    let c = load_woven(x + 1, y - 1);
+    let e = load_woven(x - 1, y);
+    let f = load_woven(x, y);
+    let g = load_woven(x + 1, y);
+    let h = load_woven(x + 2, y);
+    let i = load_woven(x - 1, y + 1);
+    let j = load_woven(x, y + 1);
+    let k = load_woven(x + 1, y + 1);
+    let l = load_woven(x + 2, y + 1);
+    let n = load_woven(x, y + 2);
+    let o = load_woven(x + 1, y + 2);
+
+    let bl = luma(b);
+    let cl = luma(c);
+    let el = luma(e);
+    let fl = luma(f);
+    let gl = luma(g);
+    let hl = luma(h);
+    let il = luma(i);
+    let jl = luma(j);
+    let kl = luma(k);
+    let ll = luma(l);
+    let nl = luma(n);
+    let ol = luma(o);
+
+    // edge analysis, bilinearly weighted between the 4 nearest texels
+    var acc = edge((1.0 - pp.x) * (1.0 - pp.y), bl, el, fl, gl, jl);
+    acc += edge(pp.x * (1.0 - pp.y), cl, fl, gl, hl, kl);
+    acc += edge((1.0 - pp.x) * pp.y, fl, il, jl, kl, nl);
+    acc += edge(pp.x * pp.y, gl, jl, kl, ll, ol);
+
+    // normalize direction, defaulting to horizontal when there is no edge
+    var dir = acc.xy;
+    let dir_r = dot(dir, dir);
+    if (dir_r < 1.0 / 32768.0) {
+        dir = vec2<f32>(1.0, 0.0);
+    } else {
+        dir *= inverseSqrt(dir_r);
+    }
+
+    // transform length from {0 to 2} to {0 to 1} and shape it
+    var len = acc.z * 0.5;
+    len *= len;
+
+    // stretch kernel {1.0 vert|horz, to sqrt(2.0) on diagonal}
+    let stretch = dot(dir, dir) / max(abs(dir.x), abs(dir.y));
+    let len2 = vec2<f32>(1.0 + (stretch - 1.0) * len, 1.0 - 0.5 * len);
+    // window shifts from +/-sqrt(2.0) to slightly beyond 2.0 depending on the amount of edge
+    let lob = 0.5 + ((1.0 / 4.0 - 0.04) - 0.5) * len;
+    let clp = 1.0 / lob;
+
+    var col = vec3<f32>(0.0);
+    var weight = 0.0;
+    var w = 0.0;
+
+    w = tap_weight(vec2<f32>(0.0, -1.0) - pp, dir, len2, lob, clp); col += b * w; weight += w;
+    w = tap_weight(vec2<f32>(1.0, -1.0) - pp, dir, len2, lob, clp); col += c * w; weight += w;
+    w = tap_weight(vec2<f32>(-1.0, 1.0) - pp, dir, len2, lob, clp); col += i * w; weight += w;
+    w = tap_weight(vec2<f32>(0.0, 1.0) - pp, dir, len2, lob, clp); col += j * w; weight += w;
+    w = tap_weight(vec2<f32>(0.0, 0.0) - pp, dir, len2, lob, clp); col += f * w; weight += w;
+    w = tap_weight(vec2<f32>(-1.0, 0.0) - pp, dir, len2, lob, clp); col += e * w; weight += w;
+    w = tap_weight(vec2<f32>(1.0, 1.0) - pp, dir, len2, lob, clp); col += k * w; weight += w;
+    w = tap_weight(vec2<f32>(2.0, 1.0) - pp, dir, len2, lob, clp); col += l * w; weight += w;
+    w = tap_weight(vec2<f32>(2.0, 0.0) - pp, dir, len2, lob, clp); col += h * w; weight += w;
+    w = tap_weight(vec2<f32>(1.0, 0.0) - pp, dir, len2, lob, clp); col += g * w; weight += w;
+    w = tap_weight(vec2<f32>(1.0, 2.0) - pp, dir, len2, lob, clp); col += o * w; weight += w;
+    w = tap_weight(vec2<f32>(0.0, 2.0) - pp, dir, len2, lob, clp); col += n * w; weight += w;
+
+    // normalize and dering with the 4 nearest texels
+    let min4 = min(min(f, g), min(j, k));
+    let max4 = max(max(f, g), max(j, k));
+    col = clamp(col / weight, min4, max4);
+
+    return vec4<f32>(col, 1.0);
+}
@@ -0,0 +1,69 @@
+// Paint egui meshes: premultiplied sRGB vertex colors, multiplied by a texture.
+
+struct GlobalUniform {
+    // size of the screen in egui points
+    screen_size: vec2<f32>,
+    // 1 if the target encodes to sRGB by itself, 0 if colors must be gamma encoded by the shader
+    output_srgb: u32,
+    padding: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> global: GlobalUniform;
+
+@group(1) @binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var input_sampler: sampler;
+
+fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, cutoff);
+}
+
+fn srgb_from_linear(rgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = rgb < vec3<f32>(0.0031308);
+    let lower = rgb * vec3<f32>(12.92);
+    let higher = vec3<f32>(1.055) * pow(rgb, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+    return select(higher, lower, cutoff);
+}
+
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    @location(0) pos: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // egui positions are in points from the top left corner
+    out.clip_position = vec4<f32>(2.0 * pos.x / global.screen_size.x - 1.0, 1.0 - 2.0 * pos.y / global.screen_size.y, 0.0, 1.0);
+    out.uv = uv;
+    out.color = vec4<f32>(linear_from_srgb(color.rgb), color.a);
+    return out;
+}
+
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = in.color * textureSample(input_texture, input_sampler, in.uv);
+
+    if (global.output_srgb == 1u) {
+        return color;
+    }
+
+    return vec4<f32>(srgb_from_linear(color.rgb), color.a);
+}
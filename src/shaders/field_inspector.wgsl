@@ -0,0 +1,119 @@
+// Field inspector: both fields side by side or stacked vertically at native resolution (or zoomed), or the difference between them.
+
+// Vertex shader
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> @builtin(position) vec4<f32> {
+    // single triangle covering the whole target
+    let x = f32(i32(in_vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(in_vertex_index & 2u) * 2 - 1);
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+
+// Fragment shader
+
+struct InspectorUniform {
+    output_size: vec2<f32>,
+    // texel of the fields at the top left corner of each view
+    pan: vec2<f32>,
+    // output pixel under the cursor, negative when outside of the window
+    cursor: vec2<f32>,
+    // pixels of the output per texel of the fields
+    zoom: f32,
+    // multiplier of the difference image
+    difference_scale: f32,
+    // 0 side by side, 1 stacked, 2 difference
+    view: u32,
+    // index of the field rendered this frame, highlighted
+    current_field: u32,
+    // mip level shown, above 0 when zoomed out
+    level: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> inspector: InspectorUniform;
+
+@group(0) @binding(1)
+var field1: texture_2d<f32>;
+
+@group(0) @binding(2)
+var field2: texture_2d<f32>;
+
+const BACKGROUND = vec4<f32>(0.1, 0.1, 0.1, 1.0);
+const HIGHLIGHT = vec4<f32>(1.0, 0.8, 0.0, 1.0);
+const CURSOR = vec4<f32>(0.0, 1.0, 1.0, 1.0);
+const BORDER_WIDTH = 3.0;
+
+// size of the view of each field, in output pixels
+fn view_size() -> vec2<f32> {
+    if (inspector.view == 0u) {
+        return vec2<f32>(floor(inspector.output_size.x / 2.0), inspector.output_size.y);
+    }
+    if (inspector.view == 1u) {
+        return vec2<f32>(inspector.output_size.x, floor(inspector.output_size.y / 2.0));
+    }
+
+    return inspector.output_size;
+}
+
+// 0 for the view of the first field (left or top), 1 for the second one (the difference view is 0)
+fn view_at(pixel: vec2<f32>) -> u32 {
+    if (inspector.view == 0u && pixel.x >= view_size().x) {
+        return 1u;
+    }
+    if (inspector.view == 1u && pixel.y >= view_size().y) {
+        return 1u;
+    }
+
+    return 0u;
+}
+
+// position in the view under an output pixel, in output pixels
+fn local_position(pixel: vec2<f32>) -> vec2<f32> {
+    let offset = select(vec2<f32>(0.0, view_size().y), vec2<f32>(view_size().x, 0.0), inspector.view == 0u);
+    return pixel - offset * f32(view_at(pixel));
+}
+
+fn texel_at(pixel: vec2<f32>) -> vec2<i32> {
+    return vec2<i32>(floor(local_position(pixel) / inspector.zoom + inspector.pan));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = position.xy;
+    let view = view_at(pixel);
+    let local = local_position(pixel);
+    let texel = texel_at(pixel);
+
+    // border of the view of the current field
+    if (inspector.view != 2u && view == inspector.current_field) {
+        if (any(local < vec2<f32>(BORDER_WIDTH)) || any(local >= view_size() - BORDER_WIDTH)) {
+            return HIGHLIGHT;
+        }
+    }
+
+    // outline of the texel under the cursor, once texels are large enough
+    if (inspector.zoom >= 4.0 && all(inspector.cursor >= vec2<f32>(0.0)) && view_at(inspector.cursor) == view && all(texel_at(inspector.cursor) == texel)) {
+        let in_texel = fract(local / inspector.zoom + inspector.pan) * inspector.zoom;
+        if (any(in_texel < vec2<f32>(1.0)) || any(in_texel >= vec2<f32>(inspector.zoom - 1.0))) {
+            return CURSOR;
+        }
+    }
+
+    let size = vec2<i32>(textureDimensions(field1));
+    if (any(texel < vec2<i32>(0)) || any(texel >= size)) {
+        return BACKGROUND;
+    }
+
+    let color1 = textureLoad(field1, texel >> vec2<u32>(inspector.level), i32(inspector.level));
+    let color2 = textureLoad(field2, texel >> vec2<u32>(inspector.level), i32(inspector.level));
+
+    if (inspector.view == 2u) {
+        return vec4<f32>(min(abs(color1.rgb - color2.rgb) * inspector.difference_scale, vec3<f32>(1.0)), 1.0);
+    }
+
+    return select(color1, color2, view == 1u);
+}
@@ -0,0 +1,90 @@
+// Compare a test image to a reference: squared error and SSIM of each 8x8 window, and a per-pixel error heatmap.
+// Must match the CPU fallback in image_metrics.rs.
+
+struct Uniform {
+    width: u32,
+    height: u32,
+    // error mapped to the top of the heatmap color scale is 1 / heatmap_scale
+    heatmap_scale: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> global: Uniform;
+
+@group(0) @binding(1)
+var reference: texture_2d<f32>;
+
+@group(0) @binding(2)
+var test: texture_2d<f32>;
+
+@group(0) @binding(3)
+var heatmap: texture_storage_2d<rgba8unorm, write>;
+
+// per window: sum of squared errors (all channels), SSIM, count of pixels
+@group(0) @binding(4)
+var<storage, read_write> windows: array<vec4<f32>>;
+
+// luma x, y, x², y²
+var<workgroup> moments: array<vec4<f32>, 64>;
+// xy, squared error, pixel count
+var<workgroup> errors: array<vec4<f32>, 64>;
+
+const C1: f32 = 0.0001; // (0.01 * 1.0)²
+const C2: f32 = 0.0009; // (0.03 * 1.0)²
+
+fn luma(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// black, red, yellow then white as the error grows
+fn heat(t: f32) -> vec3<f32> {
+    return clamp(vec3<f32>(t * 3.0, t * 3.0 - 1.0, t * 3.0 - 2.0), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(num_workgroups) group_count: vec3<u32>,
+) {
+    moments[local_index] = vec4<f32>(0.0);
+    errors[local_index] = vec4<f32>(0.0);
+
+    if (id.x < global.width && id.y < global.height) {
+        let coord = vec2<i32>(id.xy);
+        let r = textureLoad(reference, coord, 0).rgb;
+        let t = textureLoad(test, coord, 0).rgb;
+        let diff = t - r;
+
+        let x = luma(r);
+        let y = luma(t);
+        moments[local_index] = vec4<f32>(x, y, x * x, y * y);
+        errors[local_index] = vec4<f32>(x * y, dot(diff, diff), 1.0, 0.0);
+
+        textureStore(heatmap, coord, vec4<f32>(heat(length(diff) / sqrt(3.0) * global.heatmap_scale), 1.0));
+    }
+
+    workgroupBarrier();
+
+    if (local_index == 0u) {
+        var m = vec4<f32>(0.0);
+        var e = vec4<f32>(0.0);
+
+        for (var i = 0u; i < 64u; i++) {
+            m += moments[i];
+            e += errors[i];
+        }
+
+        let n = e.z;
+        let mean_x = m.x / n;
+        let mean_y = m.y / n;
+        let var_x = max(m.z / n - mean_x * mean_x, 0.0);
+        let var_y = max(m.w / n - mean_y * mean_y, 0.0);
+        let cov = e.x / n - mean_x * mean_y;
+
+        let ssim = ((2.0 * mean_x * mean_y + C1) * (2.0 * cov + C2)) / ((mean_x * mean_x + mean_y * mean_y + C1) * (var_x + var_y + C2));
+
+        windows[group.y * group_count.x + group.x] = vec4<f32>(e.y, ssim, n, 0.0);
+    }
+}
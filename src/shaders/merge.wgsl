@@ -36,6 +36,8 @@ fn vs_main(
 struct GlobalUniform {
     width: u32,
     height: u32,
+    field_width: u32,
+    field_height: u32,
 };
 
 @group(0) @binding(0)
@@ -55,8 +57,13 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let x = u32(f32(global.width) * coord_to_norm(in.vert_pos.x));
     let y = u32(f32(global.height) * coord_to_norm(-in.vert_pos.y));
 
-    var col1 = textureLoad(input_texture1, vec2<i32>(i32(x / 1u), i32(y / 2u)), 0);
-    var col2 = textureLoad(input_texture2, vec2<i32>(i32(x / 1u), i32(y / 2u)), 0);
+    // fields may be rendered at a lower resolution than the output (dynamic resolution)
+    let field_x = x * global.field_width / global.width;
+    // the last row of an odd height has no field row of its own, it repeats the last row of its field
+    let field_y = min((y / 2u) * global.field_height / max(global.height / 2u, 1u), global.field_height - 1u);
+
+    var col1 = textureLoad(input_texture1, vec2<i32>(i32(field_x), i32(field_y)), 0);
+    var col2 = textureLoad(input_texture2, vec2<i32>(i32(field_x), i32(field_y)), 0);
 
     var col = vec4<f32>(0.0, 0.0, 0.0, 0.0);
 
@@ -0,0 +1,134 @@
+// Common utility functions
+
+fn coord_to_norm(f: f32) -> f32 {
+    // scale [-1; 1] to [0; 1]
+    return (f + 1.0) / 2.0;
+}
+
+fn norm_to_coord(f: f32) -> f32 {
+    // scale [0; 1] to [-1; 1]
+    return (f * 2.0) - 1.0;
+}
+
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) vert_pos: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let x = norm_to_coord(f32(in_vertex_index & 1u));
+    let y = norm_to_coord(f32(in_vertex_index & 2u));
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.vert_pos = out.clip_position.xyz;
+    return out;
+}
+
+
+// Fragment shader
+
+struct GlobalUniform {
+    width: u32,
+    height: u32,
+    field_width: u32,
+    field_height: u32,
+    frame_number: u32,
+    crt_mask: u32,
+    crt_scanline_intensity: f32,
+    crt_persistence: f32,
+    crt_mask_intensity: f32,
+    crt_curvature: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> global: GlobalUniform;
+
+@group(0) @binding(1)
+var input_texture1: texture_2d<f32>;
+
+@group(0) @binding(2)
+var input_texture2: texture_2d<f32>;
+
+// Color of a full frame row, read from the field owning that row (even rows are in the first texture).
+fn load_row(x: u32, y: u32) -> vec3<f32> {
+    let field_x = x * global.field_width / global.width;
+    // the last row of an odd height has no field row of its own, it repeats the last row of its field
+    let field_y = min((y / 2u) * global.field_height / max(global.height / 2u, 1u), global.field_height - 1u);
+    let coord = vec2<i32>(i32(field_x), i32(field_y));
+
+    if ((y & 1u) == 0u) {
+        return textureLoad(input_texture1, coord, 0).rgb;
+    }
+
+    return textureLoad(input_texture2, coord, 0).rgb;
+}
+
+// Barrel distortion of a [0; 1] position. The middle of each edge stays in place, corners go outside of [0; 1].
+fn barrel(uv: vec2<f32>) -> vec2<f32> {
+    let c = vec2<f32>(norm_to_coord(uv.x), norm_to_coord(uv.y));
+    let d = c * (1.0 + global.crt_curvature * dot(c, c)) / (1.0 + global.crt_curvature);
+    return vec2<f32>(coord_to_norm(d.x), coord_to_norm(d.y));
+}
+
+// Attenuation of each color channel by the phosphor mask at a given output pixel.
+fn mask(x: u32, y: u32) -> vec3<f32> {
+    var triad = x % 3u;
+
+    if (global.crt_mask == 1u) {
+        // shadow mask: triads of every other line pair are shifted by 2 of their 3 subpixels, half a triad rounded to whole pixels
+        triad = (x + ((y / 2u) & 1u) * 2u) % 3u;
+    }
+
+    var m = vec3<f32>(1.0 - global.crt_mask_intensity);
+    m[triad] = 1.0;
+    return m;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let out_x = u32(f32(global.width) * coord_to_norm(in.vert_pos.x));
+    let out_y = u32(f32(global.height) * coord_to_norm(-in.vert_pos.y));
+
+    let uv = barrel(vec2<f32>(coord_to_norm(in.vert_pos.x), coord_to_norm(-in.vert_pos.y)));
+
+    if (uv.x < 0.0 || uv.y < 0.0 || uv.x >= 1.0 || uv.y >= 1.0) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let x = min(u32(uv.x * f32(global.width)), global.width - 1u);
+    let y = min(u32(uv.y * f32(global.height)), global.height - 1u);
+
+    // the field rendered this frame is the first texture (even rows) on even frames
+    let current_field = global.frame_number & 1u;
+
+    var col = load_row(x, y);
+
+    if ((y & 1u) != current_field) {
+        // rows of the inactive field still glow with the older field, fading toward the fresh rows around them
+        var y_above = y + 1u;
+        if (y > 0u) {
+            y_above = y - 1u;
+        }
+
+        var y_below = y_above;
+        if (y + 1u < global.height) {
+            y_below = y + 1u;
+        }
+
+        let fresh = (load_row(x, y_above) + load_row(x, y_below)) * 0.5;
+        col = mix(fresh, col, global.crt_persistence);
+        col *= 1.0 - global.crt_scanline_intensity;
+    }
+
+    if (global.crt_mask != 0u) {
+        col *= mask(out_x, out_y);
+    }
+
+    return vec4<f32>(col, 1.0);
+}
@@ -0,0 +1,148 @@
+// Temporal anti-aliasing merge: each field is rendered with a jitter offset and accumulated into its field texture, which is the history of the next field.
+// fs_main blends the field rendered this frame with the accumulated other field (its output is copied back into the current field texture), fs_resolve weaves both fields to the output.
+
+// Common utility functions
+
+fn coord_to_norm(f: f32) -> f32 {
+    // scale [-1; 1] to [0; 1]
+    return (f + 1.0) / 2.0;
+}
+
+fn norm_to_coord(f: f32) -> f32 {
+    // scale [0; 1] to [-1; 1]
+    return (f * 2.0) - 1.0;
+}
+
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) vert_pos: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let x = norm_to_coord(f32(in_vertex_index & 1u));
+    let y = norm_to_coord(f32(in_vertex_index & 2u));
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.vert_pos = out.clip_position.xyz;
+    return out;
+}
+
+
+// Fragment shader
+
+struct GlobalUniform {
+    width: u32,
+    height: u32,
+    field_width: u32,
+    field_height: u32,
+    frame_number: u32,
+    crt_mask: u32,
+    crt_scanline_intensity: f32,
+    crt_persistence: f32,
+    crt_mask_intensity: f32,
+    crt_curvature: f32,
+    rcas_sharpness: f32,
+    taa_feedback: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> global: GlobalUniform;
+
+@group(0) @binding(1)
+var input_texture1: texture_2d<f32>;
+
+@group(0) @binding(2)
+var input_texture2: texture_2d<f32>;
+
+// Texel of a field (the first one holds the even output rows), clamped to the edges.
+fn load_field(field: u32, texel: vec2<i32>) -> vec3<f32> {
+    let coord = clamp(texel, vec2<i32>(0, 0), vec2<i32>(i32(global.field_width) - 1, i32(global.field_height) - 1));
+
+    if (field == 0u) {
+        return textureLoad(input_texture1, coord, 0).rgb;
+    }
+
+    return textureLoad(input_texture2, coord, 0).rgb;
+}
+
+// Accumulation pass, rendered at the field resolution: the next history of the current field.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = vec2<i32>(i32(f32(global.field_width) * coord_to_norm(in.vert_pos.x)), i32(f32(global.field_height) * coord_to_norm(-in.vert_pos.y)));
+
+    // the field rendered this frame holds the even rows on even frames
+    let current_field = global.frame_number & 1u;
+    let col = load_field(current_field, texel);
+
+    // history: the accumulated other field (rendered with other jitter offsets), interpolated at this row.
+    // Rows of the second field are below the rows of the first field with the same index.
+    let above = load_field(1u - current_field, texel + vec2<i32>(0, i32(current_field) - 1));
+    let below = load_field(1u - current_field, texel + vec2<i32>(0, i32(current_field)));
+    let history = (above + below) * 0.5;
+
+    // clamped to the neighborhood of the new samples to reject stale history
+    var min_col = col;
+    var max_col = col;
+
+    for (var dy = -1; dy <= 1; dy++) {
+        for (var dx = -1; dx <= 1; dx++) {
+            let neighbor = load_field(current_field, texel + vec2<i32>(dx, dy));
+            min_col = min(min_col, neighbor);
+            max_col = max(max_col, neighbor);
+        }
+    }
+
+    return vec4<f32>(mix(col, clamp(history, min_col, max_col), global.taa_feedback), 1.0);
+}
+
+// Color of a full frame row, read from the field owning that row (same mapping as merge.wgsl).
+fn load_row(x: u32, y: u32) -> vec3<f32> {
+    let field_x = x * global.field_width / global.width;
+    // the last row of an odd height has no field row of its own, it repeats the last row of its field
+    let field_y = min((y / 2u) * global.field_height / max(global.height / 2u, 1u), global.field_height - 1u);
+    return load_field(y & 1u, vec2<i32>(i32(field_x), i32(field_y)));
+}
+
+// Resolve pass: weave of the accumulated fields, the rows of the other field are clamped to the rows of the current field around them.
+@fragment
+fn fs_resolve(in: VertexOutput) -> @location(0) vec4<f32> {
+    let x = u32(f32(global.width) * coord_to_norm(in.vert_pos.x));
+    let y = u32(f32(global.height) * coord_to_norm(-in.vert_pos.y));
+
+    let current_field = global.frame_number & 1u;
+    let col = load_row(x, y);
+
+    if ((y & 1u) == current_field) {
+        return vec4<f32>(col, 1.0);
+    }
+
+    // nearest rows of the current field on each side of y
+    var y_above = y + 1u;
+    if (y > 0u) {
+        y_above = y - 1u;
+    }
+
+    var y_below = y_above;
+    if (y + 1u < global.height) {
+        y_below = y + 1u;
+    }
+
+    var min_col = vec3<f32>(1e9);
+    var max_col = vec3<f32>(-1e9);
+
+    for (var i = 0u; i < 3u; i++) {
+        let nx = clamp(x + i, 1u, global.width) - 1u;
+        let above = load_row(nx, y_above);
+        let below = load_row(nx, y_below);
+        min_col = min(min_col, min(above, below));
+        max_col = max(max_col, max(above, below));
+    }
+
+    return vec4<f32>(clamp(col, min_col, max_col), 1.0);
+}
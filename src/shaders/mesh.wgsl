@@ -0,0 +1,80 @@
+// Meshes shaded with Lambert diffuse and Blinn-Phong specular reflection of a directional light.
+
+struct Camera3d {
+    view: mat4x4<f32>,
+    // includes the jitter
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    position: vec3<f32>,
+    orthographic: u32,
+};
+
+@group(0) @binding(2)
+var<uniform> camera: Camera3d;
+
+struct Object {
+    model: mat4x4<f32>,
+    normal_matrix: mat3x3<f32>,
+    color: vec4<f32>,
+    specular: f32,
+    shininess: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> object: Object;
+
+struct Light {
+    // to the light
+    direction: vec3<f32>,
+    color: vec3<f32>,
+    ambient: vec3<f32>,
+};
+
+@group(1) @binding(1)
+var<uniform> light: Light;
+
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = object.model * vec4<f32>(in.position, 1.0);
+    out.clip_position = camera.view_projection * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = object.normal_matrix * in.normal;
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n = normalize(in.normal);
+    let l = normalize(light.direction);
+
+    // to the viewer: the same for all the fragments with an orthographic projection (third row of the view matrix)
+    var v = normalize(camera.position - in.world_position);
+    if (camera.orthographic != 0u) {
+        v = normalize(vec3<f32>(camera.view[0].z, camera.view[1].z, camera.view[2].z));
+    }
+
+    let diffuse = max(dot(n, l), 0.0);
+    let h = normalize(l + v);
+    // no highlight on the faces turned away from the light
+    let specular = select(0.0, pow(max(dot(n, h), 0.0), object.shininess) * object.specular, diffuse > 0.0);
+
+    let col = object.color.rgb * (light.ambient + light.color * diffuse) + light.color * specular;
+    return vec4<f32>(col, 1.0);
+}
@@ -0,0 +1,70 @@
+// Downsample one mip level into the next one, with a box filter: 2 texels per axis for even sizes, 3 weighted texels for odd ones.
+// Weights must match box_filter_weights in mipmap.rs.
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    // single triangle covering the whole target
+    var out: VertexOutput;
+    let x = f32(i32(in_vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(in_vertex_index & 2u) * 2 - 1);
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    return out;
+}
+
+
+// Fragment shader
+
+// previous (larger) mip level
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+fn load_clamped(p: vec2<i32>, max_coord: vec2<i32>) -> vec4<f32> {
+    return textureLoad(source, min(p, max_coord), 0);
+}
+
+// weights of the source texels 2i, 2i + 1 and 2i + 2 for the destination texel i
+fn box_filter_weights(source_size: u32, i: u32) -> vec3<f32> {
+    if (source_size == 1u) {
+        return vec3<f32>(1.0, 0.0, 0.0);
+    }
+
+    if ((source_size & 1u) == 0u) {
+        return vec3<f32>(0.5, 0.5, 0.0);
+    }
+
+    // odd size 2n + 1 to n texels: each destination texel covers 2 + 1 / n source texels
+    let n = f32(source_size / 2u);
+    return vec3<f32>(n - f32(i), n, f32(i) + 1.0) / f32(source_size);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let source_size = textureDimensions(source);
+    let max_coord = vec2<i32>(source_size) - vec2<i32>(1, 1);
+    let texel = vec2<u32>(in.clip_position.xy);
+    let base = vec2<i32>(texel * 2u);
+
+    let weights_x = box_filter_weights(u32(source_size.x), texel.x);
+    let weights_y = box_filter_weights(u32(source_size.y), texel.y);
+
+    // loads are used instead of a sampler, so that non filterable float formats are supported too
+    var sum = vec4<f32>(0.0);
+    for (var y = 0; y < 3; y++) {
+        for (var x = 0; x < 3; x++) {
+            let weight = weights_x[x] * weights_y[y];
+            if (weight > 0.0) {
+                sum += load_clamped(base + vec2<i32>(x, y), max_coord) * weight;
+            }
+        }
+    }
+
+    return sum;
+}
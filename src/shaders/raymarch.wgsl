@@ -0,0 +1,229 @@
+// 3D SDF scene rendered by sphere tracing, with soft shadows and ambient occlusion.
+//
+// The scene is hard-coded in `map` on purpose, instead of being read from an `SdfScene`:
+// - `SdfScene` only describes 2D shapes (2D primitives and transforms), it has no 3D primitives to encode.
+// - this is the benchmark scene of the interlaced renderer: `map` is evaluated hundreds of times per pixel
+//   (tracing, normals, shadows and occlusion), a fixed scene gets inlined by the shader compiler and keeps
+//   the same cost from run to run, where a loop over a storage buffer would depend on the loaded file.
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) vert_pos: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // single triangle covering the whole target
+    let x = f32(i32(in_vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(in_vertex_index & 2u) * 2 - 1);
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.vert_pos = out.clip_position.xyz;
+    return out;
+}
+
+// Fragment shader
+
+// same globals as shader.wgsl
+
+struct Input {
+    cursor: vec2<f32>,
+    cursor_pixel: vec2<f32>,
+    scroll: vec2<f32>,
+    buttons: u32,
+    modifiers: u32,
+};
+
+struct GlobalUniform {
+    input: Input,
+    frame_number_low: u32,
+    frame_number_high: u32,
+    viewport_width: u32,
+    viewport_height: u32,
+    jitter: vec2<f32>,
+    time: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> global: GlobalUniform;
+
+struct Camera3d {
+    view: mat4x4<f32>,
+    // includes the jitter
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    position: vec3<f32>,
+    orthographic: u32,
+};
+
+@group(0) @binding(2)
+var<uniform> camera: Camera3d;
+
+const MAX_STEPS = 128;
+const HIT_DISTANCE = 0.0005;
+const SUN_DIRECTION = vec3<f32>(0.6, 0.7, 0.4);
+
+fn sd_sphere(p: vec3<f32>, r: f32) -> f32 {
+    return length(p) - r;
+}
+
+fn sd_round_box(p: vec3<f32>, half_size: vec3<f32>, r: f32) -> f32 {
+    let q = abs(p) - half_size + r;
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0) - r;
+}
+
+fn sd_torus(p: vec3<f32>, radii: vec2<f32>) -> f32 {
+    let q = vec2<f32>(length(p.xz) - radii.x, p.y);
+    return length(q) - radii.y;
+}
+
+fn smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    let h = clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
+    return mix(b, a, h) - k * h * (1.0 - h);
+}
+
+// distance to the scene (x) and material (y): 0 ground, 1 blob, 2 torus
+fn map(p: vec3<f32>) -> vec2<f32> {
+    var result = vec2<f32>(p.y + 1.0, 0.0);
+
+    // sphere bouncing into a rounded box
+    let sphere = sd_sphere(p - vec3<f32>(0.0, 0.1 + 0.35 * sin(global.time * 1.5), 0.0), 0.55);
+    let blob = smooth_union(sphere, sd_round_box(p - vec3<f32>(0.0, -0.6, 0.0), vec3<f32>(0.7, 0.4, 0.7), 0.1), 0.3);
+    if (blob < result.x) {
+        result = vec2<f32>(blob, 1.0);
+    }
+
+    // spinning torus
+    let angle = global.time * 0.7;
+    let c = cos(angle);
+    let s = sin(angle);
+    let q = p - vec3<f32>(1.8, -0.2, -0.5);
+    let torus = sd_torus(vec3<f32>(q.x, c * q.y - s * q.z, s * q.y + c * q.z), vec2<f32>(0.5, 0.15));
+    if (torus < result.x) {
+        result = vec2<f32>(torus, 2.0);
+    }
+
+    return result;
+}
+
+// distance along the ray and material, material -1 when nothing is hit
+fn sphere_trace(origin: vec3<f32>, direction: vec3<f32>, max_distance: f32) -> vec2<f32> {
+    var t = 0.0;
+
+    for (var i = 0; i < MAX_STEPS; i++) {
+        let h = map(origin + direction * t);
+        if (h.x < HIT_DISTANCE * max(t, 1.0)) {
+            return vec2<f32>(t, h.y);
+        }
+        t += h.x;
+        if (t > max_distance) {
+            break;
+        }
+    }
+
+    return vec2<f32>(max_distance, -1.0);
+}
+
+// gradient of the distance from 4 samples (tetrahedron technique)
+fn normal(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2<f32>(1.0, -1.0) * 0.0005;
+    return normalize(
+        e.xyy * map(p + e.xyy).x +
+        e.yyx * map(p + e.yyx).x +
+        e.yxy * map(p + e.yxy).x +
+        e.xxx * map(p + e.xxx).x
+    );
+}
+
+// penumbra estimated from the closest miss along the ray to the light
+fn soft_shadow(origin: vec3<f32>, direction: vec3<f32>, k: f32) -> f32 {
+    var result = 1.0;
+    var t = 0.02;
+    var previous = 1e10;
+
+    for (var i = 0; i < 64; i++) {
+        let h = map(origin + direction * t).x;
+        if (h < 0.0001) {
+            return 0.0;
+        }
+        let y = h * h / (2.0 * previous);
+        let d = sqrt(h * h - y * y);
+        result = min(result, k * d / max(t - y, 0.0001));
+        previous = h;
+        t += h;
+        if (t > 10.0) {
+            break;
+        }
+    }
+
+    return clamp(result, 0.0, 1.0);
+}
+
+// occlusion from the distances of a few points along the normal
+fn ambient_occlusion(p: vec3<f32>, n: vec3<f32>) -> f32 {
+    var occlusion = 0.0;
+    var weight = 1.0;
+
+    for (var i = 1; i <= 5; i++) {
+        let h = 0.02 + 0.12 * f32(i);
+        occlusion += (h - map(p + n * h).x) * weight;
+        weight *= 0.85;
+    }
+
+    return clamp(1.0 - 3.0 * occlusion, 0.0, 1.0);
+}
+
+fn material_color(material: f32, p: vec3<f32>) -> vec3<f32> {
+    if (material < 0.5) {
+        // checkerboard ground
+        let checker = (i32(floor(p.x)) + i32(floor(p.z))) & 1;
+        return mix(vec3<f32>(0.35), vec3<f32>(0.55), f32(checker));
+    }
+    if (material < 1.5) {
+        return vec3<f32>(0.65, 0.85, 1.0);
+    }
+    return vec3<f32>(0.9, 0.6, 0.3);
+}
+
+fn sky(direction: vec3<f32>) -> vec3<f32> {
+    return mix(vec3<f32>(0.7, 0.75, 0.8), vec3<f32>(0.3, 0.5, 0.85), clamp(direction.y, 0.0, 1.0));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the inverse projection carries the jitter: no offset to add here
+    let near = camera.inverse_view_projection * vec4<f32>(in.vert_pos.xy, 0.0, 1.0);
+    let far = camera.inverse_view_projection * vec4<f32>(in.vert_pos.xy, 1.0, 1.0);
+    let origin = near.xyz / near.w;
+    let ray = far.xyz / far.w - origin;
+    let direction = normalize(ray);
+    let max_distance = length(ray);
+
+    let hit = sphere_trace(origin, direction, max_distance);
+    if (hit.y < 0.0) {
+        return vec4<f32>(sky(direction), 1.0);
+    }
+
+    let p = origin + direction * hit.x;
+    let n = normal(p);
+    let sun = normalize(SUN_DIRECTION);
+    let albedo = material_color(hit.y, p);
+
+    let shadow = soft_shadow(p + n * 0.002, sun, 8.0);
+    let occlusion = ambient_occlusion(p, n);
+    let diffuse = max(dot(n, sun), 0.0) * shadow;
+    let specular = pow(max(dot(n, normalize(sun - direction)), 0.0), 32.0) * shadow;
+    let ambient = (0.5 + 0.5 * n.y) * occlusion;
+
+    var col = albedo * (vec3<f32>(1.0, 0.95, 0.85) * diffuse + vec3<f32>(0.25, 0.3, 0.4) * ambient) + vec3<f32>(0.3) * specular;
+
+    // fade into the sky with the distance
+    col = mix(col, sky(direction), 1.0 - exp(-0.002 * hit.x * hit.x));
+
+    return vec4<f32>(col, 1.0);
+}
@@ -0,0 +1,105 @@
+// Contrast-adaptive sharpening, derived from the RCAS pass of AMD FidelityFX Super Resolution 1.0 (MIT license).
+
+fn norm_to_coord(f: f32) -> f32 {
+    // scale [0; 1] to [-1; 1]
+    return (f * 2.0) - 1.0;
+}
+
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) vert_pos: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let x = norm_to_coord(f32(in_vertex_index & 1u));
+    let y = norm_to_coord(f32(in_vertex_index & 2u));
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.vert_pos = out.clip_position.xyz;
+    return out;
+}
+
+
+// Fragment shader
+
+struct GlobalUniform {
+    width: u32,
+    height: u32,
+    field_width: u32,
+    field_height: u32,
+    frame_number: u32,
+    crt_mask: u32,
+    crt_scanline_intensity: f32,
+    crt_persistence: f32,
+    crt_mask_intensity: f32,
+    crt_curvature: f32,
+    rcas_sharpness: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> global: GlobalUniform;
+
+// upscaled frame, at the output resolution
+@group(0) @binding(1)
+var input_texture: texture_2d<f32>;
+
+// Maximum sharpening lobe, above it the filter starts to create artifacts.
+const RCAS_LIMIT: f32 = 0.1875;
+
+fn load(x: i32, y: i32) -> vec3<f32> {
+    let coord = vec2<i32>(clamp(x, 0, i32(global.width) - 1), clamp(y, 0, i32(global.height) - 1));
+    return textureLoad(input_texture, coord, 0).rgb;
+}
+
+// Approximate luma (times 2).
+fn luma(c: vec3<f32>) -> f32 {
+    return c.b * 0.5 + (c.r * 0.5 + c.g);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let x = i32(in.clip_position.x);
+    let y = i32(in.clip_position.y);
+
+    //    b
+    //  d e f
+    //    h
+    let b = load(x, y - 1);
+    let d = load(x - 1, y);
+    let e = load(x, y);
+    let f = load(x + 1, y);
+    let h = load(x, y + 1);
+
+    let bl = luma(b);
+    let dl = luma(d);
+    let el = luma(e);
+    let fl = luma(f);
+    let hl = luma(h);
+
+    // noise detection, sharpening is reduced on isolated pixels
+    let range = max(max(max(bl, dl), max(fl, hl)), el) - min(min(min(bl, dl), min(fl, hl)), el);
+    var nz = 0.0;
+    if (range > 0.0) {
+        nz = clamp(abs(0.25 * (bl + dl + fl + hl) - el) / range, 0.0, 1.0);
+    }
+    nz = -0.5 * nz + 1.0;
+
+    // min and max of the ring, used to limit the lobe so that the result does not clip
+    let mn4 = min(min(b, d), min(f, h));
+    let mx4 = max(max(b, d), max(f, h));
+    let hit_min = min(mn4, e) / max(4.0 * mx4, vec3<f32>(1.0 / 65536.0));
+    let hit_max = (1.0 - max(mx4, e)) / min(4.0 * mn4 - 4.0, vec3<f32>(-1.0 / 65536.0));
+    let lobe_rgb = max(-hit_min, hit_max);
+    var lobe = max(-RCAS_LIMIT, min(max(max(lobe_rgb.r, lobe_rgb.g), lobe_rgb.b), 0.0)) * global.rcas_sharpness;
+    lobe *= nz;
+
+    let col = (lobe * (b + d + f + h) + e) / (4.0 * lobe + 1.0);
+
+    return vec4<f32>(col, 1.0);
+}
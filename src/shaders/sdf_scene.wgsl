@@ -0,0 +1,240 @@
+// Generic 2D SDF scene: evaluates the shapes of an SdfScene in order, each one combined with the result of the previous ones.
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) vert_pos: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // single triangle covering the whole target
+    let x = f32(i32(in_vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(in_vertex_index & 2u) * 2 - 1);
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.vert_pos = out.clip_position.xyz;
+    return out;
+}
+
+// Fragment shader
+
+// same globals as shader.wgsl
+
+struct Input {
+    cursor: vec2<f32>,
+    cursor_pixel: vec2<f32>,
+    scroll: vec2<f32>,
+    buttons: u32,
+    modifiers: u32,
+};
+
+struct GlobalUniform {
+    input: Input,
+    frame_number_low: u32,
+    frame_number_high: u32,
+    viewport_width: u32,
+    viewport_height: u32,
+    jitter: vec2<f32>,
+    time: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> global: GlobalUniform;
+
+struct Camera2d {
+    clip_to_world: mat3x3<f32>,
+    world_to_clip: mat3x3<f32>,
+    position: vec2<f32>,
+    zoom: f32,
+    rotation: f32,
+    pixel_size: f32,
+};
+
+@group(0) @binding(1)
+var<uniform> camera: Camera2d;
+
+fn clip_to_world(p: vec2<f32>) -> vec2<f32> {
+    return (camera.clip_to_world * vec3<f32>(p, 1.0)).xy;
+}
+
+// scene (see SdfSceneBuffers)
+
+struct Scene {
+    background: vec4<f32>,
+    shape_count: u32,
+    show_distance: u32,
+};
+
+struct Shape {
+    // box: half size, segment: end points
+    params: vec4<f32>,
+    color: vec4<f32>,
+    translation: vec2<f32>,
+    // cosine and sine of the rotation
+    rotation: vec2<f32>,
+    scale: f32,
+    // circle radius, rounding of boxes or half thickness of segments
+    radius: f32,
+    smoothness: f32,
+    kind: u32,
+    operation: u32,
+    first_point: u32,
+    point_count: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> scene: Scene;
+
+@group(1) @binding(1)
+var<storage, read> shapes: array<Shape>;
+
+@group(1) @binding(2)
+var<storage, read> points: array<vec2<f32>>;
+
+fn sd_box(p: vec2<f32>, half_size: vec2<f32>) -> f32 {
+    let d = abs(p) - half_size;
+    return length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0);
+}
+
+fn sd_segment(p: vec2<f32>, a: vec2<f32>, b: vec2<f32>) -> f32 {
+    let pa = p - a;
+    let ba = b - a;
+    let h = clamp(dot(pa, ba) / dot(ba, ba), 0.0, 1.0);
+    return length(pa - ba * h);
+}
+
+fn sd_polygon(p: vec2<f32>, first: u32, count: u32) -> f32 {
+    var d = dot(p - points[first], p - points[first]);
+    var s = 1.0;
+    var j = count - 1u;
+
+    for (var i = 0u; i < count; i++) {
+        let vi = points[first + i];
+        let vj = points[first + j];
+        let e = vj - vi;
+        let w = p - vi;
+        let b = w - e * clamp(dot(w, e) / dot(e, e), 0.0, 1.0);
+        d = min(d, dot(b, b));
+
+        // winding number crossings
+        let c = vec3<bool>(p.y >= vi.y, p.y < vj.y, e.x * w.y > e.y * w.x);
+        if (all(c) || all(!c)) {
+            s = -s;
+        }
+
+        j = i;
+    }
+
+    return s * sqrt(d);
+}
+
+fn shape_distance(shape: Shape, world: vec2<f32>) -> f32 {
+    // world to local: inverse translation, rotation and scale
+    let t = world - shape.translation;
+    let p = vec2<f32>(shape.rotation.x * t.x + shape.rotation.y * t.y, -shape.rotation.y * t.x + shape.rotation.x * t.y) / shape.scale;
+
+    var d = 0.0;
+    switch shape.kind {
+        // circle
+        case 0u: {
+            d = length(p) - shape.radius;
+        }
+        // (rounded) box
+        case 1u: {
+            d = sd_box(p, shape.params.xy - vec2<f32>(shape.radius)) - shape.radius;
+        }
+        // segment
+        case 2u: {
+            d = sd_segment(p, shape.params.xy, shape.params.zw) - shape.radius;
+        }
+        // polygon
+        default: {
+            d = sd_polygon(p, shape.first_point, shape.point_count);
+        }
+    }
+
+    return d * shape.scale;
+}
+
+struct Sample {
+    distance: f32,
+    color: vec3<f32>,
+};
+
+// subtractions and intersections keep the colors of the previous shapes
+fn evaluate(world: vec2<f32>) -> Sample {
+    // nothing yet: infinitely far
+    var result = Sample(1e9, scene.background.rgb);
+
+    for (var i = 0u; i < scene.shape_count; i++) {
+        let shape = shapes[i];
+        let d = shape_distance(shape, world);
+        let k = shape.smoothness;
+
+        switch shape.operation {
+            // union
+            case 0u: {
+                if (d < result.distance) {
+                    result = Sample(d, shape.color.rgb);
+                }
+            }
+            // subtraction
+            case 1u: {
+                result.distance = max(result.distance, -d);
+            }
+            // intersection
+            case 2u: {
+                result.distance = max(result.distance, d);
+            }
+            // smooth union
+            case 3u: {
+                let h = clamp(0.5 + 0.5 * (d - result.distance) / k, 0.0, 1.0);
+                result.distance = mix(d, result.distance, h) - k * h * (1.0 - h);
+                result.color = mix(shape.color.rgb, result.color, h);
+            }
+            // smooth subtraction
+            case 4u: {
+                let h = clamp(0.5 - 0.5 * (result.distance + d) / k, 0.0, 1.0);
+                result.distance = mix(result.distance, -d, h) + k * h * (1.0 - h);
+            }
+            // smooth intersection
+            default: {
+                let h = clamp(0.5 - 0.5 * (d - result.distance) / k, 0.0, 1.0);
+                result.distance = mix(d, result.distance, h) + k * h * (1.0 - h);
+            }
+        }
+    }
+
+    return result;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let p = clip_to_world(in.vert_pos.xy + global.jitter);
+    let sample = evaluate(p);
+    let pixel = camera.pixel_size;
+
+    var col = scene.background.rgb;
+
+    if (scene.show_distance != 0u && sample.distance > 0.0) {
+        col *= 1.0 - exp(-6.0 * sample.distance);
+        col *= 0.8 + 0.2 * cos(150.0 * sample.distance);
+    }
+
+    // coverage of the pixel by the shapes
+    col = mix(col, sample.color, 1.0 - smoothstep(-0.5 * pixel, 0.5 * pixel, sample.distance));
+
+    // distance under the cursor while the left button is held
+    if ((global.input.buttons & 1u) != 0u) {
+        let m = clip_to_world(global.input.cursor);
+        let d = abs(evaluate(m).distance);
+        col = mix(col, vec3<f32>(1.0, 1.0, 0.0), 1.0 - smoothstep(0.0, pixel, abs(length(p - m) - d) - pixel));
+        col = mix(col, vec3<f32>(1.0, 1.0, 0.0), 1.0 - smoothstep(0.0, pixel, length(p - m) - 3.0 * pixel));
+    }
+
+    return vec4<f32>(col, 1.0);
+}
@@ -19,18 +19,52 @@ fn vs_main(
 
 // Fragment shader
 
+// see ShaderInput
+struct Input {
+    // cursor in normalized device coordinates (y up)
+    cursor: vec2<f32>,
+    cursor_pixel: vec2<f32>,
+    // scroll since the last frame, in lines
+    scroll: vec2<f32>,
+    // bit 0 left, bit 1 right, bit 2 middle
+    buttons: u32,
+    // bit 0 shift, bit 1 ctrl, bit 2 alt, bit 3 logo
+    modifiers: u32,
+};
+
 struct GlobalUniform {
-    mouse_pos: vec3<f32>,
-    padding1: u32,
+    input: Input,
     frame_number_low: u32,
     frame_number_high: u32,
     viewport_width: u32,
     viewport_height: u32,
+    // sub-pixel offset of the field in clip space units: half row offset of the field and temporal anti-aliasing jitter
+    jitter: vec2<f32>,
+    // scene time in seconds (see TimeControl)
+    time: f32,
 };
 
 @group(0) @binding(0)
 var<uniform> global: GlobalUniform;
 
+// see Camera2dUniform
+struct Camera2d {
+    clip_to_world: mat3x3<f32>,
+    world_to_clip: mat3x3<f32>,
+    position: vec2<f32>,
+    zoom: f32,
+    rotation: f32,
+    // size of a pixel in world units
+    pixel_size: f32,
+};
+
+@group(0) @binding(1)
+var<uniform> camera: Camera2d;
+
+fn clip_to_world(p: vec2<f32>) -> vec2<f32> {
+    return (camera.clip_to_world * vec3<f32>(p, 1.0)).xy;
+}
+
 fn sd_circle(p: vec2<f32>, r: f32) -> f32 {
     return length(p) - r;
 }
@@ -44,8 +78,8 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         a = 2.0;
     }
 
-    let p = in.vert_pos.xy * a;
-    let m = global.mouse_pos.xy * a;
+    let p = clip_to_world(in.vert_pos.xy + global.jitter) * a;
+    let m = clip_to_world(global.input.cursor) * a;
 
     var d = sd_circle(p, 0.5);
 
@@ -59,7 +93,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     col *= 0.8 + 0.2 * cos(150.0 * d);
     col = mix(col, vec3<f32>(1.0), 1.0 - smoothstep(0.0, 0.01, abs(d)));
 
-    if global.mouse_pos.z > 1.0 {
+    if (global.input.buttons & 1u) != 0u {
         d = sd_circle(m, 0.5);
         col = mix(col, vec3<f32>(1.0, 1.0, 0.0), 1.0 - smoothstep(0.0, 0.005, abs(length(p - m) - abs(d)) - 0.0025));
         col = mix(col, vec3<f32>(1.0, 1.0, 0.0), 1.0 - smoothstep(0.0, 0.005, length(p - m) - 0.015));
@@ -0,0 +1,353 @@
+use std::fmt;
+
+use crate::utils::select_prefered_presentmode;
+
+/// Present modes which can be switched between at runtime, in cycling order.
+pub const SWITCHABLE_PRESENT_MODES: [wgpu::PresentMode; 4] = [
+    wgpu::PresentMode::Fifo,
+    wgpu::PresentMode::FifoRelaxed,
+    wgpu::PresentMode::Mailbox,
+    wgpu::PresentMode::Immediate,
+];
+
+/// Next mode of `SWITCHABLE_PRESENT_MODES` after the current one which is supported, cycling back to the first one.
+pub fn next_present_mode(supported: &[wgpu::PresentMode], current: wgpu::PresentMode) -> wgpu::PresentMode {
+    let start = SWITCHABLE_PRESENT_MODES.iter().position(|&mode| mode == current).unwrap_or(0);
+
+    (1..=SWITCHABLE_PRESENT_MODES.len())
+        .map(|offset| SWITCHABLE_PRESENT_MODES[(start + offset) % SWITCHABLE_PRESENT_MODES.len()])
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(current)
+}
+
+/// Color space of a surface, deduced from its format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Colors written by shaders are encoded to sRGB by the hardware.
+    Srgb,
+    /// Colors written by shaders are stored as is.
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn of(format: wgpu::TextureFormat) -> Self {
+        if format.describe().srgb { ColorSpace::Srgb } else { ColorSpace::Linear }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SurfaceNegotiationError {
+    /// The surface reports no format or no present mode, it can not be used with this adapter.
+    IncompatibleSurface,
+}
+
+impl fmt::Display for SurfaceNegotiationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SurfaceNegotiationError::IncompatibleSurface => write!(f, "the surface is incompatible with the adapter"),
+        }
+    }
+}
+
+impl std::error::Error for SurfaceNegotiationError {}
+
+/// Configuration chosen by a `SurfaceNegotiator`.
+#[derive(Clone, Debug)]
+pub struct NegotiatedSurface {
+    pub config: wgpu::SurfaceConfiguration,
+    pub color_space: ColorSpace,
+    /// Explanation of every choice which is not the first preference, in the order format, color space, present mode, alpha mode.
+    pub fallbacks: Vec<String>,
+}
+
+/// Chooses a surface configuration from ordered preferences and the capabilities of the surface.
+///
+/// Empty preference lists mean "no preference": the first supported value is used, without reporting a fallback.
+///
+/// The frame latency is not negotiated: wgpu does not let us configure it on the surface.
+#[derive(Clone, Debug)]
+pub struct SurfaceNegotiator {
+    formats: Vec<wgpu::TextureFormat>,
+    color_spaces: Vec<ColorSpace>,
+    present_modes: Vec<wgpu::PresentMode>,
+    alpha_modes: Vec<wgpu::CompositeAlphaMode>,
+    usage: wgpu::TextureUsages,
+}
+
+impl Default for SurfaceNegotiator {
+    /// sRGB surface with low latency presentation if available, as expected by the shaders of this crate.
+    fn default() -> Self {
+        Self {
+            formats: vec![],
+            color_spaces: vec![ColorSpace::Srgb],
+            present_modes: vec![wgpu::PresentMode::Mailbox, wgpu::PresentMode::Fifo],
+            alpha_modes: vec![],
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        }
+    }
+}
+
+impl SurfaceNegotiator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_formats(mut self, formats: Vec<wgpu::TextureFormat>) -> Self {
+        self.formats = formats;
+        self
+    }
+
+    pub fn with_color_spaces(mut self, color_spaces: Vec<ColorSpace>) -> Self {
+        self.color_spaces = color_spaces;
+        self
+    }
+
+    pub fn with_present_modes(mut self, present_modes: Vec<wgpu::PresentMode>) -> Self {
+        self.present_modes = present_modes;
+        self
+    }
+
+    pub fn with_alpha_modes(mut self, alpha_modes: Vec<wgpu::CompositeAlphaMode>) -> Self {
+        self.alpha_modes = alpha_modes;
+        self
+    }
+
+    /// Usage of the surface textures, RENDER_ATTACHMENT by default.
+    pub fn with_usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Choose the configuration for a surface of the given size. Only depends on the given capabilities, not on a real surface.
+    pub fn negotiate(&self, capabilities: &wgpu::SurfaceCapabilities, width: u32, height: u32) -> Result<NegotiatedSurface, SurfaceNegotiationError> {
+        if capabilities.formats.is_empty() || capabilities.present_modes.is_empty() {
+            return Err(SurfaceNegotiationError::IncompatibleSurface);
+        }
+
+        let mut fallbacks = vec![];
+
+        let (format, color_space) = self.negotiate_format(&capabilities.formats, &mut fallbacks);
+        let present_mode = self.negotiate_present_mode(&capabilities.present_modes, &mut fallbacks);
+        let alpha_mode = self.negotiate_alpha_mode(&capabilities.alpha_modes, &mut fallbacks);
+
+        Ok(NegotiatedSurface {
+            config: wgpu::SurfaceConfiguration {
+                usage: self.usage,
+                format,
+                width,
+                height,
+                present_mode,
+                alpha_mode,
+                view_formats: vec![],
+            },
+            color_space,
+            fallbacks,
+        })
+    }
+
+    fn negotiate_format(&self, supported: &[wgpu::TextureFormat], fallbacks: &mut Vec<String>) -> (wgpu::TextureFormat, ColorSpace) {
+        // preferred formats first (in order), then the other supported ones in the order given by the surface
+        let mut candidates: Vec<wgpu::TextureFormat> = self.formats.iter().copied().filter(|format| supported.contains(format)).collect();
+        candidates.extend(supported.iter().copied().filter(|format| !self.formats.contains(format)));
+
+        // the color space preference wins over the format preference, as shaders depend on it
+        let chosen = self.color_spaces.iter()
+            .find_map(|&color_space| candidates.iter().copied().find(|&format| ColorSpace::of(format) == color_space))
+            .unwrap_or(candidates[0]);
+        let color_space = ColorSpace::of(chosen);
+
+        if let Some(&preferred) = self.formats.first() {
+            if preferred != chosen {
+                if supported.contains(&preferred) {
+                    fallbacks.push(format!("format: {:?} is supported but not in a preferred color space, using {:?}", preferred, chosen));
+                } else {
+                    fallbacks.push(format!("format: {:?} is not supported, using {:?}", preferred, chosen));
+                }
+            }
+        }
+
+        if let Some(&preferred) = self.color_spaces.first() {
+            if preferred != color_space {
+                fallbacks.push(format!("color space: no supported format is {:?}, using {:?} ({:?})", preferred, color_space, chosen));
+            }
+        }
+
+        (chosen, color_space)
+    }
+
+    fn negotiate_present_mode(&self, supported: &[wgpu::PresentMode], fallbacks: &mut Vec<String>) -> wgpu::PresentMode {
+        let chosen = select_prefered_presentmode(supported, &self.present_modes)
+            // Fifo is the only mode every surface supports
+            .unwrap_or(if supported.contains(&wgpu::PresentMode::Fifo) { wgpu::PresentMode::Fifo } else { supported[0] });
+
+        if let Some(&preferred) = self.present_modes.first() {
+            if preferred != chosen {
+                fallbacks.push(format!("present mode: {:?} is not supported, using {:?}", preferred, chosen));
+            }
+        }
+
+        chosen
+    }
+
+    fn negotiate_alpha_mode(&self, supported: &[wgpu::CompositeAlphaMode], fallbacks: &mut Vec<String>) -> wgpu::CompositeAlphaMode {
+        let chosen = self.alpha_modes.iter().copied()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(supported.first().copied().unwrap_or(wgpu::CompositeAlphaMode::Auto));
+
+        if let Some(&preferred) = self.alpha_modes.first() {
+            if preferred != chosen {
+                fallbacks.push(format!("alpha mode: {:?} is not supported, using {:?}", preferred, chosen));
+            }
+        }
+
+        chosen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wgpu::{CompositeAlphaMode, PresentMode, TextureFormat};
+
+    fn capabilities(formats: Vec<TextureFormat>, present_modes: Vec<PresentMode>, alpha_modes: Vec<CompositeAlphaMode>) -> wgpu::SurfaceCapabilities {
+        wgpu::SurfaceCapabilities { formats, present_modes, alpha_modes }
+    }
+
+    fn typical_capabilities() -> wgpu::SurfaceCapabilities {
+        capabilities(
+            vec![TextureFormat::Bgra8Unorm, TextureFormat::Bgra8UnormSrgb],
+            vec![PresentMode::Fifo, PresentMode::Immediate],
+            vec![CompositeAlphaMode::Opaque, CompositeAlphaMode::PreMultiplied],
+        )
+    }
+
+    fn no_preferences() -> SurfaceNegotiator {
+        SurfaceNegotiator::new().with_color_spaces(vec![]).with_present_modes(vec![])
+    }
+
+    #[test]
+    fn first_supported_values_without_preferences() {
+        let negotiated = no_preferences().negotiate(&typical_capabilities(), 640, 480).unwrap();
+
+        assert_eq!(negotiated.config.format, TextureFormat::Bgra8Unorm);
+        assert_eq!(negotiated.color_space, ColorSpace::Linear);
+        assert_eq!(negotiated.config.present_mode, PresentMode::Fifo);
+        assert_eq!(negotiated.config.alpha_mode, CompositeAlphaMode::Opaque);
+        assert_eq!((negotiated.config.width, negotiated.config.height), (640, 480));
+        assert_eq!(negotiated.config.usage, wgpu::TextureUsages::RENDER_ATTACHMENT);
+        assert!(negotiated.fallbacks.is_empty());
+    }
+
+    #[test]
+    fn preferences_are_used_when_supported() {
+        let negotiated = SurfaceNegotiator::new()
+            .with_formats(vec![TextureFormat::Bgra8UnormSrgb])
+            .with_present_modes(vec![PresentMode::Immediate])
+            .with_alpha_modes(vec![CompositeAlphaMode::PreMultiplied])
+            .negotiate(&typical_capabilities(), 1, 1)
+            .unwrap();
+
+        assert_eq!(negotiated.config.format, TextureFormat::Bgra8UnormSrgb);
+        assert_eq!(negotiated.color_space, ColorSpace::Srgb);
+        assert_eq!(negotiated.config.present_mode, PresentMode::Immediate);
+        assert_eq!(negotiated.config.alpha_mode, CompositeAlphaMode::PreMultiplied);
+        assert!(negotiated.fallbacks.is_empty());
+    }
+
+    #[test]
+    fn unsupported_format_falls_back_to_the_color_space() {
+        let negotiated = SurfaceNegotiator::new()
+            .with_formats(vec![TextureFormat::Rgba8UnormSrgb])
+            .with_present_modes(vec![])
+            .negotiate(&typical_capabilities(), 1, 1)
+            .unwrap();
+
+        assert_eq!(negotiated.config.format, TextureFormat::Bgra8UnormSrgb);
+        assert_eq!(negotiated.fallbacks, vec!["format: Rgba8UnormSrgb is not supported, using Bgra8UnormSrgb".to_string()]);
+    }
+
+    #[test]
+    fn color_space_wins_over_the_format() {
+        let negotiated = SurfaceNegotiator::new()
+            .with_formats(vec![TextureFormat::Bgra8Unorm])
+            .with_present_modes(vec![])
+            .negotiate(&typical_capabilities(), 1, 1)
+            .unwrap();
+
+        assert_eq!(negotiated.config.format, TextureFormat::Bgra8UnormSrgb);
+        assert_eq!(negotiated.fallbacks, vec!["format: Bgra8Unorm is supported but not in a preferred color space, using Bgra8UnormSrgb".to_string()]);
+    }
+
+    #[test]
+    fn color_space_fallback() {
+        let capabilities = capabilities(vec![TextureFormat::Rgba16Float], vec![PresentMode::Fifo], vec![CompositeAlphaMode::Opaque]);
+        let negotiated = SurfaceNegotiator::new().with_present_modes(vec![]).negotiate(&capabilities, 1, 1).unwrap();
+
+        assert_eq!(negotiated.config.format, TextureFormat::Rgba16Float);
+        assert_eq!(negotiated.color_space, ColorSpace::Linear);
+        assert_eq!(negotiated.fallbacks, vec!["color space: no supported format is Srgb, using Linear (Rgba16Float)".to_string()]);
+    }
+
+    #[test]
+    fn present_mode_fallbacks() {
+        // the next preference which is supported
+        let negotiated = no_preferences()
+            .with_present_modes(vec![PresentMode::Mailbox, PresentMode::Immediate])
+            .negotiate(&typical_capabilities(), 1, 1)
+            .unwrap();
+
+        assert_eq!(negotiated.config.present_mode, PresentMode::Immediate);
+        assert_eq!(negotiated.fallbacks, vec!["present mode: Mailbox is not supported, using Immediate".to_string()]);
+
+        // Fifo when no preference is supported, even if it is not the first supported mode
+        let capabilities = capabilities(vec![TextureFormat::Bgra8Unorm], vec![PresentMode::Immediate, PresentMode::Fifo], vec![]);
+        let negotiated = no_preferences().with_present_modes(vec![PresentMode::Mailbox]).negotiate(&capabilities, 1, 1).unwrap();
+
+        assert_eq!(negotiated.config.present_mode, PresentMode::Fifo);
+        assert_eq!(negotiated.fallbacks, vec!["present mode: Mailbox is not supported, using Fifo".to_string()]);
+    }
+
+    #[test]
+    fn alpha_mode_fallbacks() {
+        let negotiated = no_preferences()
+            .with_alpha_modes(vec![CompositeAlphaMode::PostMultiplied])
+            .negotiate(&typical_capabilities(), 1, 1)
+            .unwrap();
+
+        assert_eq!(negotiated.config.alpha_mode, CompositeAlphaMode::Opaque);
+        assert_eq!(negotiated.fallbacks, vec!["alpha mode: PostMultiplied is not supported, using Opaque".to_string()]);
+
+        // Auto when the surface reports no alpha mode
+        let capabilities = capabilities(vec![TextureFormat::Bgra8Unorm], vec![PresentMode::Fifo], vec![]);
+        let negotiated = no_preferences().negotiate(&capabilities, 1, 1).unwrap();
+
+        assert_eq!(negotiated.config.alpha_mode, CompositeAlphaMode::Auto);
+        assert!(negotiated.fallbacks.is_empty());
+    }
+
+    #[test]
+    fn fallbacks_are_reported_in_order() {
+        let negotiated = SurfaceNegotiator::new()
+            .with_formats(vec![TextureFormat::Rgba8Unorm])
+            .with_present_modes(vec![PresentMode::Mailbox])
+            .with_alpha_modes(vec![CompositeAlphaMode::Inherit])
+            .negotiate(&typical_capabilities(), 1, 1)
+            .unwrap();
+
+        let kinds: Vec<&str> = negotiated.fallbacks.iter().map(|fallback| fallback.split(':').next().unwrap()).collect();
+        assert_eq!(kinds, vec!["format", "present mode", "alpha mode"]);
+    }
+
+    #[test]
+    fn incompatible_surface() {
+        let no_format = capabilities(vec![], vec![PresentMode::Fifo], vec![]);
+        let no_present_mode = capabilities(vec![TextureFormat::Bgra8Unorm], vec![], vec![]);
+
+        for capabilities in [no_format, no_present_mode] {
+            let error = SurfaceNegotiator::new().negotiate(&capabilities, 1, 1).unwrap_err();
+            assert_eq!(error, SurfaceNegotiationError::IncompatibleSurface);
+            assert_eq!(error.to_string(), "the surface is incompatible with the adapter");
+        }
+    }
+}
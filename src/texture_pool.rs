@@ -0,0 +1,123 @@
+use crate::utils::texture_descriptor;
+
+/// Keeps released textures around to reuse them instead of allocating new ones, so that render targets can change size often without hitches.
+pub struct TexturePool {
+    free_textures: Vec<wgpu::Texture>,
+    /// Maximum count of free textures kept, the oldest ones are dropped first.
+    capacity: usize,
+}
+
+impl TexturePool {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            free_textures: vec![],
+            capacity,
+        }
+    }
+
+    /// Take a free texture with the given size and usage from the pool, or create a new one (see `create_texture`).
+    pub fn acquire(&mut self, device: &wgpu::Device, label: Option<&str>, width: u32, height: u32, usage: wgpu::TextureUsages) -> wgpu::Texture {
+        self.acquire_with_descriptor(device, &texture_descriptor(label, width, height, wgpu::TextureFormat::Rgba8Unorm, usage))
+    }
+
+    /// Take a free texture matching the descriptor (except for its label) from the pool, or create a new one.
+    pub fn acquire_with_descriptor(&mut self, device: &wgpu::Device, descriptor: &wgpu::TextureDescriptor) -> wgpu::Texture {
+        let position = self.free_textures.iter().position(|texture| {
+            texture.size() == descriptor.size
+                && texture.format() == descriptor.format
+                && texture.usage() == descriptor.usage
+                && texture.sample_count() == descriptor.sample_count
+                && texture.mip_level_count() == descriptor.mip_level_count
+                && texture.dimension() == descriptor.dimension
+        });
+
+        match position {
+            Some(index) => self.free_textures.remove(index),
+            None => device.create_texture(descriptor),
+        }
+    }
+
+    /// Give back a texture which is not used anymore.
+    pub fn release(&mut self, texture: wgpu::Texture) {
+        // a pool without capacity keeps nothing, the texture is simply dropped
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.free_textures.len() >= self.capacity {
+            self.free_textures.remove(0);
+        }
+
+        self.free_textures.push(texture);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::{test_device, read_texture_pixel};
+
+    const USAGE: wgpu::TextureUsages = wgpu::TextureUsages::TEXTURE_BINDING.union(wgpu::TextureUsages::COPY_SRC).union(wgpu::TextureUsages::COPY_DST);
+
+    /// Textures are told apart by a marker in their first texel, new textures are cleared to 0.
+    fn mark(queue: &wgpu::Queue, texture: &wgpu::Texture, marker: u8) {
+        queue.write_texture(texture.as_image_copy(), &[marker, 0, 0, 0], wgpu::ImageDataLayout { offset: 0, bytes_per_row: None, rows_per_image: None }, wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 });
+    }
+
+    fn marker(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> u8 {
+        read_texture_pixel(device, queue, texture, 0, 0)[0]
+    }
+
+    #[test]
+    fn reuses_matching_textures() {
+        let Some((device, queue)) = test_device() else { return };
+        let mut pool = TexturePool::new(4);
+
+        let texture = pool.acquire(&device, None, 64, 32, USAGE);
+        mark(&queue, &texture, 1);
+        pool.release(texture);
+
+        // another size or usage does not match
+        let other_size = pool.acquire(&device, None, 32, 64, USAGE);
+        let other_usage = pool.acquire(&device, None, 64, 32, USAGE | wgpu::TextureUsages::RENDER_ATTACHMENT);
+        assert_eq!(marker(&device, &queue, &other_size), 0);
+        assert_eq!(marker(&device, &queue, &other_usage), 0);
+
+        // the label does not matter
+        let reused = pool.acquire(&device, Some("reused"), 64, 32, USAGE);
+        assert_eq!(marker(&device, &queue, &reused), 1);
+
+        // it is not handed out twice
+        assert_eq!(marker(&device, &queue, &pool.acquire(&device, None, 64, 32, USAGE)), 0);
+    }
+
+    #[test]
+    fn evicts_oldest_over_capacity() {
+        let Some((device, queue)) = test_device() else { return };
+        let mut pool = TexturePool::new(2);
+
+        let textures: Vec<wgpu::Texture> = (0..3).map(|_| pool.acquire(&device, None, 16, 16, USAGE)).collect();
+        for (i, texture) in textures.into_iter().enumerate() {
+            mark(&queue, &texture, i as u8 + 1);
+            pool.release(texture);
+        }
+
+        // the first released texture was dropped
+        let mut markers: Vec<u8> = (0..3).map(|_| marker(&device, &queue, &pool.acquire(&device, None, 16, 16, USAGE))).collect();
+        markers.sort();
+        assert_eq!(markers, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn keeps_nothing_without_capacity() {
+        let Some((device, queue)) = test_device() else { return };
+        let mut pool = TexturePool::new(0);
+
+        let texture = pool.acquire(&device, None, 16, 16, USAGE);
+        mark(&queue, &texture, 1);
+        pool.release(texture);
+
+        assert_eq!(marker(&device, &queue, &pool.acquire(&device, None, 16, 16, USAGE)), 0);
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::{Duration, Instant};
+
+/// Time step used by single steps when there is no fixed time step, one field at 120 fields per second.
+const DEFAULT_STEP: Duration = Duration::from_micros(8_333);
+
+/// Number and scene time of a field to render.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldTime {
+    /// Counts the fields since the start, its parity tells which field is rendered.
+    pub frame_number: u64,
+    /// Scene time in seconds.
+    pub time: f64,
+}
+
+/// Decides when a new field is rendered and at which scene time: pause, single steps, fixed time step.
+///
+/// Fields are only advanced by `advance`, so a sequence of fields can be reproduced exactly with a fixed time step.
+pub struct TimeControl {
+    next_frame_number: u64,
+    time: f64,
+    paused: bool,
+    pending_steps: u32,
+    /// None to follow the real time.
+    fixed_step: Option<Duration>,
+    /// Real time of the last field, to follow the real time.
+    last_advance: Option<Instant>,
+    /// Whether a field was produced at `time`: with a fixed step, the next field is a step later.
+    field_at_time: bool,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeControl {
+    pub fn new() -> Self {
+        Self {
+            next_frame_number: 0,
+            time: 0.0,
+            paused: false,
+            pending_steps: 0,
+            fixed_step: None,
+            last_advance: None,
+            field_at_time: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.pending_steps = 0;
+        // the time spent paused is not part of the real time followed by the scene
+        self.last_advance = None;
+    }
+
+    /// Advance a single field at the next `advance`, while paused.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    pub fn fixed_step(&self) -> Option<Duration> {
+        self.fixed_step
+    }
+
+    /// Advance the scene time by a fixed step per field, None to follow the real time.
+    pub fn set_fixed_step(&mut self, fixed_step: Option<Duration>) {
+        self.fixed_step = fixed_step;
+        self.last_advance = None;
+    }
+
+    /// Scene time in seconds of the last field.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Set the scene time in seconds, the next fields continue from it.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+        self.last_advance = None;
+        self.field_at_time = false;
+    }
+
+    /// Count of fields advanced since the start.
+    pub fn field_count(&self) -> u64 {
+        self.next_frame_number
+    }
+
+    /// The next field to render, or None if paused without pending step.
+    pub fn advance(&mut self, now: Instant) -> Option<FieldTime> {
+        if self.paused {
+            if self.pending_steps == 0 {
+                return None;
+            }
+
+            self.pending_steps -= 1;
+            self.time += self.fixed_step.unwrap_or(DEFAULT_STEP).as_secs_f64();
+        } else {
+            match (self.fixed_step, self.last_advance) {
+                // every field is a step after the previous one, whatever happened between them
+                (Some(fixed_step), _) if self.field_at_time => self.time += fixed_step.as_secs_f64(),
+                (Some(_), _) => {},
+                // the first field after a start, pause or time change is at the current time
+                (None, Some(last_advance)) => self.time += now.duration_since(last_advance).as_secs_f64(),
+                (None, None) => {},
+            }
+
+            self.last_advance = Some(now);
+        }
+
+        self.field_at_time = true;
+
+        let field = FieldTime {
+            frame_number: self.next_frame_number,
+            time: self.time,
+        };
+        self.next_frame_number += 1;
+
+        Some(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    fn advance_times(time_control: &mut TimeControl, start: Instant, count: u32) -> Vec<Option<FieldTime>> {
+        (0..count).map(|i| time_control.advance(start + STEP * i)).collect()
+    }
+
+    #[test]
+    fn real_time() {
+        let mut time_control = TimeControl::new();
+        let start = Instant::now();
+
+        let fields = advance_times(&mut time_control, start, 3);
+
+        // the first field is at the start time, the next ones follow the time between advances
+        let times: Vec<f64> = fields.iter().map(|field| field.unwrap().time).collect();
+        assert_eq!(times[0], 0.0);
+        assert!((times[1] - 0.01).abs() < 1e-9);
+        assert!((times[2] - 0.02).abs() < 1e-9);
+        assert_eq!(fields.iter().map(|field| field.unwrap().frame_number).collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn pause_stops_fields() {
+        let mut time_control = TimeControl::new();
+        time_control.set_fixed_step(Some(STEP));
+        let start = Instant::now();
+        time_control.advance(start);
+
+        time_control.set_paused(true);
+        assert!(advance_times(&mut time_control, start, 3).iter().all(Option::is_none));
+        assert_eq!(time_control.field_count(), 1);
+
+        // the time spent paused is skipped, the next field is a step later
+        time_control.set_paused(false);
+        let field = time_control.advance(start + Duration::from_secs(10)).unwrap();
+        assert_eq!(field.frame_number, 1);
+        assert_eq!(field.time, STEP.as_secs_f64());
+    }
+
+    #[test]
+    fn steps_while_paused() {
+        let mut time_control = TimeControl::new();
+        let start = Instant::now();
+        time_control.set_paused(true);
+
+        time_control.step();
+        time_control.step();
+
+        let fields = advance_times(&mut time_control, start, 3);
+        assert_eq!(fields[0].unwrap().frame_number, 0);
+        assert!((fields[0].unwrap().time - DEFAULT_STEP.as_secs_f64()).abs() < 1e-12);
+        assert_eq!(fields[1].unwrap().frame_number, 1);
+        assert!((fields[1].unwrap().time - 2.0 * DEFAULT_STEP.as_secs_f64()).abs() < 1e-12);
+        assert!(fields[2].is_none());
+    }
+
+    #[test]
+    fn steps_are_ignored_when_running() {
+        let mut time_control = TimeControl::new();
+        time_control.step();
+        time_control.set_paused(true);
+
+        assert!(time_control.advance(Instant::now()).is_none());
+    }
+
+    #[test]
+    fn fixed_step_sequence_is_reproducible() {
+        let sequence = |pause_at: Option<u32>| {
+            let mut time_control = TimeControl::new();
+            time_control.set_fixed_step(Some(Duration::from_millis(20)));
+            let start = Instant::now();
+
+            (0..6).map(|i| {
+                if pause_at == Some(i) {
+                    time_control.set_paused(true);
+                }
+                if time_control.is_paused() {
+                    time_control.step();
+                }
+                // irregular real time, ignored with a fixed step
+                time_control.advance(start + Duration::from_millis((i * i) as u64 * 7)).unwrap()
+            }).collect::<Vec<_>>()
+        };
+
+        let running = sequence(None);
+        let times: Vec<f64> = running.iter().map(|field| field.time).collect();
+        for (i, time) in times.iter().enumerate() {
+            assert!((time - i as f64 * 0.02).abs() < 1e-9, "field {} at {}", i, time);
+        }
+
+        // single steps advance by the fixed step too
+        assert_eq!(sequence(Some(3)), running);
+    }
+
+    #[test]
+    fn fixed_step_pause_resume_is_reproducible() {
+        let sequence = |paused: &[u32], fixed_step_changes: &[u32]| {
+            let mut time_control = TimeControl::new();
+            time_control.set_fixed_step(Some(STEP));
+            let start = Instant::now();
+
+            (0..8).map(|i| {
+                time_control.set_paused(paused.contains(&i));
+                if fixed_step_changes.contains(&i) {
+                    time_control.set_fixed_step(Some(STEP));
+                }
+                if time_control.is_paused() {
+                    time_control.step();
+                }
+                time_control.advance(start + Duration::from_millis(i as u64 * 3)).unwrap()
+            }).collect::<Vec<_>>()
+        };
+
+        let running = sequence(&[], &[]);
+        for (i, field) in running.iter().enumerate() {
+            assert_eq!(field.frame_number, i as u64);
+            assert!((field.time - i as f64 * STEP.as_secs_f64()).abs() < 1e-9, "field {} at {}", i, field.time);
+        }
+
+        // paused and resumed several times, with the same step set again
+        assert_eq!(sequence(&[2, 3, 5], &[]), running);
+        assert_eq!(sequence(&[1], &[4, 6]), running);
+    }
+
+    #[test]
+    fn set_time() {
+        let mut time_control = TimeControl::new();
+        time_control.set_fixed_step(Some(STEP));
+        let start = Instant::now();
+        time_control.advance(start);
+
+        time_control.set_time(5.0);
+        let field = time_control.advance(start + STEP).unwrap();
+        assert_eq!(field.time, 5.0);
+        assert_eq!(field.frame_number, 1);
+    }
+}
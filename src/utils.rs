@@ -1,44 +1,159 @@
-use std::{rc::Rc, cell::RefCell};
-
 /// Select the first desired present mode to be supported (among given lists)
-pub fn select_prefered_presentmode(supported_modes: &Vec<wgpu::PresentMode>, desired_modes: &Vec<wgpu::PresentMode>) -> Option::<wgpu::PresentMode> {
-    let mut desired_modes_iter = desired_modes.into_iter();
-    let mut selected_mode  = Option::<wgpu::PresentMode>::None;
+pub fn select_prefered_presentmode(supported_modes: &[wgpu::PresentMode], desired_modes: &[wgpu::PresentMode]) -> Option::<wgpu::PresentMode> {
+    desired_modes.iter().copied().find(|mode| supported_modes.contains(mode))
+}
 
-    loop {
-        selected_mode = desired_modes_iter.next().copied();
+/// Sample counts which can be used to render to a texture format and resolve it, in increasing order (always starts with 1).
+///
+/// Counts other than 1 and 4 require the device to be created with `Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`.
+pub fn supported_sample_counts(adapter: &wgpu::Adapter, device_features: wgpu::Features, format: wgpu::TextureFormat) -> Vec<u32> {
+    let flags = if device_features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+        adapter.get_texture_format_features(format).flags
+    } else {
+        format.describe().guaranteed_format_features.flags
+    };
 
-        match selected_mode {
-            Some(x) if supported_modes.iter().position(|&mode| mode == x) == None => {
-                selected_mode = None;
-            },
-            _ => {
-                break;
-            }
-        }
-    }
+    [1, 2, 4, 8].into_iter()
+        .filter(|&count| count == 1 || (flags.sample_count_supported(count) && flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)))
+        .collect()
+}
 
-    selected_mode
+/// Select the highest supported sample count which does not exceed the desired one (falls back to 1).
+pub fn select_sample_count(supported_counts: &[u32], desired_count: u32) -> u32 {
+    supported_counts.iter().copied().filter(|&count| count <= desired_count).max().unwrap_or(1)
 }
 
-/// Create a simple 2D texture with Rgba8Unorm format (no multisampling, no mip-levels)
-pub fn create_texture(device: &wgpu::Device, label: Option<&str>, width: u32, height: u32, usage: wgpu::TextureUsages) -> wgpu::Texture {
-    device.create_texture(&wgpu::TextureDescriptor {
-        size: wgpu::Extent3d { width: width, height: height, depth_or_array_layers: 1 },
+/// Descriptor of a simple 2D texture (no multisampling, no mip-levels), to be tweaked with struct update syntax.
+pub fn texture_descriptor(label: Option<&str>, width: u32, height: u32, format: wgpu::TextureFormat, usage: wgpu::TextureUsages) -> wgpu::TextureDescriptor<'_> {
+    wgpu::TextureDescriptor {
+        size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
         mip_level_count: 1,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8Unorm,
-        usage: usage,
-        label: label,
+        format,
+        usage,
+        label,
         view_formats: &[]
+    }
+}
+
+/// Create a simple 2D texture with Rgba8Unorm format (no multisampling, no mip-levels)
+pub fn create_texture(device: &wgpu::Device, label: Option<&str>, width: u32, height: u32, usage: wgpu::TextureUsages) -> wgpu::Texture {
+    device.create_texture(&texture_descriptor(label, width, height, wgpu::TextureFormat::Rgba8Unorm, usage))
+}
+
+/// Create a 2D texture which is only rendered to, such as a multisampled color target (resolved into a single sampled texture) or a depth buffer.
+pub fn create_attachment_texture(device: &wgpu::Device, label: Option<&str>, width: u32, height: u32, format: wgpu::TextureFormat, sample_count: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        sample_count,
+        ..texture_descriptor(label, width, height, format, wgpu::TextureUsages::RENDER_ATTACHMENT)
     })
 }
 
+/// Copy a texture with 4 bytes per pixel (such as Rgba8Unorm) to the CPU, with tightly packed rows. Blocks until the copy is done.
+///
+/// The texture must have the COPY_SRC usage.
+pub fn read_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Vec<u8> {
+    read_texture_region(device, queue, texture, 0, 0, texture.width(), texture.height())
+}
+
+/// Same as `read_texture` for a single pixel.
+pub fn read_texture_pixel(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, x: u32, y: u32) -> [u8; 4] {
+    let pixel = read_texture_region(device, queue, texture, x, y, 1, 1);
+    [pixel[0], pixel[1], pixel[2], pixel[3]]
+}
+
+/// Same as `read_texture` for a rectangle of the texture, of any uncompressed format (the data keeps the size of a pixel in that format).
+pub fn read_texture_region(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+    let bytes_per_row = width * texture.format().describe().block_size as u32;
+    // rows of buffers used in copies must be aligned
+    let padded_bytes_per_row = bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Texture readback buffer"),
+        size: padded_bytes_per_row as u64 * height as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Texture readback encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d { x, y, z: 0 },
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+        sender.send(result).unwrap();
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv().unwrap().expect("failed to map the texture readback buffer");
+
+    let mut pixels = Vec::with_capacity((bytes_per_row * height) as usize);
+    {
+        let data = buffer.slice(..).get_mapped_range();
+
+        for row in data.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..bytes_per_row as usize]);
+        }
+    }
+    buffer.unmap();
+
+    pixels
+}
+
+/// Optional parameters of `create_render_pipeline_with_options`, defaults match `create_render_pipeline`.
+#[derive(Clone, Copy, Debug)]
+pub struct RenderPipelineOptions {
+    /// Must match the sample count of the render target.
+    pub sample_count: u32,
+    /// Must be None for non blendable formats (such as 32 bits float ones).
+    pub blend: Option<wgpu::BlendState>,
+    /// Allows several fragment shaders in the same module.
+    pub fragment_entry_point: &'static str,
+    /// None to draw triangles of both windings (such as UI meshes).
+    pub cull_mode: Option<wgpu::Face>,
+    /// Depth tested and written with the Less comparison when set, the render pass must have a depth attachment of this format.
+    pub depth_format: Option<wgpu::TextureFormat>,
+}
+
+impl Default for RenderPipelineOptions {
+    fn default() -> Self {
+        Self {
+            sample_count: 1,
+            blend: Some(wgpu::BlendState::REPLACE),
+            fragment_entry_point: "fs_main",
+            cull_mode: Some(wgpu::Face::Back),
+            depth_format: None,
+        }
+    }
+}
+
 /// Create a render pipeline with "vs_main" as vertex shader entry point, and "fs_main" AS fragment shader entry point, and some other default parameters. No multisampling.
 pub fn create_render_pipeline(device: &wgpu::Device, label: Option<&str>, vertex_buffers: &[wgpu::VertexBufferLayout], pipeline_layout: &wgpu::PipelineLayout, shader_module: &wgpu::ShaderModule, target: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+    create_render_pipeline_with_options(device, label, vertex_buffers, pipeline_layout, shader_module, target, RenderPipelineOptions::default())
+}
+
+/// Same as `create_render_pipeline`, with some non default parameters.
+pub fn create_render_pipeline_with_options(device: &wgpu::Device, label: Option<&str>, vertex_buffers: &[wgpu::VertexBufferLayout], pipeline_layout: &wgpu::PipelineLayout, shader_module: &wgpu::ShaderModule, target: wgpu::TextureFormat, options: RenderPipelineOptions) -> wgpu::RenderPipeline {
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: label,
+        label,
         layout: Some(pipeline_layout),
         vertex: wgpu::VertexState {
             module: shader_module,
@@ -47,10 +162,10 @@ pub fn create_render_pipeline(device: &wgpu::Device, label: Option<&str>, vertex
         },
         fragment: Some(wgpu::FragmentState {
             module: shader_module,
-            entry_point: "fs_main",
+            entry_point: options.fragment_entry_point,
             targets: &[Some(wgpu::ColorTargetState {
                 format: target,
-                blend: Some(wgpu::BlendState::REPLACE),
+                blend: options.blend,
                 write_mask: wgpu::ColorWrites::ALL,
             })],
         }),
@@ -58,7 +173,7 @@ pub fn create_render_pipeline(device: &wgpu::Device, label: Option<&str>, vertex
             topology: wgpu::PrimitiveTopology::TriangleList,
             strip_index_format: None,
             front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
+            cull_mode: options.cull_mode,
             // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
             polygon_mode: wgpu::PolygonMode::Fill,
             // Requires Features::DEPTH_CLIP_CONTROL
@@ -66,9 +181,15 @@ pub fn create_render_pipeline(device: &wgpu::Device, label: Option<&str>, vertex
             // Requires Features::CONSERVATIVE_RASTERIZATION
             conservative: false,
         },
-        depth_stencil: None,
+        depth_stencil: options.depth_format.map(|format| wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: options.sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
@@ -76,51 +197,103 @@ pub fn create_render_pipeline(device: &wgpu::Device, label: Option<&str>, vertex
     })
 }
 
+/// Create a sampler clamping to edges, with the same filter for magnification, minification and mip-levels.
+pub fn create_sampler(device: &wgpu::Device, label: Option<&str>, filter: wgpu::FilterMode) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label,
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter: filter,
+        ..Default::default()
+    })
+}
+
 /// Create a bind group layout from a vector of bindings, with automatic binding indexes, and same visibility for every binding.
 pub fn create_bind_group_layout(device: &wgpu::Device, label: Option<&str>, bindings: Vec<wgpu::BindingType>, global_visiblity: wgpu::ShaderStages) -> wgpu::BindGroupLayout {
     let mut entries: Vec<wgpu::BindGroupLayoutEntry> = vec![];
-    let mut counter = 0;
 
-    for binding in bindings {
+    for (counter, binding) in bindings.into_iter().enumerate() {
         entries.push(wgpu::BindGroupLayoutEntry {
-            binding: counter,
+            binding: counter as u32,
             visibility: global_visiblity,
             ty: binding,
             count: None
         });
-
-        counter += 1;
     }
 
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         entries: &entries,
-        label: label,
+        label,
     })
 }
 
 pub fn create_bind_group(device: &wgpu::Device, label: Option<&str>, layout: &wgpu::BindGroupLayout, resources: Vec<wgpu::BindingResource>) -> wgpu::BindGroup {
     let mut entries: Vec<wgpu::BindGroupEntry> = vec![];
-    let mut counter = 0;
 
-    for resource in resources {
+    for (counter, resource) in resources.into_iter().enumerate() {
         entries.push(wgpu::BindGroupEntry {
-            binding: counter,
-            resource: resource,
+            binding: counter as u32,
+            resource,
         });
-
-        counter += 1;
     }
 
     device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: layout,
+        layout,
         entries: &entries,
-        label: label,
+        label,
     })
 }
 
+/// Element of the Halton low-discrepancy sequence for a given base, in [0; 1[. The index should start at 1.
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+
+    result
+}
+
+/// View any sized value as raw bytes, to upload it to a GPU buffer.
+///
+/// # Safety
+///
+/// `T` must not contain padding bytes nor pointers, and its layout must match what the shader expects (use `#[repr(C)]`).
 pub unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
     core::slice::from_raw_parts(
         (p as *const T) as *const u8,
         core::mem::size_of::<T>(),
     )
-}
\ No newline at end of file
+}
+/// Same as `any_as_u8_slice` for a slice of values.
+///
+/// # Safety
+///
+/// Same as `any_as_u8_slice`.
+pub unsafe fn slice_as_u8_slice<T: Sized>(p: &[T]) -> &[u8] {
+    core::slice::from_raw_parts(
+        p.as_ptr() as *const u8,
+        std::mem::size_of_val(p),
+    )
+}
+
+/// Device of the default adapter for the tests which need one, None when there is no adapter (such tests are then skipped).
+#[cfg(test)]
+pub(crate) fn test_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()));
+
+    let Some(adapter) = adapter else {
+        eprintln!("no adapter, skipping the test");
+        return None;
+    };
+
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()
+}